@@ -0,0 +1,164 @@
+use super::{Cursor, Engine, Entry, ReadTx, SyncMode, Tx};
+use crate::{Cli, TERABYTE};
+use lmdb::{Cursor as _, Transaction};
+use std::{
+    ffi::CString,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+pub struct LmdbEngine {
+    env: lmdb::Environment,
+    dbs: Vec<lmdb::Database>,
+}
+
+impl LmdbEngine {
+    pub fn open(cli: &Cli, readonly: bool) -> anyhow::Result<Self> {
+        // Unlike mdbx, LMDB does not create the directory itself.
+        std::fs::create_dir_all(&cli.path)?;
+        let mut flags = if readonly {
+            lmdb::EnvironmentFlags::READ_ONLY
+        } else {
+            lmdb::EnvironmentFlags::WRITE_MAP
+        };
+        match cli.sync_mode {
+            SyncMode::Durable => {}
+            // Only the meta page is not synced, a crash rolls back to the previous one.
+            SyncMode::SafeNoSync => flags |= lmdb::EnvironmentFlags::NO_META_SYNC,
+            SyncMode::UtterlyNoSync | SyncMode::Periodic(_) => {
+                flags |= lmdb::EnvironmentFlags::NO_SYNC | lmdb::EnvironmentFlags::NO_META_SYNC
+            }
+        }
+        let env = lmdb::Environment::new()
+            .set_flags(flags)
+            .set_max_dbs(cli.tables as u32)
+            // LMDB has no growth step, the map is reserved upfront. Match the mdbx maximum.
+            .set_map_size(4 * TERABYTE)
+            .open(&PathBuf::from(&cli.path))?;
+        let dbs = (0..cli.tables)
+            .map(|t| {
+                let name = super::table_name(cli, t);
+                if readonly {
+                    env.open_db(name.as_deref())
+                } else {
+                    env.create_db(name.as_deref(), lmdb::DatabaseFlags::empty())
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(LmdbEngine { env, dbs })
+    }
+}
+
+impl Engine for LmdbEngine {
+    fn begin(&self) -> anyhow::Result<Box<dyn Tx + '_>> {
+        Ok(Box::new(LmdbTx {
+            txn: self.env.begin_rw_txn()?,
+            dbs: &self.dbs,
+        }))
+    }
+
+    fn begin_read(&self) -> anyhow::Result<Box<dyn ReadTx + '_>> {
+        Ok(Box::new(LmdbTx {
+            txn: self.env.begin_ro_txn()?,
+            dbs: &self.dbs,
+        }))
+    }
+
+    fn sync(&self) -> anyhow::Result<()> {
+        self.env.sync(true)?;
+        Ok(())
+    }
+
+    fn backup(&self, dest: &Path, compact: bool) -> anyhow::Result<()> {
+        std::fs::create_dir(dest)?;
+        let dest = CString::new(dest.as_os_str().as_bytes())?;
+        let flags = if compact { lmdb_sys::MDB_CP_COMPACT } else { 0 };
+        let rc = unsafe { lmdb_sys::mdb_env_copy2(self.env.env(), dest.as_ptr(), flags) };
+        if rc != 0 {
+            return Err(lmdb::Error::from_err_code(rc).into());
+        }
+        Ok(())
+    }
+}
+
+struct LmdbTx<'a, T: Transaction> {
+    txn: T,
+    dbs: &'a [lmdb::Database],
+}
+
+impl<T: Transaction> ReadTx for LmdbTx<'_, T> {
+    fn get(&self, table: usize, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        match self.txn.get(self.dbs[table], &key) {
+            Ok(value) => Ok(Some(value.to_vec())),
+            Err(lmdb::Error::NotFound) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn cursor(&self, table: usize) -> anyhow::Result<Box<dyn Cursor + '_>> {
+        let cursor = self.txn.open_ro_cursor(self.dbs[table])?;
+        Ok(Box::new(LmdbCursor(cursor)))
+    }
+}
+
+impl Tx for LmdbTx<'_, lmdb::RwTransaction<'_>> {
+    fn put(&mut self, table: usize, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.txn
+            .put(self.dbs[table], key, value, lmdb::WriteFlags::empty())?;
+        Ok(())
+    }
+
+    fn delete(&mut self, table: usize, key: &[u8]) -> anyhow::Result<()> {
+        match self.txn.del(self.dbs[table], &key, None) {
+            Ok(()) | Err(lmdb::Error::NotFound) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn commit(self: Box<Self>) -> anyhow::Result<()> {
+        self.txn.commit()?;
+        Ok(())
+    }
+}
+
+struct LmdbCursor<'a>(lmdb::RoCursor<'a>);
+
+impl LmdbCursor<'_> {
+    fn get(&self, key: Option<&[u8]>, op: u32) -> anyhow::Result<Option<Entry>> {
+        match self.0.get(key, None, op) {
+            Ok((key, value)) => Ok(Some((key.unwrap_or_default().to_vec(), value.to_vec()))),
+            Err(lmdb::Error::NotFound) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl Cursor for LmdbCursor<'_> {
+    fn first(&mut self) -> anyhow::Result<Option<Entry>> {
+        self.get(None, lmdb_sys::MDB_FIRST)
+    }
+
+    fn last(&mut self) -> anyhow::Result<Option<Entry>> {
+        self.get(None, lmdb_sys::MDB_LAST)
+    }
+
+    fn seek(&mut self, key: &[u8]) -> anyhow::Result<Option<Entry>> {
+        self.get(Some(key), lmdb_sys::MDB_SET_RANGE)
+    }
+
+    fn seek_for_prev(&mut self, key: &[u8]) -> anyhow::Result<Option<Entry>> {
+        match self.get(Some(key), lmdb_sys::MDB_SET_RANGE)? {
+            Some((found, value)) if found == key => Ok(Some((found, value))),
+            Some(_) => self.get(None, lmdb_sys::MDB_PREV),
+            None => self.get(None, lmdb_sys::MDB_LAST),
+        }
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Entry>> {
+        self.get(None, lmdb_sys::MDB_NEXT)
+    }
+
+    fn prev(&mut self) -> anyhow::Result<Option<Entry>> {
+        self.get(None, lmdb_sys::MDB_PREV)
+    }
+}
@@ -0,0 +1,396 @@
+use super::{Cursor, Engine, Entry, GcStat, OnDuplicate, ReadTx, SyncMode, Tx};
+use crate::{Cli, GIGABYTE, TERABYTE};
+use reth_libmdbx::{
+    Database, DatabaseFlags, Environment, EnvironmentFlags, Geometry, Mode, PageSize, Transaction,
+    TransactionKind, WriteFlags, RW,
+};
+use std::{
+    ffi::CString,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+/// The table of the dupsort workload.
+const DUPS: &str = "dups";
+
+pub struct MdbxEngine {
+    env: Environment,
+    tables: Vec<Option<String>>,
+}
+
+impl MdbxEngine {
+    /// Opens the environment, read-only without the writemap and the geometry, which only a
+    /// writer may change.
+    pub fn open(cli: &Cli, readonly: bool) -> anyhow::Result<Self> {
+        let tuning = &cli.mdbx;
+        let mut builder = Environment::builder();
+        builder.set_max_dbs(tuning.max_dbs);
+        if !tuning.no_writemap && !readonly {
+            builder.write_map();
+        }
+        // The tables and the dupsort table.
+        if cli.tables + 1 > tuning.max_dbs {
+            anyhow::bail!(
+                "{} tables need --mdbx-max-dbs {} at least",
+                cli.tables,
+                cli.tables + 1
+            );
+        }
+        if let Some(max_readers) = tuning.max_readers {
+            builder.set_max_readers(max_readers);
+        }
+        let mode = if readonly {
+            Mode::ReadOnly
+        } else {
+            Mode::ReadWrite {
+                sync_mode: match cli.sync_mode {
+                    SyncMode::Durable => reth_libmdbx::SyncMode::Durable,
+                    SyncMode::SafeNoSync | SyncMode::Periodic(_) => {
+                        reth_libmdbx::SyncMode::SafeNoSync
+                    }
+                    SyncMode::UtterlyNoSync => reth_libmdbx::SyncMode::UtterlyNoSync,
+                },
+            }
+        };
+        builder.set_flags(EnvironmentFlags {
+            mode,
+            ..Default::default()
+        });
+        if !readonly {
+            builder.set_geometry(Geometry {
+                // Maximum database size of 4 terabytes by default
+                size: Some(0..tuning.max_size.unwrap_or(4 * TERABYTE)),
+                // We grow the database in increments of 1 gigabytes by default
+                growth_step: Some(tuning.growth_step.unwrap_or(GIGABYTE) as isize),
+                // The database never shrinks
+                shrink_threshold: Some(0),
+                page_size: Some(PageSize::Set(tuning.page_size)),
+            });
+        }
+        let env = builder.open(&PathBuf::from(&cli.path))?;
+
+        // Create the tables upfront, read transactions can't do it.
+        let tables: Vec<_> = (0..cli.tables).map(|t| super::table_name(cli, t)).collect();
+        if !readonly {
+            let txn = env.begin_rw_txn()?;
+            for name in &tables {
+                txn.create_db(name.as_deref(), DatabaseFlags::CREATE)?;
+            }
+            txn.commit()?;
+        }
+        Ok(MdbxEngine { env, tables })
+    }
+
+    fn open_tx<K: TransactionKind>(&self, txn: Transaction<K>) -> anyhow::Result<MdbxTx<'_, K>> {
+        let dbs = self
+            .tables
+            .iter()
+            .map(|name| txn.open_db(name.as_deref()))
+            .collect::<Result<_, _>>()?;
+        // Only the dupsort workload creates the table. Named tables are entries of the main
+        // database, which is table 0 without `--tables`, and the others mustn't find it there.
+        let dups = match txn.open_db(Some(DUPS)) {
+            Ok(dups) => Some(dups),
+            Err(reth_libmdbx::Error::NotFound) => None,
+            Err(err) => return Err(err.into()),
+        };
+        Ok(MdbxTx {
+            engine: self,
+            txn,
+            dbs,
+            dups,
+        })
+    }
+}
+
+impl Engine for MdbxEngine {
+    fn begin(&self) -> anyhow::Result<Box<dyn Tx + '_>> {
+        Ok(Box::new(self.open_tx(self.env.begin_rw_txn()?)?))
+    }
+
+    fn begin_read(&self) -> anyhow::Result<Box<dyn ReadTx + '_>> {
+        Ok(Box::new(self.open_tx(self.env.begin_ro_txn()?)?))
+    }
+
+    fn sync(&self) -> anyhow::Result<()> {
+        self.env.sync(true)?;
+        Ok(())
+    }
+
+    fn print_stat(&self) -> anyhow::Result<String> {
+        let tx = self.open_tx(self.env.begin_ro_txn()?)?;
+        let mut out = format!("env:\n{}", self.diagnostics()?);
+        let tables = self
+            .tables
+            .iter()
+            .map(|name| name.as_deref().unwrap_or("main"));
+        for (name, db) in tables.chain([DUPS]).zip(tx.dbs.iter().chain(&tx.dups)) {
+            let stat = tx.txn.db_stat(db)?;
+            out += &format!(
+                "{}:\n  entries: {}\n  depth: {}\n  pages: {} branch, {} leaf, {} overflow\n",
+                name,
+                stat.entries(),
+                stat.depth(),
+                stat.branch_pages(),
+                stat.leaf_pages(),
+                stat.overflow_pages()
+            );
+        }
+        Ok(out)
+    }
+
+    fn diagnostics(&self) -> anyhow::Result<String> {
+        let info = self.env.info()?;
+        let stat = self.env.stat()?;
+        Ok(format!(
+            "  map size: {} bytes\n  last page: {}\n  last txn: {}\n  readers: {}\n  \
+             freelist: {} pages\n  depth: {}\n  pages: {} branch, {} leaf, {} overflow\n  \
+             page ops: {:?}\n",
+            info.map_size(),
+            info.last_pgno(),
+            info.last_txnid(),
+            info.num_readers(),
+            self.env.freelist()?,
+            stat.depth(),
+            stat.branch_pages(),
+            stat.leaf_pages(),
+            stat.overflow_pages(),
+            info.page_ops()
+        ))
+    }
+
+    fn backup(&self, dest: &Path, compact: bool) -> anyhow::Result<()> {
+        // The copy is a single file, put it where an environment in `dest` expects it.
+        std::fs::create_dir(dest)?;
+        let file = CString::new(dest.join("mdbx.dat").as_os_str().as_bytes())?;
+        let flags = if compact {
+            reth_libmdbx::ffi::MDBX_CP_COMPACT
+        } else {
+            reth_libmdbx::ffi::MDBX_CP_DEFAULTS
+        };
+        let rc = self.env.with_raw_env_ptr(|env| unsafe {
+            reth_libmdbx::ffi::mdbx_env_copy(env, file.as_ptr(), flags)
+        });
+        if rc != 0 {
+            return Err(reth_libmdbx::Error::from_err_code(rc).into());
+        }
+        Ok(())
+    }
+
+    /// The pages up to the last allocated one minus the free ones. The file itself is as large
+    /// as the geometry grew it.
+    fn used_bytes(&self) -> anyhow::Result<Option<u64>> {
+        let info = self.env.info()?;
+        let page_size = self.env.stat()?.page_size() as u64;
+        let pages = info.last_pgno() as u64 + 1 - self.env.freelist()? as u64;
+        Ok(Some(pages * page_size))
+    }
+
+    /// The page operations and the oldest reader are in the lock file, shared by all processes
+    /// of the environment.
+    fn gc_stat(&self) -> anyhow::Result<Option<GcStat>> {
+        // The oldest reader is not exposed by the bindings.
+        let mut info: reth_libmdbx::ffi::MDBX_envinfo = unsafe { std::mem::zeroed() };
+        let rc = self.env.with_raw_env_ptr(|env| unsafe {
+            reth_libmdbx::ffi::mdbx_env_info_ex(
+                env,
+                std::ptr::null(),
+                &mut info,
+                std::mem::size_of::<reth_libmdbx::ffi::MDBX_envinfo>(),
+            )
+        });
+        if rc != 0 {
+            return Err(reth_libmdbx::Error::from_err_code(rc).into());
+        }
+        Ok(Some(GcStat {
+            free_pages: self.env.freelist()? as u64,
+            allocated: info.mi_pgop_stat.newly,
+            last_page: info.mi_last_pgno,
+            reader_lag: info
+                .mi_recent_txnid
+                .saturating_sub(info.mi_latter_reader_txnid),
+        }))
+    }
+
+    /// Grows by the geometry growth step whenever a commit runs out of pages.
+    fn map_size(&self) -> anyhow::Result<Option<u64>> {
+        Ok(Some(self.env.info()?.map_size() as u64))
+    }
+}
+
+struct MdbxTx<'a, K: TransactionKind> {
+    engine: &'a MdbxEngine,
+    txn: Transaction<K>,
+    dbs: Vec<Database>,
+    /// `None` until the first dupsort write creates the table, reads find no dups until then.
+    dups: Option<Database>,
+}
+
+impl<K: TransactionKind> ReadTx for MdbxTx<'_, K> {
+    fn get(&self, table: usize, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(self.txn.get(self.dbs[table].dbi(), key)?)
+    }
+
+    fn cursor(&self, table: usize) -> anyhow::Result<Box<dyn Cursor + '_>> {
+        Ok(Box::new(MdbxCursor(self.txn.cursor(&self.dbs[table])?)))
+    }
+
+    /// Looks the keys up in key order with one cursor per table, so that neighbouring keys reuse
+    /// the pages the cursor already stands on.
+    fn multi_get(&self, keys: &[(usize, &[u8])]) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        order.sort_unstable_by_key(|&i| keys[i]);
+        let mut values = vec![None; keys.len()];
+        let mut open = None;
+        for i in order {
+            let (table, key) = keys[i];
+            let cursor = match &mut open {
+                Some((current, cursor)) if *current == table => cursor,
+                open => &mut open.insert((table, self.txn.cursor(&self.dbs[table])?)).1,
+            };
+            values[i] = cursor.set::<Vec<u8>>(key)?;
+        }
+        Ok(values)
+    }
+
+    fn dups(&self, key: &[u8]) -> anyhow::Result<Vec<Vec<u8>>> {
+        let Some(db) = &self.dups else {
+            return Ok(Vec::new());
+        };
+        let mut cursor = self.txn.cursor(db)?;
+        let mut dups = Vec::new();
+        let mut value = cursor.set::<Vec<u8>>(key)?;
+        while let Some(found) = value {
+            dups.push(found);
+            value = cursor
+                .next_dup::<Vec<u8>, Vec<u8>>()?
+                .map(|(_, value)| value);
+        }
+        Ok(dups)
+    }
+}
+
+impl Tx for MdbxTx<'_, RW> {
+    fn put(&mut self, table: usize, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.txn
+            .put(self.dbs[table].dbi(), key, value, WriteFlags::empty())?;
+        Ok(())
+    }
+
+    /// With `MDBX_APPEND`, which fails unless the key is the greatest.
+    fn append(&mut self, table: usize, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.txn
+            .put(self.dbs[table].dbi(), key, value, WriteFlags::APPEND)?;
+        Ok(())
+    }
+
+    fn delete(&mut self, table: usize, key: &[u8]) -> anyhow::Result<()> {
+        self.txn.del(self.dbs[table].dbi(), key, None)?;
+        Ok(())
+    }
+
+    /// Deletes under a single cursor, which moves on to the following entry with every delete.
+    fn delete_range(&mut self, table: usize, start: &[u8], end: &[u8]) -> anyhow::Result<()> {
+        let mut cursor = self.txn.cursor(&self.dbs[table])?;
+        let mut entry = cursor.set_range::<Vec<u8>, ()>(start)?;
+        while let Some((key, ())) = entry {
+            if key.as_slice() >= end {
+                break;
+            }
+            cursor.del(WriteFlags::empty())?;
+            entry = cursor.next()?;
+        }
+        Ok(())
+    }
+
+    /// With `MDBX_NOOVERWRITE` instead of a lookup, unless upserting.
+    fn insert(
+        &mut self,
+        table: usize,
+        key: &[u8],
+        value: &[u8],
+        on_duplicate: OnDuplicate,
+    ) -> anyhow::Result<bool> {
+        if let OnDuplicate::Upsert = on_duplicate {
+            self.put(table, key, value)?;
+            return Ok(true);
+        }
+        let dbi = self.dbs[table].dbi();
+        match self.txn.put(dbi, key, value, WriteFlags::NO_OVERWRITE) {
+            Ok(()) => Ok(true),
+            Err(reth_libmdbx::Error::KeyExist) => match on_duplicate {
+                OnDuplicate::Error => anyhow::bail!("Key {:02x?} already exists", key),
+                _ => Ok(false),
+            },
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn put_dup(&mut self, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        let dbi = match &self.dups {
+            Some(db) => db.dbi(),
+            None => {
+                let flags = DatabaseFlags::CREATE | DatabaseFlags::DUP_SORT;
+                let db = self.txn.create_db(Some(DUPS), flags)?;
+                self.dups.insert(db).dbi()
+            }
+        };
+        self.txn.put(dbi, key, value, WriteFlags::empty())?;
+        Ok(())
+    }
+
+    /// Not with the writemap, which mdbx can't combine with nested transactions. The tables are
+    /// opened again in the child.
+    fn nested(&mut self) -> anyhow::Result<Box<dyn Tx + '_>> {
+        let txn = self.txn.begin_nested_txn()?;
+        Ok(Box::new(self.engine.open_tx(txn)?))
+    }
+
+    fn commit(self: Box<Self>) -> anyhow::Result<()> {
+        let (_, latency) = self.txn.commit()?;
+        let us = |lat: std::time::Duration| lat.as_micros() as u64;
+        super::set_commit_info(vec![
+            ("mdbx_preparation_us", us(latency.preparation())),
+            ("mdbx_gc_wallclock_us", us(latency.gc_wallclock())),
+            ("mdbx_gc_cputime_us", us(latency.gc_cputime())),
+            ("mdbx_audit_us", us(latency.audit())),
+            ("mdbx_write_us", us(latency.write())),
+            ("mdbx_sync_us", us(latency.sync())),
+            ("mdbx_ending_us", us(latency.ending())),
+            ("mdbx_whole_us", us(latency.whole())),
+        ]);
+        Ok(())
+    }
+}
+
+struct MdbxCursor<K: TransactionKind>(reth_libmdbx::Cursor<K>);
+
+impl<K: TransactionKind> Cursor for MdbxCursor<K> {
+    fn first(&mut self) -> anyhow::Result<Option<Entry>> {
+        Ok(self.0.first()?)
+    }
+
+    fn last(&mut self) -> anyhow::Result<Option<Entry>> {
+        Ok(self.0.last()?)
+    }
+
+    fn seek(&mut self, key: &[u8]) -> anyhow::Result<Option<Entry>> {
+        Ok(self.0.set_range(key)?)
+    }
+
+    fn seek_for_prev(&mut self, key: &[u8]) -> anyhow::Result<Option<Entry>> {
+        match self.0.set_range::<Vec<u8>, Vec<u8>>(key)? {
+            Some((found, value)) if found == key => Ok(Some((found, value))),
+            Some(_) => Ok(self.0.prev()?),
+            None => Ok(self.0.last()?),
+        }
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Entry>> {
+        Ok(self.0.next()?)
+    }
+
+    fn prev(&mut self) -> anyhow::Result<Option<Entry>> {
+        Ok(self.0.prev()?)
+    }
+}
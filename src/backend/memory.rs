@@ -0,0 +1,325 @@
+//! An engine keeping every table in a `BTreeMap`, as an upper bound for the others and a way to
+//! run the workloads without a real engine. Nothing is persisted.
+//!
+//! A write transaction holds the single writer lock and buffers its writes, which it applies to
+//! the tables on commit. Reads see the latest commit at the time of every call, not a snapshot.
+
+use super::{Cursor, Engine, Entry, ReadTx, Tx};
+use crate::Cli;
+use std::{
+    collections::BTreeMap,
+    ops::Bound,
+    sync::{Mutex, MutexGuard, RwLock},
+};
+
+type Table = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// The writes of a transaction to a table, `None` for a deleted key.
+type Writes = BTreeMap<Vec<u8>, Option<Vec<u8>>>;
+
+struct State {
+    tables: Vec<Table>,
+    /// The bytes of all keys and values.
+    bytes: u64,
+}
+
+pub struct MemoryEngine {
+    state: RwLock<State>,
+    writer: Mutex<()>,
+}
+
+impl MemoryEngine {
+    pub fn open(cli: &Cli) -> anyhow::Result<Self> {
+        // Empty, but the size of the database is measured on the directory.
+        std::fs::create_dir_all(&cli.path)?;
+        Ok(MemoryEngine {
+            state: RwLock::new(State {
+                tables: (0..cli.tables).map(|_| Table::new()).collect(),
+                bytes: 0,
+            }),
+            writer: Mutex::new(()),
+        })
+    }
+}
+
+impl Engine for MemoryEngine {
+    fn begin(&self) -> anyhow::Result<Box<dyn Tx + '_>> {
+        let writer = self.writer.lock().unwrap();
+        let tables = self.state.read().unwrap().tables.len();
+        Ok(Box::new(MemoryTx {
+            engine: self,
+            writes: (0..tables).map(|_| Writes::new()).collect(),
+            _writer: Some(writer),
+        }))
+    }
+
+    fn begin_read(&self) -> anyhow::Result<Box<dyn ReadTx + '_>> {
+        Ok(Box::new(MemoryTx {
+            engine: self,
+            writes: Vec::new(),
+            _writer: None,
+        }))
+    }
+
+    fn sync(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn print_stat(&self) -> anyhow::Result<String> {
+        let state = self.state.read().unwrap();
+        let entries: usize = state.tables.iter().map(Table::len).sum();
+        Ok(format!("entries: {}\nbytes: {}", entries, state.bytes))
+    }
+
+    fn used_bytes(&self) -> anyhow::Result<Option<u64>> {
+        Ok(Some(self.state.read().unwrap().bytes))
+    }
+}
+
+/// A read transaction has no writes and no writer lock.
+struct MemoryTx<'a> {
+    engine: &'a MemoryEngine,
+    writes: Vec<Writes>,
+    _writer: Option<MutexGuard<'a, ()>>,
+}
+
+impl ReadTx for MemoryTx<'_> {
+    fn get(&self, table: usize, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        if let Some(written) = self.writes.get(table).and_then(|writes| writes.get(key)) {
+            return Ok(written.clone());
+        }
+        let state = self.engine.state.read().unwrap();
+        Ok(state.tables[table].get(key).cloned())
+    }
+
+    fn cursor(&self, table: usize) -> anyhow::Result<Box<dyn Cursor + '_>> {
+        Ok(Box::new(MemoryCursor {
+            tx: self,
+            table,
+            position: None,
+        }))
+    }
+}
+
+impl Tx for MemoryTx<'_> {
+    fn put(&mut self, table: usize, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.writes[table].insert(key.to_vec(), Some(value.to_vec()));
+        Ok(())
+    }
+
+    fn delete(&mut self, table: usize, key: &[u8]) -> anyhow::Result<()> {
+        self.writes[table].insert(key.to_vec(), None);
+        Ok(())
+    }
+
+    fn commit(self: Box<Self>) -> anyhow::Result<()> {
+        let mut state = self.engine.state.write().unwrap();
+        let State { tables, bytes } = &mut *state;
+        for (table, writes) in tables.iter_mut().zip(self.writes) {
+            for (key, value) in writes {
+                let len = key.len() as u64;
+                let old = match value {
+                    Some(value) => {
+                        *bytes += len + value.len() as u64;
+                        table.insert(key, value)
+                    }
+                    None => table.remove(&key),
+                };
+                if let Some(old) = old {
+                    *bytes -= len + old.len() as u64;
+                }
+            }
+        }
+        // Nothing to tell, but the info of an earlier commit on this thread mustn't stick.
+        super::set_commit_info(Vec::new());
+        Ok(())
+    }
+}
+
+/// Remembers the key it is at and looks up its neighbors on every move, so that it holds no lock
+/// between calls.
+struct MemoryCursor<'a> {
+    tx: &'a MemoryTx<'a>,
+    table: usize,
+    position: Option<Vec<u8>>,
+}
+
+impl MemoryCursor<'_> {
+    /// Moves to the first entry within the bounds, the lowest one if `forward` and the highest one
+    /// otherwise.
+    fn find(
+        &mut self,
+        bounds: (Bound<&[u8]>, Bound<&[u8]>),
+        forward: bool,
+    ) -> anyhow::Result<Option<Entry>> {
+        let state = self.tx.engine.state.read().unwrap();
+        let table = state.tables[self.table].range::<[u8], _>(bounds);
+        let no_writes = Writes::new();
+        let writes = self.tx.writes.get(self.table).unwrap_or(&no_writes);
+        let writes = writes.range::<[u8], _>(bounds);
+        let found = if forward {
+            first(table, writes, forward)
+        } else {
+            first(table.rev(), writes.rev(), forward)
+        };
+        self.position = found.as_ref().map(|(key, _)| key.clone());
+        Ok(found)
+    }
+
+    /// Moves past the current entry, or stays off the end if the cursor ran off it.
+    fn step(&mut self, forward: bool) -> anyhow::Result<Option<Entry>> {
+        let Some(position) = self.position.take() else {
+            return Ok(None);
+        };
+        let bounds = if forward {
+            (Bound::Excluded(&position[..]), Bound::Unbounded)
+        } else {
+            (Bound::Unbounded, Bound::Excluded(&position[..]))
+        };
+        self.find(bounds, forward)
+    }
+}
+
+/// Returns the first entry of a table with the writes of a transaction applied, both iterated in
+/// the same order.
+fn first<'a>(
+    table: impl Iterator<Item = (&'a Vec<u8>, &'a Vec<u8>)>,
+    writes: impl Iterator<Item = (&'a Vec<u8>, &'a Option<Vec<u8>>)>,
+    forward: bool,
+) -> Option<Entry> {
+    let mut table = table.peekable();
+    for (key, value) in writes {
+        if let Some((found, found_value)) = table.peek() {
+            if (forward && *found < key) || (!forward && *found > key) {
+                return Some(((*found).clone(), (*found_value).clone()));
+            }
+            if *found == key {
+                table.next();
+            }
+        }
+        // Deleted keys are skipped, along with the entry of the table they replace.
+        if let Some(value) = value {
+            return Some((key.clone(), value.clone()));
+        }
+    }
+    table
+        .next()
+        .map(|(key, value)| (key.clone(), value.clone()))
+}
+
+impl Cursor for MemoryCursor<'_> {
+    fn first(&mut self) -> anyhow::Result<Option<Entry>> {
+        self.find((Bound::Unbounded, Bound::Unbounded), true)
+    }
+
+    fn last(&mut self) -> anyhow::Result<Option<Entry>> {
+        self.find((Bound::Unbounded, Bound::Unbounded), false)
+    }
+
+    fn seek(&mut self, key: &[u8]) -> anyhow::Result<Option<Entry>> {
+        self.find((Bound::Included(key), Bound::Unbounded), true)
+    }
+
+    fn seek_for_prev(&mut self, key: &[u8]) -> anyhow::Result<Option<Entry>> {
+        self.find((Bound::Unbounded, Bound::Included(key)), false)
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Entry>> {
+        self.step(true)
+    }
+
+    fn prev(&mut self) -> anyhow::Result<Option<Entry>> {
+        self.step(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine(tables: usize) -> MemoryEngine {
+        MemoryEngine {
+            state: RwLock::new(State {
+                tables: (0..tables).map(|_| Table::new()).collect(),
+                bytes: 0,
+            }),
+            writer: Mutex::new(()),
+        }
+    }
+
+    fn entry(key: &[u8], value: &[u8]) -> Option<Entry> {
+        Some((key.to_vec(), value.to_vec()))
+    }
+
+    #[test]
+    fn cursor_merges_the_writes_of_the_transaction() -> anyhow::Result<()> {
+        let engine = engine(1);
+        let mut txn = engine.begin()?;
+        for key in [b"a", b"c", b"e"] {
+            txn.put(0, key, b"old")?;
+        }
+        txn.commit()?;
+
+        let mut txn = engine.begin()?;
+        txn.put(0, b"b", b"new")?;
+        txn.put(0, b"c", b"new")?;
+        txn.delete(0, b"a")?;
+        let mut cursor = txn.cursor(0)?;
+        assert_eq!(cursor.first()?, entry(b"b", b"new"));
+        assert_eq!(cursor.next()?, entry(b"c", b"new"));
+        assert_eq!(cursor.next()?, entry(b"e", b"old"));
+        assert_eq!(cursor.next()?, None);
+        assert_eq!(cursor.next()?, None);
+        assert_eq!(cursor.seek(b"d")?, entry(b"e", b"old"));
+        assert_eq!(cursor.seek(b"a")?, entry(b"b", b"new"));
+        assert_eq!(cursor.prev()?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn commit_applies_the_writes_and_counts_the_bytes() -> anyhow::Result<()> {
+        let engine = engine(2);
+        let mut txn = engine.begin()?;
+        txn.put(0, b"a", b"value")?;
+        txn.put(1, b"b", b"value")?;
+        txn.commit()?;
+
+        let txn = engine.begin()?;
+        txn.commit()?;
+        let mut txn = engine.begin()?;
+        txn.put(0, b"a", b"v")?;
+        txn.delete(1, b"b")?;
+        assert_eq!(engine.used_bytes()?, Some(12));
+        txn.commit()?;
+
+        let txn = engine.begin_read()?;
+        assert_eq!(txn.get(0, b"a")?, Some(b"v".to_vec()));
+        assert_eq!(txn.get(1, b"b")?, None);
+        assert_eq!(engine.used_bytes()?, Some(2));
+        Ok(())
+    }
+
+    #[test]
+    fn delete_range_deletes_up_to_the_end() -> anyhow::Result<()> {
+        let engine = engine(2);
+        let mut txn = engine.begin()?;
+        for key in [b"a", b"b", b"c", b"d"] {
+            txn.put(0, key, b"value")?;
+            txn.put(1, key, b"value")?;
+        }
+        txn.commit()?;
+
+        let mut txn = engine.begin()?;
+        txn.delete_range(0, b"b", b"d")?;
+        assert_eq!(txn.get(0, b"b")?, None);
+        txn.commit()?;
+
+        let txn = engine.begin_read()?;
+        let mut cursor = txn.cursor(0)?;
+        assert_eq!(cursor.first()?, entry(b"a", b"value"));
+        assert_eq!(cursor.next()?, entry(b"d", b"value"));
+        assert_eq!(cursor.next()?, None);
+        assert_eq!(txn.get(1, b"c")?, Some(b"value".to_vec()));
+        Ok(())
+    }
+}
@@ -0,0 +1,806 @@
+//! The storage engines under torture.
+//!
+//! Every engine lives in its own module behind a cargo feature of the same name and only has to
+//! implement the [`Engine`], [`Tx`] and [`Cursor`] traits to be usable by all the workloads.
+
+use crate::Cli;
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+/// Implements the named methods of [`Engine`] by calling them on `self.engine`, so that the
+/// wrappers of an engine only spell out the methods they change.
+macro_rules! forward_engine {
+    ($($method:ident),* $(,)?) => {
+        $(forward_engine!(@ $method);)*
+    };
+    (@ begin) => {
+        fn begin(&self) -> anyhow::Result<Box<dyn $crate::backend::Tx + '_>> {
+            self.engine.begin()
+        }
+    };
+    (@ begin_read) => {
+        fn begin_read(&self) -> anyhow::Result<Box<dyn $crate::backend::ReadTx + '_>> {
+            self.engine.begin_read()
+        }
+    };
+    (@ sync) => {
+        fn sync(&self) -> anyhow::Result<()> {
+            self.engine.sync()
+        }
+    };
+    (@ print_stat) => {
+        fn print_stat(&self) -> anyhow::Result<String> {
+            self.engine.print_stat()
+        }
+    };
+    (@ diagnostics) => {
+        fn diagnostics(&self) -> anyhow::Result<String> {
+            self.engine.diagnostics()
+        }
+    };
+    (@ used_bytes) => {
+        fn used_bytes(&self) -> anyhow::Result<Option<u64>> {
+            self.engine.used_bytes()
+        }
+    };
+    (@ map_size) => {
+        fn map_size(&self) -> anyhow::Result<Option<u64>> {
+            self.engine.map_size()
+        }
+    };
+    (@ gc_stat) => {
+        fn gc_stat(&self) -> anyhow::Result<Option<$crate::backend::GcStat>> {
+            self.engine.gc_stat()
+        }
+    };
+    (@ ingest) => {
+        fn ingest(&self, table: usize, entries: &[$crate::backend::Entry]) -> anyhow::Result<()> {
+            self.engine.ingest(table, entries)
+        }
+    };
+    (@ write_file) => {
+        fn write_file(
+            &self,
+            table: usize,
+            entries: &[$crate::backend::Entry],
+            dest: &std::path::Path,
+        ) -> anyhow::Result<()> {
+            self.engine.write_file(table, entries, dest)
+        }
+    };
+    (@ ingest_files) => {
+        fn ingest_files(&self, table: usize, files: &[std::path::PathBuf]) -> anyhow::Result<()> {
+            self.engine.ingest_files(table, files)
+        }
+    };
+    (@ engine_written) => {
+        fn engine_written(&self) -> anyhow::Result<Option<u64>> {
+            self.engine.engine_written()
+        }
+    };
+    (@ compact) => {
+        fn compact(&self) -> anyhow::Result<()> {
+            self.engine.compact()
+        }
+    };
+    (@ compaction_report) => {
+        fn compaction_report(&self) -> anyhow::Result<String> {
+            self.engine.compaction_report()
+        }
+    };
+    (@ backup) => {
+        fn backup(&self, dest: &std::path::Path, compact: bool) -> anyhow::Result<()> {
+            self.engine.backup(dest, compact)
+        }
+    };
+}
+
+#[cfg(feature = "lmdb")]
+mod lmdb;
+#[cfg(feature = "mdbx")]
+mod mdbx;
+mod memory;
+mod null;
+mod readback;
+#[cfg(feature = "redb")]
+mod redb;
+#[cfg(feature = "rocksdb")]
+mod rocksdb;
+#[cfg(feature = "rocksdb")]
+mod rocksdb_txn;
+#[cfg(feature = "sled")]
+mod sled;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+pub mod trace;
+
+pub type Entry = (Vec<u8>, Vec<u8>);
+
+thread_local! {
+    /// Where the last commit of this thread spent its time, see [`last_commit_info`].
+    static COMMIT_INFO: RefCell<Vec<(&'static str, u64)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Returns the breakdown of the last commit on this thread into engine internal counters, like
+/// the time spent syncing in microseconds. Empty for engines that don't tell.
+pub fn last_commit_info() -> Vec<(&'static str, u64)> {
+    COMMIT_INFO.with(|info| info.borrow().clone())
+}
+
+fn set_commit_info(info: Vec<(&'static str, u64)>) {
+    COMMIT_INFO.with(|cell| *cell.borrow_mut() = info);
+}
+
+/// Adds a counter to the info of the last commit, for wrappers of the engines.
+fn add_commit_info(name: &'static str, value: u64) {
+    COMMIT_INFO.with(|cell| {
+        let mut info = cell.borrow_mut();
+        info.retain(|(n, _)| *n != name);
+        info.push((name, value));
+    });
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum EngineKind {
+    Mdbx,
+    Rocksdb,
+    /// rocksdb with pessimistic or optimistic transactions instead of write batches.
+    RocksdbTxn,
+    Sled,
+    Lmdb,
+    Redb,
+    Sqlite,
+    /// `BTreeMap`s in memory, persisting nothing.
+    Memory,
+}
+
+impl FromStr for EngineKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mdbx" => Ok(EngineKind::Mdbx),
+            "rocksdb" | "rdb" => Ok(EngineKind::Rocksdb),
+            "rocksdb-txn" => Ok(EngineKind::RocksdbTxn),
+            "sled" => Ok(EngineKind::Sled),
+            "lmdb" => Ok(EngineKind::Lmdb),
+            "redb" => Ok(EngineKind::Redb),
+            "sqlite" => Ok(EngineKind::Sqlite),
+            "memory" => Ok(EngineKind::Memory),
+            _ => anyhow::bail!("Unknown engine kind: {}", s),
+        }
+    }
+}
+
+/// When commits are made durable. Each engine maps these to its closest settings.
+#[derive(Debug, Copy, Clone)]
+pub enum SyncMode {
+    /// Every commit is synced.
+    Durable,
+    /// Commits are not synced, but a system crash can only lose the latest ones, not corrupt the
+    /// database.
+    SafeNoSync,
+    /// Nothing is synced, a system crash may corrupt the database.
+    UtterlyNoSync,
+    /// Commits are not synced, a background thread syncs in the given interval instead.
+    Periodic(Duration),
+}
+
+impl FromStr for SyncMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "durable" => Ok(SyncMode::Durable),
+            None if s == "safe-nosync" => Ok(SyncMode::SafeNoSync),
+            None if s == "utterly-nosync" => Ok(SyncMode::UtterlyNoSync),
+            Some(("periodic", secs)) => {
+                Ok(SyncMode::Periodic(Duration::from_secs_f64(secs.parse()?)))
+            }
+            _ => anyhow::bail!("Unknown sync mode: {}", s),
+        }
+    }
+}
+
+/// How often write workloads sync explicitly between commits, on top of the sync mode.
+#[derive(Debug, Copy, Clone)]
+pub enum SyncEvery {
+    /// After every given number of commits, a plain number.
+    Batches(u64),
+    /// After the first commit once the given time passed since the last sync, like `10s`.
+    Time(Duration),
+}
+
+impl FromStr for SyncEvery {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.chars().all(|c| c.is_ascii_digit()) {
+            let batches = s.parse()?;
+            if batches == 0 {
+                anyhow::bail!("Syncing every 0 batches");
+            }
+            return Ok(SyncEvery::Batches(batches));
+        }
+        let crate::units::Span(duration) = s.parse()?;
+        Ok(SyncEvery::Time(duration))
+    }
+}
+
+/// The free pages of an engine that reuses them, like the GC table of mdbx.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct GcStat {
+    /// The pages in the GC, free for reuse once no reader needs them anymore.
+    pub free_pages: u64,
+    /// The pages allocated since the environment was created, from the GC or the end of the file.
+    pub allocated: u64,
+    /// The last page of the file in use, which allocations from the end move forward.
+    pub last_page: u64,
+    /// The transactions the oldest reader is behind, which hold their pages back from the GC.
+    pub reader_lag: u64,
+}
+
+impl GcStat {
+    /// Returns the pages allocated since `before`, and how many of them came from the GC.
+    pub fn allocated_since(&self, before: &GcStat) -> (u64, u64) {
+        let allocated = self.allocated.saturating_sub(before.allocated);
+        let extended = self.last_page.saturating_sub(before.last_page);
+        (allocated, allocated.saturating_sub(extended))
+    }
+}
+
+/// What an insert does if the key exists already.
+#[derive(Debug, Copy, Clone)]
+pub enum OnDuplicate {
+    /// Overwrites the value.
+    Upsert,
+    /// Keeps the existing value.
+    Skip,
+    /// Fails the workload.
+    Error,
+}
+
+impl FromStr for OnDuplicate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "upsert" => Ok(OnDuplicate::Upsert),
+            "skip" => Ok(OnDuplicate::Skip),
+            "error" => Ok(OnDuplicate::Error),
+            _ => anyhow::bail!("Unknown duplicate handling: {}", s),
+        }
+    }
+}
+
+/// Tuning of rocksdb, unset options keep the rocksdb defaults.
+#[derive(Debug, Clone, clap::Args)]
+pub struct RocksdbOpts {
+    /// The size of a single memtable in bytes.
+    #[clap(long = "rocksdb-write-buffer-size")]
+    pub write_buffer_size: Option<usize>,
+
+    /// The maximum number of concurrent flushes and compactions.
+    #[clap(long = "rocksdb-max-background-jobs")]
+    pub max_background_jobs: Option<i32>,
+
+    /// The compression of all levels: `none`, `snappy`, `zlib`, `bz2`, `lz4`, `lz4hc` or `zstd`.
+    #[clap(long = "rocksdb-compression")]
+    pub compression: Option<String>,
+
+    /// The size of the block cache in bytes.
+    #[clap(long = "rocksdb-block-cache-size")]
+    pub block_cache_size: Option<usize>,
+
+    /// The compaction style: `level`, `universal` or `fifo`.
+    #[clap(long = "rocksdb-compaction-style")]
+    pub compaction_style: Option<String>,
+
+    /// Whether to write without the WAL, whatever the sync mode. The same as `--wal off`.
+    #[clap(long = "rocksdb-disable-wal")]
+    pub disable_wal: bool,
+
+    /// Whether commits go through the WAL, `on` or `off`. Defaults to on, unless the sync mode is
+    /// `utterly-nosync`.
+    #[clap(long = "wal", value_parser = parse_switch)]
+    pub wal: Option<bool>,
+
+    /// Whether every commit syncs the WAL, `on` or `off`. Defaults to on in the `durable` sync
+    /// mode with the WAL.
+    #[clap(long = "wal-sync", value_parser = parse_switch)]
+    pub wal_sync: Option<bool>,
+
+    /// Separates values from keys into blob files, the integrated BlobDB.
+    #[clap(long = "rocksdb-blob-files")]
+    pub blob_files: bool,
+
+    /// The size in bytes from which values go to blob files. Defaults to 0, all of them.
+    #[clap(long = "rocksdb-min-blob-size")]
+    pub min_blob_size: Option<u64>,
+
+    /// The size of a single blob file in bytes.
+    #[clap(long = "rocksdb-blob-file-size")]
+    pub blob_file_size: Option<u64>,
+
+    /// The compression of the blob files, like `--rocksdb-compression`.
+    #[clap(long = "rocksdb-blob-compression")]
+    pub blob_compression: Option<String>,
+
+    /// Whether compactions relocate the live values of the oldest blob files, so that they can be
+    /// deleted.
+    #[clap(long = "rocksdb-blob-gc")]
+    pub blob_gc: bool,
+
+    /// The fraction of the oldest blob files the garbage collection relocates values from.
+    #[clap(long = "rocksdb-blob-gc-age-cutoff")]
+    pub blob_gc_age_cutoff: Option<f64>,
+
+    /// The fraction of garbage in the oldest blob files that forces a compaction of the files
+    /// referencing them.
+    #[clap(long = "rocksdb-blob-gc-force-threshold")]
+    pub blob_gc_force_threshold: Option<f64>,
+
+    /// Whether `rocksdb-txn` uses optimistic transactions, which fail the commit on a conflict,
+    /// instead of pessimistic ones, which lock the keys.
+    #[clap(long = "rocksdb-optimistic")]
+    pub optimistic: bool,
+}
+
+/// Tuning of the mdbx environment.
+#[derive(Debug, Clone, clap::Args)]
+pub struct MdbxOpts {
+    /// The page size in bytes.
+    #[clap(long = "mdbx-page-size", default_value = "4096")]
+    pub page_size: usize,
+
+    /// The maximum size of the database in bytes. Defaults to 4 TiB.
+    #[clap(long = "mdbx-max-size")]
+    pub max_size: Option<usize>,
+
+    /// How much the database grows at once in bytes. Defaults to 1 GiB.
+    #[clap(long = "mdbx-growth-step")]
+    pub growth_step: Option<usize>,
+
+    /// The maximum number of named databases.
+    #[clap(long = "mdbx-max-dbs", default_value = "256")]
+    pub max_dbs: usize,
+
+    /// The maximum number of concurrent readers. Defaults to the mdbx default.
+    #[clap(long = "mdbx-max-readers")]
+    pub max_readers: Option<u64>,
+
+    /// Whether to write through `pwrite` instead of a writable memory map.
+    #[clap(long = "no-writemap", alias = "mdbx-no-writemap")]
+    pub no_writemap: bool,
+}
+
+fn parse_switch(s: &str) -> anyhow::Result<bool> {
+    match s {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => anyhow::bail!("Expected on or off: {}", s),
+    }
+}
+
+/// Opens the engine selected on the command line.
+pub fn open(cli: &Cli) -> anyhow::Result<Box<dyn Engine>> {
+    open_mode(cli, false)
+}
+
+/// Opens an existing database read-only, so that it can be read while another process writes
+/// it. Engines without a read-only mode open it as usual.
+pub fn open_readonly(cli: &Cli) -> anyhow::Result<Box<dyn Engine>> {
+    open_mode(cli, true)
+}
+
+/// Opens every shard of `--shards`, or just the database if it is not sharded.
+pub fn open_shards(cli: &Cli) -> anyhow::Result<Vec<Box<dyn Engine>>> {
+    if cli.shards == 1 {
+        return Ok(vec![open(cli)?]);
+    }
+    if !cli.dry_run {
+        std::fs::create_dir_all(&cli.path)?;
+    }
+    (0..cli.shards)
+        .map(|shard| {
+            let mut shard_cli = cli.clone();
+            shard_cli.path = shard_path(cli, shard).to_string_lossy().into_owned();
+            open(&shard_cli)
+        })
+        .collect()
+}
+
+pub fn shard_path(cli: &Cli, shard: usize) -> PathBuf {
+    Path::new(&cli.path).join(format!("shard-{}", shard))
+}
+
+fn open_mode(cli: &Cli, readonly: bool) -> anyhow::Result<Box<dyn Engine>> {
+    if cli.tables == 0 {
+        anyhow::bail!("At least one table is required.");
+    }
+    if cli.direct_io && !matches!(cli.kind, EngineKind::Rocksdb | EngineKind::RocksdbTxn) {
+        anyhow::bail!("Only rocksdb supports O_DIRECT, {:?} does not.", cli.kind);
+    }
+    if cli.read_your_writes > 0.0 && matches!(cli.kind, EngineKind::Rocksdb | EngineKind::Sled) {
+        anyhow::bail!(
+            "{:?} writes batches that its transactions can't read, --read-your-writes needs \
+             transactions.",
+            cli.kind
+        );
+    }
+    if cli.dry_run {
+        return Ok(Box::new(null::NullEngine));
+    }
+    let mut engine = open_engine(cli, readonly)?;
+    if !readonly {
+        crate::procs::ready(cli)?;
+    }
+    // Inside the trace, so that the reads back aren't recorded.
+    if cli.read_your_writes > 0.0 && !readonly {
+        engine = Box::new(readback::ReadBack::new(engine, cli.read_your_writes));
+    }
+    if let Some(path) = &cli.record_trace {
+        engine = Box::new(trace::Traced::new(engine, path)?);
+    }
+    match cli.sync_mode {
+        SyncMode::Periodic(interval) if !readonly => {
+            Ok(Box::new(PeriodicSync::new(engine, interval)))
+        }
+        _ => Ok(engine),
+    }
+}
+
+fn open_engine(cli: &Cli, readonly: bool) -> anyhow::Result<Box<dyn Engine>> {
+    match cli.kind {
+        #[cfg(feature = "mdbx")]
+        EngineKind::Mdbx => Ok(Box::new(self::mdbx::MdbxEngine::open(cli, readonly)?)),
+        #[cfg(feature = "rocksdb")]
+        EngineKind::Rocksdb => Ok(Box::new(self::rocksdb::RocksdbEngine::open(cli, readonly)?)),
+        #[cfg(feature = "rocksdb")]
+        EngineKind::RocksdbTxn => self::rocksdb_txn::open(cli),
+        #[cfg(feature = "sled")]
+        EngineKind::Sled => Ok(Box::new(self::sled::SledEngine::open(cli, readonly)?)),
+        #[cfg(feature = "lmdb")]
+        EngineKind::Lmdb => Ok(Box::new(self::lmdb::LmdbEngine::open(cli, readonly)?)),
+        #[cfg(feature = "redb")]
+        EngineKind::Redb => Ok(Box::new(self::redb::RedbEngine::open(cli, readonly)?)),
+        #[cfg(feature = "sqlite")]
+        EngineKind::Sqlite => Ok(Box::new(self::sqlite::SqliteEngine::open(cli, readonly)?)),
+        EngineKind::Memory => Ok(Box::new(self::memory::MemoryEngine::open(cli)?)),
+        #[allow(unreachable_patterns)]
+        kind => anyhow::bail!(
+            "Engine {:?} is not compiled in, enable its cargo feature",
+            kind
+        ),
+    }
+}
+
+/// Returns the name of the given table, `None` for the single table of the default layout, which
+/// every engine maps to its main table so that databases from before `--tables` still open.
+fn table_name(cli: &Cli, table: usize) -> Option<String> {
+    if cli.tables == 1 {
+        return None;
+    }
+    Some(format!("table{}", table))
+}
+
+/// Decodes a counter, a little-endian u64 where an absent key counts as 0.
+pub fn decode_counter(value: Option<&[u8]>) -> anyhow::Result<u64> {
+    match value {
+        None => Ok(0),
+        Some(value) => match <[u8; 8]>::try_from(value) {
+            Ok(bytes) => Ok(u64::from_le_bytes(bytes)),
+            Err(_) => anyhow::bail!("Counter of {} bytes, expected 8", value.len()),
+        },
+    }
+}
+
+/// Engines are shared between the worker threads, each of which begins its own transactions.
+pub trait Engine: Send + Sync {
+    /// Begins a write transaction over all tables.
+    fn begin(&self) -> anyhow::Result<Box<dyn Tx + '_>>;
+
+    /// Begins a read transaction, which must not block a concurrent writer.
+    fn begin_read(&self) -> anyhow::Result<Box<dyn ReadTx + '_>>;
+
+    /// Makes all commits so far durable, waiting for the current writer if needed.
+    fn sync(&self) -> anyhow::Result<()>;
+
+    fn print_stat(&self) -> anyhow::Result<String> {
+        Ok(String::new())
+    }
+
+    /// Returns the state of the engine internals that can stall a commit, like the free list or
+    /// the compaction backlog, one item per line.
+    fn diagnostics(&self) -> anyhow::Result<String> {
+        Ok(String::new())
+    }
+
+    /// Returns the bytes of the database that hold live pages, if the engine can tell them apart
+    /// from space that is allocated but free.
+    fn used_bytes(&self) -> anyhow::Result<Option<u64>> {
+        Ok(None)
+    }
+
+    /// Returns the bytes the engine wrote to its files since it was opened, like the WAL, flushes
+    /// and compactions, if it counts them.
+    fn engine_written(&self) -> anyhow::Result<Option<u64>> {
+        Ok(None)
+    }
+
+    /// Returns the size of the map, for engines that grow their file in steps.
+    fn map_size(&self) -> anyhow::Result<Option<u64>> {
+        Ok(None)
+    }
+
+    /// Returns the state of the free pages, for engines that reuse them.
+    fn gc_stat(&self) -> anyhow::Result<Option<GcStat>> {
+        Ok(None)
+    }
+
+    /// Loads entries sorted by key straight into the files of a table, bypassing transactions,
+    /// for engines that can.
+    fn ingest(&self, _table: usize, _entries: &[Entry]) -> anyhow::Result<()> {
+        anyhow::bail!("The engine has no file ingestion")
+    }
+
+    /// Writes entries sorted by key into a file that [`Engine::ingest_files`] can load later.
+    fn write_file(&self, _table: usize, _entries: &[Entry], _dest: &Path) -> anyhow::Result<()> {
+        anyhow::bail!("The engine has no file ingestion")
+    }
+
+    /// Loads files written by [`Engine::write_file`] into a table, which may consume them.
+    fn ingest_files(&self, _table: usize, _files: &[PathBuf]) -> anyhow::Result<()> {
+        anyhow::bail!("The engine has no file ingestion")
+    }
+
+    /// Compacts the whole database, blocking until done.
+    fn compact(&self) -> anyhow::Result<()> {
+        anyhow::bail!("The engine has no manual compaction")
+    }
+
+    /// Writes a consistent copy of the database to `dest`, which must not exist yet, leaving out
+    /// free space if `compact` is set.
+    fn backup(&self, _dest: &Path, _compact: bool) -> anyhow::Result<()> {
+        anyhow::bail!("The engine has no online backup")
+    }
+
+    /// Returns the background compaction work since the engine was opened and the work still
+    /// pending, for engines that compact in the background.
+    fn compaction_report(&self) -> anyhow::Result<String> {
+        Ok(String::new())
+    }
+}
+
+/// Syncs the wrapped engine from a background thread until dropped.
+struct PeriodicSync {
+    engine: Arc<dyn Engine>,
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PeriodicSync {
+    fn new(engine: Box<dyn Engine>, interval: Duration) -> Self {
+        let engine: Arc<dyn Engine> = Arc::from(engine);
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = {
+            let engine = engine.clone();
+            std::thread::spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    let synced = tracing::debug_span!("sync").in_scope(|| engine.sync());
+                    if let Err(err) = synced {
+                        tracing::error!("Periodic sync failed: {:#}", err);
+                    }
+                }
+            })
+        };
+        PeriodicSync {
+            engine,
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for PeriodicSync {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Engine for PeriodicSync {
+    forward_engine!(
+        begin,
+        begin_read,
+        sync,
+        print_stat,
+        diagnostics,
+        used_bytes,
+        map_size,
+        gc_stat,
+        ingest,
+        write_file,
+        ingest_files,
+        engine_written,
+        compact,
+        compaction_report,
+        backup,
+    );
+}
+
+/// Tables are addressed by their index, from 0 to `--tables`.
+pub trait ReadTx {
+    fn get(&self, table: usize, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>>;
+
+    fn cursor(&self, table: usize) -> anyhow::Result<Box<dyn Cursor + '_>>;
+
+    /// Looks up a batch of `(table, key)` pairs and returns the values in the same order.
+    fn multi_get(&self, keys: &[(usize, &[u8])]) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+        keys.iter()
+            .map(|(table, key)| self.get(*table, key))
+            .collect()
+    }
+
+    /// Returns the first key that is greater or equal to the given one, wrapping around to the
+    /// first key in the table if there is none. Returns `None` only if the table is empty.
+    fn seek(&self, table: usize, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        let mut cursor = self.cursor(table)?;
+        let found = match cursor.seek(key)? {
+            Some(entry) => Some(entry),
+            None => cursor.first()?,
+        };
+        Ok(found.map(|(key, _)| key))
+    }
+
+    /// Returns the values of a key in the dupsort table, in order. Engines without dupsort tables
+    /// store every value as a suffix of its key in table 0, with an empty value.
+    fn dups(&self, key: &[u8]) -> anyhow::Result<Vec<Vec<u8>>> {
+        let mut cursor = self.cursor(0)?;
+        let mut dups = Vec::new();
+        let mut entry = cursor.seek(key)?;
+        while let Some((found, _)) = entry {
+            let Some(value) = found.strip_prefix(key) else {
+                break;
+            };
+            dups.push(value.to_vec());
+            entry = cursor.next()?;
+        }
+        Ok(dups)
+    }
+}
+
+/// Writes take the key and the value by reference, so that workloads can reuse their buffers.
+/// Engines copy them into their own pages or batches anyway.
+pub trait Tx: ReadTx {
+    fn put(&mut self, table: usize, key: &[u8], value: &[u8]) -> anyhow::Result<()>;
+
+    fn delete(&mut self, table: usize, key: &[u8]) -> anyhow::Result<()>;
+
+    /// Deletes the keys from `start` up to but excluding `end`. By default one by one, after
+    /// collecting them with a cursor.
+    fn delete_range(&mut self, table: usize, start: &[u8], end: &[u8]) -> anyhow::Result<()> {
+        let mut keys = Vec::new();
+        let mut cursor = self.cursor(table)?;
+        let mut entry = cursor.seek(start)?;
+        while let Some((key, _)) = entry {
+            if key.as_slice() >= end {
+                break;
+            }
+            keys.push(key);
+            entry = cursor.next()?;
+        }
+        drop(cursor);
+        for key in keys {
+            self.delete(table, &key)?;
+        }
+        Ok(())
+    }
+
+    /// Puts a key unless it exists and `on_duplicate` says otherwise, returns whether it was
+    /// written. By default the key is looked up first, which may not see the writes of this
+    /// transaction in engines that buffer them, like rocksdb.
+    fn insert(
+        &mut self,
+        table: usize,
+        key: &[u8],
+        value: &[u8],
+        on_duplicate: OnDuplicate,
+    ) -> anyhow::Result<bool> {
+        if !matches!(on_duplicate, OnDuplicate::Upsert) && self.get(table, key)?.is_some() {
+            if let OnDuplicate::Error = on_duplicate {
+                anyhow::bail!("Key {:02x?} already exists", key);
+            }
+            return Ok(false);
+        }
+        self.put(table, key, value)?;
+        Ok(true)
+    }
+
+    /// Puts a key greater than all keys of the table, which engines may do without a search. By
+    /// default a plain put.
+    fn append(&mut self, table: usize, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.put(table, key, value)
+    }
+
+    /// Adds `delta` to the counter of a key, see [`decode_counter`]. By default a read-modify-write,
+    /// rocksdb merges instead, without reading.
+    fn add(&mut self, table: usize, key: &[u8], delta: u64) -> anyhow::Result<()> {
+        let value = decode_counter(self.get(table, key)?.as_deref())?;
+        self.put(table, key, &(value + delta).to_le_bytes())
+    }
+
+    /// Adds a value to the values of a key in the dupsort table, see `ReadTx::dups`.
+    fn put_dup(&mut self, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.put(0, &[key, value].concat(), &[])
+    }
+
+    /// Begins a transaction nested in this one. Its writes become part of this one when it
+    /// commits and vanish when it is dropped. Only engines with nested transactions, like mdbx,
+    /// support it.
+    fn nested(&mut self) -> anyhow::Result<Box<dyn Tx + '_>> {
+        anyhow::bail!("Nested transactions are not supported")
+    }
+
+    fn commit(self: Box<Self>) -> anyhow::Result<()>;
+}
+
+/// A positioned iterator over the entries of a table.
+///
+/// Every method returns the entry the cursor ends up at, or `None` if it ran off either end.
+pub trait Cursor {
+    fn first(&mut self) -> anyhow::Result<Option<Entry>>;
+
+    fn last(&mut self) -> anyhow::Result<Option<Entry>>;
+
+    /// Positions the cursor at the first entry with a key greater or equal to `key`.
+    fn seek(&mut self, key: &[u8]) -> anyhow::Result<Option<Entry>>;
+
+    /// Positions the cursor at the last entry with a key less or equal to `key`.
+    fn seek_for_prev(&mut self, key: &[u8]) -> anyhow::Result<Option<Entry>>;
+
+    fn next(&mut self) -> anyhow::Result<Option<Entry>>;
+
+    fn prev(&mut self) -> anyhow::Result<Option<Entry>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sync_modes() -> anyhow::Result<()> {
+        assert!(matches!("durable".parse()?, SyncMode::Durable));
+        assert!(matches!("safe-nosync".parse()?, SyncMode::SafeNoSync));
+        assert!(matches!("utterly-nosync".parse()?, SyncMode::UtterlyNoSync));
+        let SyncMode::Periodic(interval) = "periodic:0.5".parse()? else {
+            anyhow::bail!("Not periodic");
+        };
+        assert_eq!(interval, Duration::from_millis(500));
+        assert!("periodic:soon".parse::<SyncMode>().is_err());
+        assert!("nosync".parse::<SyncMode>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parses_sync_every() -> anyhow::Result<()> {
+        assert!(matches!("10".parse()?, SyncEvery::Batches(10)));
+        let SyncEvery::Time(interval) = "2m".parse()? else {
+            anyhow::bail!("Not a time");
+        };
+        assert_eq!(interval, Duration::from_secs(120));
+        assert!("0".parse::<SyncEvery>().is_err());
+        Ok(())
+    }
+}
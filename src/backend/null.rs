@@ -0,0 +1,78 @@
+//! The engine of `--dry-run`, which drops every write and finds nothing, so that a workload
+//! measures the generation of its keys and values and its own bookkeeping.
+
+use super::{Cursor, Engine, Entry, ReadTx, Tx};
+
+pub struct NullEngine;
+
+impl Engine for NullEngine {
+    fn begin(&self) -> anyhow::Result<Box<dyn Tx + '_>> {
+        Ok(Box::new(NullTx))
+    }
+
+    fn begin_read(&self) -> anyhow::Result<Box<dyn ReadTx + '_>> {
+        Ok(Box::new(NullTx))
+    }
+
+    fn sync(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+struct NullTx;
+
+impl ReadTx for NullTx {
+    fn get(&self, _table: usize, _key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+
+    fn cursor(&self, _table: usize) -> anyhow::Result<Box<dyn Cursor + '_>> {
+        Ok(Box::new(NullCursor))
+    }
+}
+
+impl Tx for NullTx {
+    fn put(&mut self, _table: usize, _key: &[u8], _value: &[u8]) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn delete(&mut self, _table: usize, _key: &[u8]) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn nested(&mut self) -> anyhow::Result<Box<dyn Tx + '_>> {
+        Ok(Box::new(NullTx))
+    }
+
+    fn commit(self: Box<Self>) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+struct NullCursor;
+
+impl Cursor for NullCursor {
+    fn first(&mut self) -> anyhow::Result<Option<Entry>> {
+        Ok(None)
+    }
+
+    fn last(&mut self) -> anyhow::Result<Option<Entry>> {
+        Ok(None)
+    }
+
+    fn seek(&mut self, _key: &[u8]) -> anyhow::Result<Option<Entry>> {
+        Ok(None)
+    }
+
+    fn seek_for_prev(&mut self, _key: &[u8]) -> anyhow::Result<Option<Entry>> {
+        Ok(None)
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Entry>> {
+        Ok(None)
+    }
+
+    fn prev(&mut self) -> anyhow::Result<Option<Entry>> {
+        Ok(None)
+    }
+}
@@ -0,0 +1,212 @@
+//! Checking that write transactions see their own writes before they commit, see
+//! `--read-your-writes`.
+//!
+//! The keys whose hash falls into the sampled fraction are followed through every write of a
+//! transaction and read back before it commits or begins a nested transaction. The read back
+//! keys and the time it took end up in the info of the commit as `read_back_keys` and
+//! `read_back_us`.
+
+use super::{trace::hash, Cursor, Engine, OnDuplicate, ReadTx, Tx};
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+pub struct ReadBack {
+    engine: Box<dyn Engine>,
+    /// Keys with a hash below it are followed.
+    threshold: u64,
+    reads: AtomicU64,
+    read_ns: AtomicU64,
+}
+
+impl ReadBack {
+    pub fn new(engine: Box<dyn Engine>, fraction: f64) -> Self {
+        ReadBack {
+            engine,
+            threshold: (fraction * u64::MAX as f64) as u64,
+            reads: AtomicU64::new(0),
+            read_ns: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Drop for ReadBack {
+    fn drop(&mut self) {
+        let reads = *self.reads.get_mut();
+        if reads > 0 {
+            let per_read = Duration::from_nanos(*self.read_ns.get_mut() / reads);
+            tracing::info!(reads, ?per_read, "Read back writes in their transactions");
+        }
+    }
+}
+
+impl Engine for ReadBack {
+    fn begin(&self) -> anyhow::Result<Box<dyn Tx + '_>> {
+        Ok(Box::new(ReadBackTx {
+            engine: self,
+            txn: self.engine.begin()?,
+            expected: HashMap::new(),
+        }))
+    }
+
+    forward_engine!(
+        begin_read,
+        sync,
+        print_stat,
+        diagnostics,
+        used_bytes,
+        map_size,
+        gc_stat,
+        ingest,
+        write_file,
+        ingest_files,
+        engine_written,
+        compact,
+        compaction_report,
+        backup,
+    );
+}
+
+struct ReadBackTx<'a> {
+    engine: &'a ReadBack,
+    txn: Box<dyn Tx + 'a>,
+    /// What every followed key was last written as, `None` if it was deleted.
+    expected: HashMap<(usize, Vec<u8>), Option<Vec<u8>>>,
+}
+
+impl ReadBackTx<'_> {
+    fn expect(&mut self, table: usize, key: &[u8], value: Option<&[u8]>) {
+        if hash(key) < self.engine.threshold {
+            let value = value.map(<[u8]>::to_vec);
+            self.expected.insert((table, key.to_vec()), value);
+        }
+    }
+
+    /// Reads back the followed keys, fails on the first that doesn't read as written and forgets
+    /// them all. Returns the time the reads took.
+    fn check(&mut self) -> anyhow::Result<Duration> {
+        let start = Instant::now();
+        for ((table, key), expected) in &self.expected {
+            let found = self.txn.get(*table, key)?;
+            if found != *expected {
+                anyhow::bail!(
+                    "Key {:02x?} of table {} reads as {} in its own transaction, but was written \
+                     as {}",
+                    key,
+                    table,
+                    describe(found.as_deref()),
+                    describe(expected.as_deref())
+                );
+            }
+        }
+        let elapsed = start.elapsed();
+        let reads = self.expected.len() as u64;
+        self.engine.reads.fetch_add(reads, Ordering::Relaxed);
+        let ns = elapsed.as_nanos() as u64;
+        self.engine.read_ns.fetch_add(ns, Ordering::Relaxed);
+        self.expected.clear();
+        Ok(elapsed)
+    }
+}
+
+fn describe(value: Option<&[u8]>) -> String {
+    match value {
+        Some(value) => format!("{} bytes of hash {:x}", value.len(), hash(value)),
+        None => "absent".to_string(),
+    }
+}
+
+impl ReadTx for ReadBackTx<'_> {
+    fn get(&self, table: usize, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        self.txn.get(table, key)
+    }
+
+    fn cursor(&self, table: usize) -> anyhow::Result<Box<dyn Cursor + '_>> {
+        self.txn.cursor(table)
+    }
+
+    fn multi_get(&self, keys: &[(usize, &[u8])]) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+        self.txn.multi_get(keys)
+    }
+
+    fn seek(&self, table: usize, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        self.txn.seek(table, key)
+    }
+
+    fn dups(&self, key: &[u8]) -> anyhow::Result<Vec<Vec<u8>>> {
+        self.txn.dups(key)
+    }
+}
+
+impl Tx for ReadBackTx<'_> {
+    fn put(&mut self, table: usize, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.txn.put(table, key, value)?;
+        self.expect(table, key, Some(value));
+        Ok(())
+    }
+
+    fn delete(&mut self, table: usize, key: &[u8]) -> anyhow::Result<()> {
+        self.txn.delete(table, key)?;
+        self.expect(table, key, None);
+        Ok(())
+    }
+
+    fn delete_range(&mut self, table: usize, start: &[u8], end: &[u8]) -> anyhow::Result<()> {
+        self.txn.delete_range(table, start, end)?;
+        for ((t, key), value) in &mut self.expected {
+            if *t == table && start <= key.as_slice() && key.as_slice() < end {
+                *value = None;
+            }
+        }
+        Ok(())
+    }
+
+    fn insert(
+        &mut self,
+        table: usize,
+        key: &[u8],
+        value: &[u8],
+        on_duplicate: OnDuplicate,
+    ) -> anyhow::Result<bool> {
+        let written = self.txn.insert(table, key, value, on_duplicate)?;
+        if written {
+            self.expect(table, key, Some(value));
+        }
+        Ok(written)
+    }
+
+    fn append(&mut self, table: usize, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.txn.append(table, key, value)?;
+        self.expect(table, key, Some(value));
+        Ok(())
+    }
+
+    /// The sum isn't known without a read, so the key is forgotten.
+    fn add(&mut self, table: usize, key: &[u8], delta: u64) -> anyhow::Result<()> {
+        self.txn.add(table, key, delta)?;
+        self.expected.remove(&(table, key.to_vec()));
+        Ok(())
+    }
+
+    fn put_dup(&mut self, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.txn.put_dup(key, value)
+    }
+
+    /// The writes of the nested transaction aren't followed, so the ones so far are checked
+    /// before it begins.
+    fn nested(&mut self) -> anyhow::Result<Box<dyn Tx + '_>> {
+        self.check()?;
+        self.txn.nested()
+    }
+
+    fn commit(mut self: Box<Self>) -> anyhow::Result<()> {
+        let keys = self.expected.len() as u64;
+        let elapsed = self.check()?;
+        self.txn.commit()?;
+        super::add_commit_info("read_back_keys", keys);
+        super::add_commit_info("read_back_us", elapsed.as_micros() as u64);
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+use super::{Cursor, Engine, Entry, ReadTx, SyncMode, Tx};
+use crate::Cli;
+use redb::ReadableTable;
+use std::{ops::Bound, path::PathBuf};
+
+type Table<'a> = redb::TableDefinition<'a, &'static [u8], &'static [u8]>;
+
+pub struct RedbEngine {
+    db: redb::Database,
+    durability: redb::Durability,
+    tables: Vec<String>,
+}
+
+impl RedbEngine {
+    /// redb has no read-only mode, read-only it only doesn't create the database and the tables.
+    pub fn open(cli: &Cli, readonly: bool) -> anyhow::Result<Self> {
+        let path = PathBuf::from(&cli.path).join("data.redb");
+        let tables: Vec<String> = (0..cli.tables)
+            .map(|t| super::table_name(cli, t).unwrap_or_else(|| "kv".to_string()))
+            .collect();
+        let db = if readonly {
+            redb::Database::open(path)?
+        } else {
+            // redb keeps everything in a single file, put it inside the directory like the others.
+            std::fs::create_dir_all(&cli.path)?;
+            let db = redb::Database::create(path)?;
+            // Create the tables upfront, read transactions can't do it.
+            let txn = db.begin_write()?;
+            for name in &tables {
+                txn.open_table(Table::new(name))?;
+            }
+            txn.commit()?;
+            db
+        };
+        let durability = match cli.sync_mode {
+            SyncMode::Durable => redb::Durability::Immediate,
+            SyncMode::SafeNoSync => redb::Durability::Eventual,
+            // Non-durable commits become durable with the next immediate one, see `sync`.
+            SyncMode::UtterlyNoSync | SyncMode::Periodic(_) => redb::Durability::None,
+        };
+        Ok(RedbEngine {
+            db,
+            durability,
+            tables,
+        })
+    }
+}
+
+impl Engine for RedbEngine {
+    fn begin(&self) -> anyhow::Result<Box<dyn Tx + '_>> {
+        let mut txn = self.db.begin_write()?;
+        txn.set_durability(self.durability);
+        Ok(Box::new(RedbTx {
+            txn,
+            tables: &self.tables,
+        }))
+    }
+
+    fn begin_read(&self) -> anyhow::Result<Box<dyn ReadTx + '_>> {
+        Ok(Box::new(RedbReadTx {
+            txn: self.db.begin_read()?,
+            tables: &self.tables,
+        }))
+    }
+
+    fn sync(&self) -> anyhow::Result<()> {
+        let mut txn = self.db.begin_write()?;
+        txn.set_durability(redb::Durability::Immediate);
+        txn.commit()?;
+        Ok(())
+    }
+}
+
+/// The table borrows the transaction, so it is reopened for every operation.
+struct RedbTx<'a> {
+    txn: redb::WriteTransaction,
+    tables: &'a [String],
+}
+
+impl ReadTx for RedbTx<'_> {
+    fn get(&self, table: usize, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        let table = self.txn.open_table(Table::new(&self.tables[table]))?;
+        let value = table.get(key)?.map(|value| value.value().to_vec());
+        Ok(value)
+    }
+
+    fn cursor(&self, table: usize) -> anyhow::Result<Box<dyn Cursor + '_>> {
+        Ok(Box::new(RedbCursor {
+            table: self.txn.open_table(Table::new(&self.tables[table]))?,
+            pos: None,
+        }))
+    }
+}
+
+impl Tx for RedbTx<'_> {
+    fn put(&mut self, table: usize, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.txn
+            .open_table(Table::new(&self.tables[table]))?
+            .insert(key, value)?;
+        Ok(())
+    }
+
+    fn delete(&mut self, table: usize, key: &[u8]) -> anyhow::Result<()> {
+        self.txn
+            .open_table(Table::new(&self.tables[table]))?
+            .remove(key)?;
+        Ok(())
+    }
+
+    fn commit(self: Box<Self>) -> anyhow::Result<()> {
+        self.txn.commit()?;
+        Ok(())
+    }
+}
+
+struct RedbReadTx<'a> {
+    txn: redb::ReadTransaction,
+    tables: &'a [String],
+}
+
+impl ReadTx for RedbReadTx<'_> {
+    fn get(&self, table: usize, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        let table = self.txn.open_table(Table::new(&self.tables[table]))?;
+        let value = table.get(key)?.map(|value| value.value().to_vec());
+        Ok(value)
+    }
+
+    fn cursor(&self, table: usize) -> anyhow::Result<Box<dyn Cursor + '_>> {
+        Ok(Box::new(RedbCursor {
+            table: self.txn.open_table(Table::new(&self.tables[table]))?,
+            pos: None,
+        }))
+    }
+}
+
+/// Same as the sled cursor, but the table is kept open for the lifetime of the cursor.
+struct RedbCursor<T> {
+    table: T,
+    pos: Option<Vec<u8>>,
+}
+
+type Guards<'a> = (
+    redb::AccessGuard<'a, &'static [u8]>,
+    redb::AccessGuard<'a, &'static [u8]>,
+);
+
+impl<T> RedbCursor<T> {
+    fn entry(pos: &mut Option<Vec<u8>>, found: Option<Guards>) -> anyhow::Result<Option<Entry>> {
+        let entry = found.map(|(key, value)| (key.value().to_vec(), value.value().to_vec()));
+        *pos = entry.as_ref().map(|(key, _)| key.clone());
+        Ok(entry)
+    }
+}
+
+impl<T: ReadableTable<&'static [u8], &'static [u8]>> Cursor for RedbCursor<T> {
+    fn first(&mut self) -> anyhow::Result<Option<Entry>> {
+        Self::entry(&mut self.pos, self.table.first()?)
+    }
+
+    fn last(&mut self) -> anyhow::Result<Option<Entry>> {
+        Self::entry(&mut self.pos, self.table.last()?)
+    }
+
+    fn seek(&mut self, key: &[u8]) -> anyhow::Result<Option<Entry>> {
+        let found = self.table.range(key..)?.next().transpose()?;
+        Self::entry(&mut self.pos, found)
+    }
+
+    fn seek_for_prev(&mut self, key: &[u8]) -> anyhow::Result<Option<Entry>> {
+        let found = self.table.range(..=key)?.next_back().transpose()?;
+        Self::entry(&mut self.pos, found)
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Entry>> {
+        let found = match self.pos.as_deref() {
+            Some(from) => self
+                .table
+                .range::<&[u8]>((Bound::Excluded(from), Bound::Unbounded))?
+                .next()
+                .transpose()?,
+            None => None,
+        };
+        Self::entry(&mut self.pos, found)
+    }
+
+    fn prev(&mut self) -> anyhow::Result<Option<Entry>> {
+        let found = match self.pos.as_deref() {
+            Some(to) => self.table.range(..to)?.next_back().transpose()?,
+            None => None,
+        };
+        Self::entry(&mut self.pos, found)
+    }
+}
@@ -0,0 +1,517 @@
+use super::{Cursor, Engine, Entry, ReadTx, SyncMode, Tx};
+use crate::Cli;
+use rocksdb::perf::{set_perf_stats, PerfContext, PerfMetric, PerfStatsLevel};
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+pub struct RocksdbEngine {
+    db: rocksdb::DB,
+    /// Kept for the statistics.
+    opts: rocksdb::Options,
+    /// The column family of every table.
+    tables: Vec<String>,
+    sync: bool,
+    wal: bool,
+    /// Whether values are separated into blob files.
+    blob_files: bool,
+    /// The number of SST files ingested so far, to name the next one.
+    ingested: AtomicU64,
+}
+
+impl RocksdbEngine {
+    pub fn open(cli: &Cli, readonly: bool) -> anyhow::Result<Self> {
+        let tuning = &cli.rocksdb;
+        let (opts, tables) = options(cli)?;
+        let (wal, sync) = wal(cli)?;
+        let cfs = tables
+            .iter()
+            .map(|name| rocksdb::ColumnFamilyDescriptor::new(name, opts.clone()));
+        let db = if readonly {
+            rocksdb::DB::open_cf_descriptors_read_only(&opts, &cli.path, cfs, false)?
+        } else {
+            rocksdb::DB::open_cf_descriptors(&opts, &cli.path, cfs)?
+        };
+        Ok(RocksdbEngine {
+            db,
+            opts,
+            tables,
+            sync,
+            wal,
+            blob_files: tuning.blob_files,
+            ingested: AtomicU64::new(0),
+        })
+    }
+}
+
+/// Returns whether commits go through the WAL and whether they sync it. Without the WAL
+/// everything since the last memtable flush is lost on a crash.
+pub(super) fn wal(cli: &Cli) -> anyhow::Result<(bool, bool)> {
+    let tuning = &cli.rocksdb;
+    if tuning.disable_wal && tuning.wal == Some(true) {
+        anyhow::bail!("--rocksdb-disable-wal contradicts --wal on");
+    }
+    let wal = match tuning.wal {
+        Some(wal) => wal,
+        None => !tuning.disable_wal && !matches!(cli.sync_mode, SyncMode::UtterlyNoSync),
+    };
+    if tuning.wal_sync == Some(true) && !wal {
+        anyhow::bail!("Commits can't sync the WAL when they don't write it.");
+    }
+    let sync = tuning
+        .wal_sync
+        .unwrap_or(wal && matches!(cli.sync_mode, SyncMode::Durable));
+    Ok((wal, sync))
+}
+
+/// Returns the options of the database and its column families, and the names of the column
+/// families of the tables.
+pub(super) fn options(cli: &Cli) -> anyhow::Result<(rocksdb::Options, Vec<String>)> {
+    let tuning = &cli.rocksdb;
+    let mut opts = rocksdb::Options::default();
+    opts.create_if_missing(true);
+    opts.enable_statistics();
+    opts.set_merge_operator_associative("counter", merge_counters);
+    if cli.direct_io {
+        opts.set_use_direct_reads(true);
+        opts.set_use_direct_io_for_flush_and_compaction(true);
+    }
+    if let Some(size) = tuning.write_buffer_size {
+        opts.set_write_buffer_size(size);
+    }
+    if let Some(jobs) = tuning.max_background_jobs {
+        opts.set_max_background_jobs(jobs);
+    }
+    if let Some(compression) = &tuning.compression {
+        opts.set_compression_type(compression_type(compression)?);
+    }
+    if let Some(size) = tuning.block_cache_size {
+        let mut table = rocksdb::BlockBasedOptions::default();
+        table.set_block_cache(&rocksdb::Cache::new_lru_cache(size));
+        opts.set_block_based_table_factory(&table);
+    }
+    if let Some(style) = &tuning.compaction_style {
+        opts.set_compaction_style(compaction_style(style)?);
+    }
+    if tuning.blob_files {
+        opts.set_enable_blob_files(true);
+        opts.set_min_blob_size(tuning.min_blob_size.unwrap_or(0));
+        if let Some(size) = tuning.blob_file_size {
+            opts.set_blob_file_size(size);
+        }
+        if let Some(compression) = &tuning.blob_compression {
+            opts.set_blob_compression_type(compression_type(compression)?);
+        }
+        opts.set_enable_blob_gc(tuning.blob_gc);
+        if let Some(cutoff) = tuning.blob_gc_age_cutoff {
+            opts.set_blob_gc_age_cutoff(cutoff);
+        }
+        if let Some(threshold) = tuning.blob_gc_force_threshold {
+            opts.set_blob_gc_force_threshold(threshold);
+        }
+    }
+
+    // Every column family gets the same tuning.
+    let tables: Vec<String> = (0..cli.tables)
+        .map(|t| {
+            super::table_name(cli, t)
+                .unwrap_or_else(|| rocksdb::DEFAULT_COLUMN_FAMILY_NAME.to_string())
+        })
+        .collect();
+    opts.create_missing_column_families(true);
+    Ok((opts, tables))
+}
+
+/// Returns the counts of the given statistics tickers, parsed from the
+/// `rocksdb.compact.write.bytes COUNT : 1234` lines of the statistics.
+pub(super) fn tickers(
+    opts: &rocksdb::Options,
+    names: &[&str],
+) -> anyhow::Result<Option<Vec<(String, u64)>>> {
+    let Some(stats) = opts.get_statistics() else {
+        return Ok(None);
+    };
+    let mut counts = Vec::new();
+    for line in stats.lines() {
+        if let Some((ticker, count)) = line.split_once(" COUNT : ") {
+            if names.contains(&ticker) {
+                counts.push((ticker.to_string(), count.trim().parse()?));
+            }
+        }
+    }
+    Ok(Some(counts))
+}
+
+/// The properties of every column family that explain write stalls.
+const STALL_PROPERTIES: &[&str] = &[
+    "rocksdb.num-immutable-mem-table",
+    "rocksdb.cur-size-all-mem-tables",
+    "rocksdb.num-running-flushes",
+    "rocksdb.num-running-compactions",
+    "rocksdb.estimate-pending-compaction-bytes",
+    "rocksdb.is-write-stopped",
+    "rocksdb.actual-delayed-write-rate",
+    "rocksdb.levelstats",
+];
+
+/// The properties of every column family printed by `stat`.
+const STAT_PROPERTIES: &[&str] = &[
+    "rocksdb.estimate-num-keys",
+    "rocksdb.estimate-live-data-size",
+    "rocksdb.total-sst-files-size",
+    "rocksdb.live-sst-files-size",
+    "rocksdb.estimate-pending-compaction-bytes",
+    "rocksdb.num-immutable-mem-table",
+    "rocksdb.cur-size-all-mem-tables",
+    "rocksdb.levelstats",
+];
+
+/// The statistics tickers of everything the engine writes to its files.
+pub(super) const WRITE_TICKERS: &[&str] = &[
+    "rocksdb.wal.bytes",
+    "rocksdb.flush.write.bytes",
+    "rocksdb.compact.write.bytes",
+    "rocksdb.blobdb.blob.file.bytes.written",
+];
+
+/// The properties of every column family about its blob files.
+const BLOB_PROPERTIES: &[&str] = &[
+    "rocksdb.num-blob-files",
+    "rocksdb.total-blob-file-size",
+    "rocksdb.live-blob-file-size",
+    "rocksdb.live-blob-file-garbage-size",
+];
+
+/// The statistics tickers of the blob garbage collection.
+const BLOB_GC_TICKERS: &[&str] = &[
+    "rocksdb.blobdb.gc.num.files",
+    "rocksdb.blobdb.gc.num.new.files",
+    "rocksdb.blobdb.gc.num.keys.relocated",
+    "rocksdb.blobdb.gc.bytes.relocated",
+];
+
+/// The properties of every column family in the compaction report.
+const COMPACTION_PROPERTIES: &[&str] = &[
+    "rocksdb.compaction-pending",
+    "rocksdb.estimate-pending-compaction-bytes",
+    "rocksdb.levelstats",
+];
+
+impl RocksdbEngine {
+    /// Returns the total time writes were stalled since the database was opened, parsed from
+    /// `Cumulative stall: 00:00:1.234 H:M:S` in the database stats.
+    fn stall_time(&self) -> anyhow::Result<Option<Duration>> {
+        let Some(stats) = self.db.property_value("rocksdb.dbstats")? else {
+            return Ok(None);
+        };
+        let Some(stall) = stats
+            .split("Cumulative stall: ")
+            .nth(1)
+            .and_then(|rest| rest.split_whitespace().next())
+        else {
+            return Ok(None);
+        };
+        let mut secs = 0.0;
+        for part in stall.split(':') {
+            secs = secs * 60.0 + part.parse::<f64>()?;
+        }
+        Ok(Some(Duration::from_secs_f64(secs)))
+    }
+
+    /// Returns the given properties of every column family, one per line.
+    fn properties(&self, names: &[&str]) -> anyhow::Result<String> {
+        let mut out = String::new();
+        for table in 0..self.tables.len() {
+            out += &format!("{}:\n", self.tables[table]);
+            for name in names {
+                let value = self.db.property_value_cf(self.cf(table), *name)?;
+                let value = value.unwrap_or_default();
+                if value.contains('\n') {
+                    out += &format!("  {}:\n{}", name, value);
+                } else {
+                    out += &format!("  {}: {}\n", name, value);
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    fn cf(&self, table: usize) -> &rocksdb::ColumnFamily {
+        self.db
+            .cf_handle(&self.tables[table])
+            .expect("all column families are opened")
+    }
+}
+
+/// Sums the counters of `Tx::add`. Undecodable operands fail the merge, which rocksdb reports as
+/// corruption on the next read.
+fn merge_counters(
+    _key: &[u8],
+    existing: Option<&[u8]>,
+    operands: &rocksdb::MergeOperands,
+) -> Option<Vec<u8>> {
+    let mut sum = super::decode_counter(existing).ok()?;
+    for operand in operands.iter() {
+        sum += super::decode_counter(Some(operand)).ok()?;
+    }
+    Some(sum.to_le_bytes().to_vec())
+}
+
+fn compression_type(s: &str) -> anyhow::Result<rocksdb::DBCompressionType> {
+    match s {
+        "none" => Ok(rocksdb::DBCompressionType::None),
+        "snappy" => Ok(rocksdb::DBCompressionType::Snappy),
+        "zlib" => Ok(rocksdb::DBCompressionType::Zlib),
+        "bz2" => Ok(rocksdb::DBCompressionType::Bz2),
+        "lz4" => Ok(rocksdb::DBCompressionType::Lz4),
+        "lz4hc" => Ok(rocksdb::DBCompressionType::Lz4hc),
+        "zstd" => Ok(rocksdb::DBCompressionType::Zstd),
+        _ => anyhow::bail!("Unknown rocksdb compression: {}", s),
+    }
+}
+
+fn compaction_style(s: &str) -> anyhow::Result<rocksdb::DBCompactionStyle> {
+    match s {
+        "level" => Ok(rocksdb::DBCompactionStyle::Level),
+        "universal" => Ok(rocksdb::DBCompactionStyle::Universal),
+        "fifo" => Ok(rocksdb::DBCompactionStyle::Fifo),
+        _ => anyhow::bail!("Unknown rocksdb compaction style: {}", s),
+    }
+}
+
+impl Engine for RocksdbEngine {
+    fn begin(&self) -> anyhow::Result<Box<dyn Tx + '_>> {
+        Ok(Box::new(RocksdbTx {
+            engine: self,
+            batch: rocksdb::WriteBatch::default(),
+        }))
+    }
+
+    fn begin_read(&self) -> anyhow::Result<Box<dyn ReadTx + '_>> {
+        Ok(Box::new(RocksdbTx {
+            engine: self,
+            batch: rocksdb::WriteBatch::default(),
+        }))
+    }
+
+    fn sync(&self) -> anyhow::Result<()> {
+        if self.wal {
+            self.db.flush_wal(true)?;
+        } else {
+            self.db.flush()?;
+        }
+        Ok(())
+    }
+
+    fn print_stat(&self) -> anyhow::Result<String> {
+        if self.blob_files {
+            return Ok(self.properties(STAT_PROPERTIES)? + &self.properties(BLOB_PROPERTIES)?);
+        }
+        self.properties(STAT_PROPERTIES)
+    }
+
+    fn diagnostics(&self) -> anyhow::Result<String> {
+        self.properties(STALL_PROPERTIES)
+    }
+
+    fn engine_written(&self) -> anyhow::Result<Option<u64>> {
+        let counts = tickers(&self.opts, WRITE_TICKERS)?;
+        Ok(counts.map(|counts| counts.iter().map(|(_, count)| count).sum()))
+    }
+
+    /// Through an SST file in the database directory.
+    fn ingest(&self, table: usize, entries: &[Entry]) -> anyhow::Result<()> {
+        let n = self.ingested.fetch_add(1, Ordering::Relaxed);
+        let path = self.db.path().join(format!("ingest-{}.sst", n));
+        self.write_file(table, entries, &path)?;
+        self.ingest_files(table, &[path])
+    }
+
+    /// An SST file with the options of the column families.
+    fn write_file(&self, _table: usize, entries: &[Entry], dest: &Path) -> anyhow::Result<()> {
+        let mut writer = rocksdb::SstFileWriter::create(&self.opts);
+        writer.open(dest)?;
+        for (key, value) in entries {
+            writer.put(key, value)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Moves the files into the tree, or copies them if they are on another filesystem.
+    fn ingest_files(&self, table: usize, files: &[PathBuf]) -> anyhow::Result<()> {
+        let mut opts = rocksdb::IngestExternalFileOptions::default();
+        opts.set_move_files(true);
+        self.db
+            .ingest_external_file_cf_opts(self.cf(table), &opts, files.to_vec())?;
+        Ok(())
+    }
+
+    fn compact(&self) -> anyhow::Result<()> {
+        for table in 0..self.tables.len() {
+            self.db
+                .compact_range_cf(self.cf(table), None::<&[u8]>, None::<&[u8]>);
+        }
+        Ok(())
+    }
+
+    /// A checkpoint, which hard links the SST files, so `compact` makes no difference.
+    fn backup(&self, dest: &Path, _compact: bool) -> anyhow::Result<()> {
+        rocksdb::checkpoint::Checkpoint::new(&self.db)?.create_checkpoint(dest)?;
+        Ok(())
+    }
+
+    fn compaction_report(&self) -> anyhow::Result<String> {
+        let mut out = String::from("Compaction:\n");
+        if let Some(stall) = self.stall_time()? {
+            out += &format!("  writes stalled for {:?}\n", stall);
+        }
+        out += &self.properties(COMPACTION_PROPERTIES)?;
+        if self.blob_files {
+            out += &self.properties(BLOB_PROPERTIES)?;
+            out += "Blob garbage collection:\n";
+            for (ticker, count) in tickers(&self.opts, BLOB_GC_TICKERS)?.unwrap_or_default() {
+                out += &format!("  {}: {}\n", ticker, count);
+            }
+        }
+        Ok(out)
+    }
+}
+
+struct RocksdbTx<'a> {
+    engine: &'a RocksdbEngine,
+    batch: rocksdb::WriteBatch,
+}
+
+impl ReadTx for RocksdbTx<'_> {
+    fn get(&self, table: usize, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(self.engine.db.get_cf(self.engine.cf(table), key)?)
+    }
+
+    fn multi_get(&self, keys: &[(usize, &[u8])]) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+        let keys = keys
+            .iter()
+            .map(|(table, key)| (self.engine.cf(*table), *key));
+        let values = self.engine.db.multi_get_cf(keys).into_iter();
+        Ok(values.collect::<Result<_, _>>()?)
+    }
+
+    fn cursor(&self, table: usize) -> anyhow::Result<Box<dyn Cursor + '_>> {
+        let iter = self.engine.db.raw_iterator_cf(self.engine.cf(table));
+        Ok(Box::new(RocksdbCursor(iter)))
+    }
+}
+
+impl Tx for RocksdbTx<'_> {
+    fn put(&mut self, table: usize, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.batch.put_cf(self.engine.cf(table), key, value);
+        Ok(())
+    }
+
+    fn delete(&mut self, table: usize, key: &[u8]) -> anyhow::Result<()> {
+        self.batch.delete_cf(self.engine.cf(table), key);
+        Ok(())
+    }
+
+    /// A single range tombstone.
+    fn delete_range(&mut self, table: usize, start: &[u8], end: &[u8]) -> anyhow::Result<()> {
+        self.batch
+            .delete_range_cf(self.engine.cf(table), start, end);
+        Ok(())
+    }
+
+    fn add(&mut self, table: usize, key: &[u8], delta: u64) -> anyhow::Result<()> {
+        self.batch
+            .merge_cf(self.engine.cf(table), key, delta.to_le_bytes());
+        Ok(())
+    }
+
+    fn commit(self: Box<Self>) -> anyhow::Result<()> {
+        let mut opts = rocksdb::WriteOptions::default();
+        opts.set_sync(self.engine.sync);
+        opts.disable_wal(!self.engine.wal);
+        // The perf context is per thread, like the commits.
+        set_perf_stats(PerfStatsLevel::EnableTime);
+        let mut perf = PerfContext::default();
+        perf.reset();
+        self.engine.db.write_opt(self.batch, &opts)?;
+        let us = |metric| perf.metric(metric) / 1000;
+        super::set_commit_info(vec![
+            ("rocksdb_wal_us", us(PerfMetric::WriteWalTime)),
+            ("rocksdb_memtable_us", us(PerfMetric::WriteMemtableTime)),
+            ("rocksdb_delay_us", us(PerfMetric::WriteDelayTime)),
+            (
+                "rocksdb_scheduling_us",
+                us(PerfMetric::WriteSchedulingFlushesCompactionsTime),
+            ),
+            (
+                "rocksdb_thread_wait_us",
+                us(PerfMetric::WriteThreadWaitNanos),
+            ),
+            (
+                "rocksdb_processing_us",
+                us(PerfMetric::WritePreAndPostProcessTime),
+            ),
+        ]);
+        Ok(())
+    }
+}
+
+/// A cursor over the database or, for the transactional engine, a transaction.
+pub(super) struct RocksdbCursor<'a, D: rocksdb::DBAccess = rocksdb::DB>(
+    pub(super) rocksdb::DBRawIteratorWithThreadMode<'a, D>,
+);
+
+impl<D: rocksdb::DBAccess> RocksdbCursor<'_, D> {
+    fn entry(&self) -> anyhow::Result<Option<Entry>> {
+        if !self.0.valid() {
+            self.0.status()?;
+            return Ok(None);
+        }
+        match (self.0.key(), self.0.value()) {
+            (Some(key), Some(value)) => Ok(Some((key.to_vec(), value.to_vec()))),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl<D: rocksdb::DBAccess> Cursor for RocksdbCursor<'_, D> {
+    fn first(&mut self) -> anyhow::Result<Option<Entry>> {
+        self.0.seek_to_first();
+        self.entry()
+    }
+
+    fn last(&mut self) -> anyhow::Result<Option<Entry>> {
+        self.0.seek_to_last();
+        self.entry()
+    }
+
+    fn seek(&mut self, key: &[u8]) -> anyhow::Result<Option<Entry>> {
+        self.0.seek(key);
+        self.entry()
+    }
+
+    fn seek_for_prev(&mut self, key: &[u8]) -> anyhow::Result<Option<Entry>> {
+        self.0.seek_for_prev(key);
+        self.entry()
+    }
+
+    /// Stays off the end once the iterator is invalid, stepping it then is undefined.
+    fn next(&mut self) -> anyhow::Result<Option<Entry>> {
+        if !self.0.valid() {
+            return Ok(None);
+        }
+        self.0.next();
+        self.entry()
+    }
+
+    fn prev(&mut self) -> anyhow::Result<Option<Entry>> {
+        if !self.0.valid() {
+            return Ok(None);
+        }
+        self.0.prev();
+        self.entry()
+    }
+}
@@ -0,0 +1,190 @@
+//! rocksdb with transactions instead of plain write batches: the pessimistic ones of a
+//! `TransactionDB`, which lock the keys they write or read, or the optimistic ones of an
+//! `OptimisticTransactionDB`, which fail the commit if another one changed the keys they read.
+//! Reads see the snapshot taken at the start of the transaction and its own writes, like in mdbx.
+
+use super::{
+    rocksdb::{options, tickers, wal, RocksdbCursor, WRITE_TICKERS},
+    Cursor, Engine, ReadTx, Tx,
+};
+use crate::Cli;
+use rocksdb::{OptimisticTransactionDB, ReadOptions, Transaction, TransactionDB, WriteOptions};
+
+/// The databases with transactions.
+pub trait TxnDb: Send + Sync + Sized {
+    fn transaction(&self, opts: &WriteOptions) -> Transaction<'_, Self>;
+
+    fn cf(&self, name: &str) -> Option<&rocksdb::ColumnFamily>;
+
+    fn flush_wal(&self) -> anyhow::Result<()>;
+}
+
+impl TxnDb for TransactionDB {
+    fn transaction(&self, opts: &WriteOptions) -> Transaction<'_, Self> {
+        let mut txn_opts = rocksdb::TransactionOptions::default();
+        txn_opts.set_snapshot(true);
+        self.transaction_opt(opts, &txn_opts)
+    }
+
+    fn cf(&self, name: &str) -> Option<&rocksdb::ColumnFamily> {
+        self.cf_handle(name)
+    }
+
+    fn flush_wal(&self) -> anyhow::Result<()> {
+        Ok(TransactionDB::flush_wal(self, true)?)
+    }
+}
+
+impl TxnDb for OptimisticTransactionDB {
+    fn transaction(&self, opts: &WriteOptions) -> Transaction<'_, Self> {
+        let mut txn_opts = rocksdb::OptimisticTransactionOptions::default();
+        txn_opts.set_snapshot(true);
+        self.transaction_opt(opts, &txn_opts)
+    }
+
+    fn cf(&self, name: &str) -> Option<&rocksdb::ColumnFamily> {
+        self.cf_handle(name)
+    }
+
+    fn flush_wal(&self) -> anyhow::Result<()> {
+        Ok(OptimisticTransactionDB::flush_wal(self, true)?)
+    }
+}
+
+pub struct RocksdbTxnEngine<D> {
+    db: D,
+    /// Kept for the statistics.
+    opts: rocksdb::Options,
+    /// The column family of every table.
+    tables: Vec<String>,
+    sync: bool,
+    wal: bool,
+}
+
+/// Opens the pessimistic or, with `--rocksdb-optimistic`, the optimistic database. Neither has a
+/// read-only mode.
+pub fn open(cli: &Cli) -> anyhow::Result<Box<dyn Engine>> {
+    let (opts, tables) = options(cli)?;
+    let cfs = tables
+        .iter()
+        .map(|name| rocksdb::ColumnFamilyDescriptor::new(name, opts.clone()));
+    let (wal, sync) = wal(cli)?;
+    if cli.rocksdb.optimistic {
+        let db = OptimisticTransactionDB::open_cf_descriptors(&opts, &cli.path, cfs)?;
+        return Ok(Box::new(RocksdbTxnEngine {
+            db,
+            opts,
+            tables,
+            sync,
+            wal,
+        }));
+    }
+    let txn_db_opts = rocksdb::TransactionDBOptions::default();
+    let db = TransactionDB::open_cf_descriptors(&opts, &txn_db_opts, &cli.path, cfs)?;
+    Ok(Box::new(RocksdbTxnEngine {
+        db,
+        opts,
+        tables,
+        sync,
+        wal,
+    }))
+}
+
+impl<D: TxnDb> RocksdbTxnEngine<D> {
+    fn transaction(&self) -> Transaction<'_, D> {
+        let mut opts = WriteOptions::default();
+        opts.set_sync(self.sync);
+        opts.disable_wal(!self.wal);
+        self.db.transaction(&opts)
+    }
+
+    fn cf(&self, table: usize) -> &rocksdb::ColumnFamily {
+        self.db
+            .cf(&self.tables[table])
+            .expect("all column families are opened")
+    }
+}
+
+impl<D: TxnDb> Engine for RocksdbTxnEngine<D> {
+    fn begin(&self) -> anyhow::Result<Box<dyn Tx + '_>> {
+        Ok(Box::new(RocksdbTxnTx {
+            engine: self,
+            txn: self.transaction(),
+            for_update: true,
+        }))
+    }
+
+    /// A transaction that is never committed, for its snapshot.
+    fn begin_read(&self) -> anyhow::Result<Box<dyn ReadTx + '_>> {
+        Ok(Box::new(RocksdbTxnTx {
+            engine: self,
+            txn: self.transaction(),
+            for_update: false,
+        }))
+    }
+
+    fn sync(&self) -> anyhow::Result<()> {
+        self.db.flush_wal()
+    }
+
+    fn engine_written(&self) -> anyhow::Result<Option<u64>> {
+        let counts = tickers(&self.opts, WRITE_TICKERS)?;
+        Ok(counts.map(|counts| counts.iter().map(|(_, count)| count).sum()))
+    }
+}
+
+struct RocksdbTxnTx<'a, D> {
+    engine: &'a RocksdbTxnEngine<D>,
+    txn: Transaction<'a, D>,
+    /// Whether reads lock the keys or, if optimistic, make the commit check them for conflicts.
+    for_update: bool,
+}
+
+impl<D: TxnDb> RocksdbTxnTx<'_, D> {
+    /// Reads at the snapshot of the transaction, which it only uses for its conflict checks
+    /// otherwise.
+    fn read_opts(&self) -> ReadOptions {
+        let mut opts = ReadOptions::default();
+        opts.set_snapshot(&self.txn.snapshot());
+        opts
+    }
+}
+
+impl<D: TxnDb> ReadTx for RocksdbTxnTx<'_, D> {
+    fn get(&self, table: usize, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        let cf = self.engine.cf(table);
+        let opts = self.read_opts();
+        if self.for_update {
+            return Ok(self.txn.get_for_update_cf_opt(cf, key, true, &opts)?);
+        }
+        Ok(self.txn.get_cf_opt(cf, key, &opts)?)
+    }
+
+    fn cursor(&self, table: usize) -> anyhow::Result<Box<dyn Cursor + '_>> {
+        let iter = self
+            .txn
+            .raw_iterator_cf_opt(self.engine.cf(table), self.read_opts());
+        Ok(Box::new(RocksdbCursor(iter)))
+    }
+}
+
+impl<D: TxnDb> Tx for RocksdbTxnTx<'_, D> {
+    fn put(&mut self, table: usize, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        Ok(self.txn.put_cf(self.engine.cf(table), key, value)?)
+    }
+
+    fn delete(&mut self, table: usize, key: &[u8]) -> anyhow::Result<()> {
+        Ok(self.txn.delete_cf(self.engine.cf(table), key)?)
+    }
+
+    fn add(&mut self, table: usize, key: &[u8], delta: u64) -> anyhow::Result<()> {
+        Ok(self
+            .txn
+            .merge_cf(self.engine.cf(table), key, delta.to_le_bytes())?)
+    }
+
+    /// Fails with `Busy` if an optimistic transaction conflicts with another one.
+    fn commit(self: Box<Self>) -> anyhow::Result<()> {
+        Ok(self.txn.commit()?)
+    }
+}
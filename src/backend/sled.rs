@@ -0,0 +1,158 @@
+use super::{Cursor, Engine, Entry, ReadTx, SyncMode, Tx};
+use crate::Cli;
+use std::ops::Bound;
+
+pub struct SledEngine {
+    db: sled::Db,
+    trees: Vec<sled::Tree>,
+    sync: bool,
+}
+
+impl SledEngine {
+    /// sled has no read-only mode, it locks the database for a single process either way.
+    pub fn open(cli: &Cli, _readonly: bool) -> anyhow::Result<Self> {
+        // sled flushes in the background every 500ms by default, which makes it safe-nosync.
+        let flush_every_ms = match cli.sync_mode {
+            SyncMode::Durable | SyncMode::SafeNoSync => Some(500),
+            SyncMode::UtterlyNoSync | SyncMode::Periodic(_) => None,
+        };
+        let db = sled::Config::new()
+            .path(&cli.path)
+            .flush_every_ms(flush_every_ms)
+            .open()?;
+        let trees = (0..cli.tables)
+            .map(|t| match super::table_name(cli, t) {
+                Some(name) => db.open_tree(name),
+                None => Ok((*db).clone()),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(SledEngine {
+            db,
+            trees,
+            sync: matches!(cli.sync_mode, SyncMode::Durable),
+        })
+    }
+}
+
+impl Engine for SledEngine {
+    fn begin(&self) -> anyhow::Result<Box<dyn Tx + '_>> {
+        Ok(Box::new(SledTx {
+            engine: self,
+            batches: self.trees.iter().map(|_| sled::Batch::default()).collect(),
+            sync: self.sync,
+        }))
+    }
+
+    fn begin_read(&self) -> anyhow::Result<Box<dyn ReadTx + '_>> {
+        Ok(Box::new(SledTx {
+            engine: self,
+            batches: Vec::new(),
+            sync: false,
+        }))
+    }
+
+    fn sync(&self) -> anyhow::Result<()> {
+        self.db.flush()?;
+        Ok(())
+    }
+}
+
+/// A batch per tree. Batches of different trees are applied one after the other, so a crash can
+/// leave a transaction over several tables partially applied.
+struct SledTx<'a> {
+    engine: &'a SledEngine,
+    batches: Vec<sled::Batch>,
+    sync: bool,
+}
+
+impl ReadTx for SledTx<'_> {
+    fn get(&self, table: usize, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        let value = self.engine.trees[table].get(key)?;
+        Ok(value.map(|value| value.to_vec()))
+    }
+
+    fn cursor(&self, table: usize) -> anyhow::Result<Box<dyn Cursor + '_>> {
+        Ok(Box::new(SledCursor {
+            tree: &self.engine.trees[table],
+            pos: None,
+        }))
+    }
+}
+
+impl Tx for SledTx<'_> {
+    fn put(&mut self, table: usize, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.batches[table].insert(key, value);
+        Ok(())
+    }
+
+    fn delete(&mut self, table: usize, key: &[u8]) -> anyhow::Result<()> {
+        self.batches[table].remove(key);
+        Ok(())
+    }
+
+    fn commit(self: Box<Self>) -> anyhow::Result<()> {
+        for (tree, batch) in self.engine.trees.iter().zip(self.batches) {
+            tree.apply_batch(batch)?;
+        }
+        if self.sync {
+            self.engine.db.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// sled has no positioned cursor, so every step is a fresh range query from the last key.
+struct SledCursor<'a> {
+    tree: &'a sled::Tree,
+    pos: Option<Vec<u8>>,
+}
+
+impl SledCursor<'_> {
+    fn entry(&mut self, found: Option<(sled::IVec, sled::IVec)>) -> anyhow::Result<Option<Entry>> {
+        let entry = found.map(|(key, value)| (key.to_vec(), value.to_vec()));
+        self.pos = entry.as_ref().map(|(key, _)| key.clone());
+        Ok(entry)
+    }
+}
+
+impl Cursor for SledCursor<'_> {
+    fn first(&mut self) -> anyhow::Result<Option<Entry>> {
+        let found = self.tree.first()?;
+        self.entry(found)
+    }
+
+    fn last(&mut self) -> anyhow::Result<Option<Entry>> {
+        let found = self.tree.last()?;
+        self.entry(found)
+    }
+
+    fn seek(&mut self, key: &[u8]) -> anyhow::Result<Option<Entry>> {
+        let found = self.tree.range(key..).next().transpose()?;
+        self.entry(found)
+    }
+
+    fn seek_for_prev(&mut self, key: &[u8]) -> anyhow::Result<Option<Entry>> {
+        let found = self.tree.range(..=key).next_back().transpose()?;
+        self.entry(found)
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Entry>> {
+        let found = match self.pos.as_deref() {
+            Some(from) => self
+                .tree
+                .range::<&[u8], _>((Bound::Excluded(from), Bound::Unbounded))
+                .next()
+                .transpose()?,
+            None => None,
+        };
+        self.entry(found)
+    }
+
+    fn prev(&mut self) -> anyhow::Result<Option<Entry>> {
+        let found = match self.pos.as_deref() {
+            Some(to) => self.tree.range(..to).next_back().transpose()?,
+            None => None,
+        };
+        self.entry(found)
+    }
+}
@@ -0,0 +1,246 @@
+use super::{Cursor, Engine, Entry, ReadTx, SyncMode, Tx};
+use crate::Cli;
+use rusqlite::OptionalExtension;
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+/// SQLite has a single writer anyway, so the connection is simply locked for the duration of a
+/// write transaction. Readers open their own connections, in WAL mode they don't block the writer.
+pub struct SqliteEngine {
+    conn: Mutex<rusqlite::Connection>,
+    path: PathBuf,
+    tables: Vec<String>,
+}
+
+impl SqliteEngine {
+    pub fn open(cli: &Cli, readonly: bool) -> anyhow::Result<Self> {
+        let path = PathBuf::from(&cli.path).join("data.sqlite");
+        let tables: Vec<String> = (0..cli.tables)
+            .map(|t| super::table_name(cli, t).unwrap_or_else(|| "kv".to_string()))
+            .collect();
+        if readonly {
+            let flags = rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY;
+            let conn = rusqlite::Connection::open_with_flags(&path, flags)?;
+            return Ok(SqliteEngine {
+                conn: Mutex::new(conn),
+                path,
+                tables,
+            });
+        }
+
+        std::fs::create_dir_all(&cli.path)?;
+        let conn = rusqlite::Connection::open(&path)?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        // In WAL mode NORMAL only syncs on checkpoints, `sync` forces one.
+        let synchronous = match cli.sync_mode {
+            SyncMode::Durable => "FULL",
+            SyncMode::SafeNoSync | SyncMode::Periodic(_) => "NORMAL",
+            SyncMode::UtterlyNoSync => "OFF",
+        };
+        conn.pragma_update(None, "synchronous", synchronous)?;
+        for name in &tables {
+            // Without the rowid the table is clustered by key, like in the other engines.
+            conn.execute(
+                &format!(
+                    "CREATE TABLE IF NOT EXISTS {} (key BLOB PRIMARY KEY, value BLOB) WITHOUT ROWID",
+                    name
+                ),
+                [],
+            )?;
+        }
+        Ok(SqliteEngine {
+            conn: Mutex::new(conn),
+            path,
+            tables,
+        })
+    }
+}
+
+impl Engine for SqliteEngine {
+    fn begin(&self) -> anyhow::Result<Box<dyn Tx + '_>> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute_batch("BEGIN")?;
+        Ok(Box::new(SqliteTx {
+            conn,
+            tables: &self.tables,
+            committed: false,
+        }))
+    }
+
+    fn begin_read(&self) -> anyhow::Result<Box<dyn ReadTx + '_>> {
+        let conn = rusqlite::Connection::open(&self.path)?;
+        conn.execute_batch("BEGIN")?;
+        Ok(Box::new(SqliteTx {
+            conn,
+            tables: &self.tables,
+            committed: false,
+        }))
+    }
+
+    fn sync(&self) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("PRAGMA wal_checkpoint(FULL)", [], |_| Ok(()))?;
+        Ok(())
+    }
+
+    /// `VACUUM INTO` always compacts.
+    fn backup(&self, dest: &Path, _compact: bool) -> anyhow::Result<()> {
+        std::fs::create_dir(dest)?;
+        let file = dest.join("data.sqlite");
+        let conn = self.conn.lock().unwrap();
+        conn.execute("VACUUM INTO ?1", [file.to_string_lossy()])?;
+        Ok(())
+    }
+
+    /// Rebuilds the database file without free pages.
+    fn compact(&self) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute_batch("VACUUM")?;
+        Ok(())
+    }
+}
+
+/// `rusqlite::Transaction` would borrow the guard, so the transaction is managed by hand.
+struct SqliteTx<'a, C: Deref<Target = rusqlite::Connection>> {
+    conn: C,
+    tables: &'a [String],
+    committed: bool,
+}
+
+impl<C: Deref<Target = rusqlite::Connection>> Drop for SqliteTx<'_, C> {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = self.conn.execute_batch("ROLLBACK");
+        }
+    }
+}
+
+impl<C: Deref<Target = rusqlite::Connection>> ReadTx for SqliteTx<'_, C> {
+    fn get(&self, table: usize, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        let sql = format!("SELECT value FROM {} WHERE key = ?1", self.tables[table]);
+        Ok(self
+            .conn
+            .prepare_cached(&sql)?
+            .query_row([key], |row| row.get(0))
+            .optional()?)
+    }
+
+    fn cursor(&self, table: usize) -> anyhow::Result<Box<dyn Cursor + '_>> {
+        Ok(Box::new(SqliteCursor {
+            conn: &self.conn,
+            table: &self.tables[table],
+            pos: None,
+        }))
+    }
+}
+
+impl Tx for SqliteTx<'_, MutexGuard<'_, rusqlite::Connection>> {
+    fn put(&mut self, table: usize, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        let sql = format!(
+            "INSERT OR REPLACE INTO {} (key, value) VALUES (?1, ?2)",
+            self.tables[table]
+        );
+        self.conn
+            .prepare_cached(&sql)?
+            .execute(rusqlite::params![key, value])?;
+        Ok(())
+    }
+
+    fn delete(&mut self, table: usize, key: &[u8]) -> anyhow::Result<()> {
+        let sql = format!("DELETE FROM {} WHERE key = ?1", self.tables[table]);
+        self.conn.prepare_cached(&sql)?.execute([key])?;
+        Ok(())
+    }
+
+    fn delete_range(&mut self, table: usize, start: &[u8], end: &[u8]) -> anyhow::Result<()> {
+        let sql = format!(
+            "DELETE FROM {} WHERE key >= ?1 AND key < ?2",
+            self.tables[table]
+        );
+        self.conn.prepare_cached(&sql)?.execute([start, end])?;
+        Ok(())
+    }
+
+    fn commit(mut self: Box<Self>) -> anyhow::Result<()> {
+        self.conn.execute_batch("COMMIT")?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+/// Every step is a query for the nearest key in the requested direction.
+struct SqliteCursor<'a> {
+    conn: &'a rusqlite::Connection,
+    table: &'a str,
+    pos: Option<Vec<u8>>,
+}
+
+impl SqliteCursor<'_> {
+    fn query(&mut self, sql: &str, key: Option<&[u8]>) -> anyhow::Result<Option<Entry>> {
+        let mut stmt = self.conn.prepare_cached(sql)?;
+        let entry: Option<Entry> = match key {
+            Some(key) => stmt.query_row([key], |row| Ok((row.get(0)?, row.get(1)?))),
+            None => stmt.query_row([], |row| Ok((row.get(0)?, row.get(1)?))),
+        }
+        .optional()?;
+        self.pos = entry.as_ref().map(|(key, _)| key.clone());
+        Ok(entry)
+    }
+
+    fn step(&mut self, sql: &str) -> anyhow::Result<Option<Entry>> {
+        match self.pos.take() {
+            Some(from) => self.query(sql, Some(&from)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Cursor for SqliteCursor<'_> {
+    fn first(&mut self) -> anyhow::Result<Option<Entry>> {
+        let sql = format!("SELECT key, value FROM {} ORDER BY key LIMIT 1", self.table);
+        self.query(&sql, None)
+    }
+
+    fn last(&mut self) -> anyhow::Result<Option<Entry>> {
+        let sql = format!(
+            "SELECT key, value FROM {} ORDER BY key DESC LIMIT 1",
+            self.table
+        );
+        self.query(&sql, None)
+    }
+
+    fn seek(&mut self, key: &[u8]) -> anyhow::Result<Option<Entry>> {
+        let sql = format!(
+            "SELECT key, value FROM {} WHERE key >= ?1 ORDER BY key LIMIT 1",
+            self.table
+        );
+        self.query(&sql, Some(key))
+    }
+
+    fn seek_for_prev(&mut self, key: &[u8]) -> anyhow::Result<Option<Entry>> {
+        let sql = format!(
+            "SELECT key, value FROM {} WHERE key <= ?1 ORDER BY key DESC LIMIT 1",
+            self.table
+        );
+        self.query(&sql, Some(key))
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Entry>> {
+        let sql = format!(
+            "SELECT key, value FROM {} WHERE key > ?1 ORDER BY key LIMIT 1",
+            self.table
+        );
+        self.step(&sql)
+    }
+
+    fn prev(&mut self) -> anyhow::Result<Option<Entry>> {
+        let sql = format!(
+            "SELECT key, value FROM {} WHERE key < ?1 ORDER BY key DESC LIMIT 1",
+            self.table
+        );
+        self.step(&sql)
+    }
+}
@@ -0,0 +1,321 @@
+//! Recording the operations of a run, so that `replay` can execute them again on any engine.
+//!
+//! A trace is a text file with one operation per line:
+//!
+//! ```text
+//! get <table> <key>
+//! seek <table> <key>
+//! put <table> <key> <value length> <value hash>
+//! del <table> <key>
+//! delrange <table> <start key> <end key>
+//! add <table> <key> <delta>
+//! dup <key> <value>
+//! commit
+//! ```
+//!
+//! Keys and dupsort values are hex, the hash is the FNV-1a of the value. Reads of read
+//! transactions are recorded as they happen, everything a write transaction does only when it
+//! commits, so its reads and writes appear together and in order, transactions in commit order
+//! and aborted ones leave no trace. A committed nested transaction is part of its parent. Cursor
+//! iteration is not recorded.
+
+use super::{Cursor, Engine, Entry, OnDuplicate, ReadTx, Tx};
+use crate::{values, Cli};
+use std::{
+    cell::RefCell,
+    fmt::Write as _,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+/// The trace of the process, shared by all engines it opens, like the shards.
+static TRACE: OnceLock<Mutex<BufWriter<File>>> = OnceLock::new();
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+pub fn unhex(hex: &str) -> anyhow::Result<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        anyhow::bail!("Odd length hex: {}", hex);
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| Ok(u8::from_str_radix(&hex[i..i + 2], 16)?))
+        .collect()
+}
+
+pub fn hash(value: &[u8]) -> u64 {
+    value.iter().fold(0xcbf29ce484222325, |hash: u64, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Generates a stand-in for a recorded value, of the same length and the same for every replay.
+pub fn value(cli: &Cli, len: usize, hash: u64) -> Vec<u8> {
+    let mut rand = rand_pcg::Pcg64::new(hash as u128, 0x60e11a7bf9cb254560e11a7bf9cb2545);
+    let mut value = vec![0; len];
+    values::fill(&mut rand, &mut value, cli.compressibility);
+    value
+}
+
+/// Records every operation on the wrapped engine into the trace.
+pub struct Traced {
+    engine: Box<dyn Engine>,
+    out: &'static Mutex<BufWriter<File>>,
+}
+
+impl Traced {
+    /// Creates the trace the first time, later engines of the process append to it.
+    pub fn new(engine: Box<dyn Engine>, path: &Path) -> anyhow::Result<Self> {
+        let out = match TRACE.get() {
+            Some(out) => out,
+            None => {
+                let file = BufWriter::new(File::create(path)?);
+                TRACE.get_or_init(|| Mutex::new(file))
+            }
+        };
+        Ok(Traced { engine, out })
+    }
+}
+
+impl Drop for Traced {
+    fn drop(&mut self) {
+        if let Err(err) = self.out.lock().unwrap().flush() {
+            tracing::error!("Flushing the trace failed: {:#}", err);
+        }
+    }
+}
+
+impl Engine for Traced {
+    fn begin(&self) -> anyhow::Result<Box<dyn Tx + '_>> {
+        Ok(Box::new(TracedTx {
+            txn: self.engine.begin()?,
+            out: self.out,
+            writes: RefCell::new(String::new()),
+            parent: None,
+        }))
+    }
+
+    fn begin_read(&self) -> anyhow::Result<Box<dyn ReadTx + '_>> {
+        Ok(Box::new(TracedReadTx {
+            txn: self.engine.begin_read()?,
+            out: self.out,
+        }))
+    }
+
+    forward_engine!(sync, print_stat, diagnostics, used_bytes, map_size, gc_stat);
+
+    /// Recorded like a transaction of puts.
+    fn ingest(&self, table: usize, entries: &[Entry]) -> anyhow::Result<()> {
+        self.engine.ingest(table, entries)?;
+        let mut out = self.out.lock().unwrap();
+        for (key, value) in entries {
+            let (len, hash) = (value.len(), hash(value));
+            writeln!(out, "put {} {} {} {:x}", table, hex(key), len, hash)?;
+        }
+        writeln!(out, "commit")?;
+        Ok(())
+    }
+
+    /// Not recorded, the trace doesn't know what the files hold.
+    fn ingest_files(&self, table: usize, files: &[PathBuf]) -> anyhow::Result<()> {
+        self.engine.ingest_files(table, files)
+    }
+
+    forward_engine!(
+        write_file,
+        engine_written,
+        compact,
+        compaction_report,
+        backup
+    );
+}
+
+fn record_read(
+    out: &Mutex<BufWriter<File>>,
+    op: &str,
+    table: usize,
+    key: &[u8],
+) -> anyhow::Result<()> {
+    writeln!(out.lock().unwrap(), "{} {} {}", op, table, hex(key))?;
+    Ok(())
+}
+
+struct TracedReadTx<'a> {
+    txn: Box<dyn ReadTx + 'a>,
+    out: &'static Mutex<BufWriter<File>>,
+}
+
+impl ReadTx for TracedReadTx<'_> {
+    fn get(&self, table: usize, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        record_read(self.out, "get", table, key)?;
+        self.txn.get(table, key)
+    }
+
+    fn cursor(&self, table: usize) -> anyhow::Result<Box<dyn Cursor + '_>> {
+        self.txn.cursor(table)
+    }
+
+    fn multi_get(&self, keys: &[(usize, &[u8])]) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+        for (table, key) in keys {
+            record_read(self.out, "get", *table, key)?;
+        }
+        self.txn.multi_get(keys)
+    }
+
+    fn seek(&self, table: usize, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        record_read(self.out, "seek", table, key)?;
+        self.txn.seek(table, key)
+    }
+
+    fn dups(&self, key: &[u8]) -> anyhow::Result<Vec<Vec<u8>>> {
+        self.txn.dups(key)
+    }
+}
+
+struct TracedTx<'a> {
+    txn: Box<dyn Tx + 'a>,
+    out: &'static Mutex<BufWriter<File>>,
+    /// The lines of the reads and writes, appended to the trace on commit.
+    writes: RefCell<String>,
+    /// The lines of the transaction a nested one belongs to, which it adds its own to on commit.
+    parent: Option<&'a RefCell<String>>,
+}
+
+impl ReadTx for TracedTx<'_> {
+    fn get(&self, table: usize, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        self.record_read("get", table, key);
+        self.txn.get(table, key)
+    }
+
+    fn cursor(&self, table: usize) -> anyhow::Result<Box<dyn Cursor + '_>> {
+        self.txn.cursor(table)
+    }
+
+    fn multi_get(&self, keys: &[(usize, &[u8])]) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+        for (table, key) in keys {
+            self.record_read("get", *table, key);
+        }
+        self.txn.multi_get(keys)
+    }
+
+    fn seek(&self, table: usize, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        self.record_read("seek", table, key);
+        self.txn.seek(table, key)
+    }
+
+    fn dups(&self, key: &[u8]) -> anyhow::Result<Vec<Vec<u8>>> {
+        self.txn.dups(key)
+    }
+}
+
+impl TracedTx<'_> {
+    fn record_read(&self, op: &str, table: usize, key: &[u8]) {
+        let _ = writeln!(self.writes.borrow_mut(), "{} {} {}", op, table, hex(key));
+    }
+
+    fn record_put(&mut self, table: usize, key: &[u8], value: &[u8]) {
+        let _ = writeln!(
+            self.writes.get_mut(),
+            "put {} {} {} {:x}",
+            table,
+            hex(key),
+            value.len(),
+            hash(value)
+        );
+    }
+}
+
+impl Tx for TracedTx<'_> {
+    fn put(&mut self, table: usize, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.record_put(table, key, value);
+        self.txn.put(table, key, value)
+    }
+
+    fn append(&mut self, table: usize, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.record_put(table, key, value);
+        self.txn.append(table, key, value)
+    }
+
+    fn delete(&mut self, table: usize, key: &[u8]) -> anyhow::Result<()> {
+        let _ = writeln!(self.writes.get_mut(), "del {} {}", table, hex(key));
+        self.txn.delete(table, key)
+    }
+
+    fn delete_range(&mut self, table: usize, start: &[u8], end: &[u8]) -> anyhow::Result<()> {
+        let _ = writeln!(
+            self.writes.get_mut(),
+            "delrange {} {} {}",
+            table,
+            hex(start),
+            hex(end)
+        );
+        self.txn.delete_range(table, start, end)
+    }
+
+    fn insert(
+        &mut self,
+        table: usize,
+        key: &[u8],
+        value: &[u8],
+        on_duplicate: OnDuplicate,
+    ) -> anyhow::Result<bool> {
+        // Replayed as a put, if it was written at all.
+        let len = self.writes.get_mut().len();
+        self.record_put(table, key, value);
+        let written = self.txn.insert(table, key, value, on_duplicate)?;
+        if !written {
+            self.writes.get_mut().truncate(len);
+        }
+        Ok(written)
+    }
+
+    fn add(&mut self, table: usize, key: &[u8], delta: u64) -> anyhow::Result<()> {
+        let _ = writeln!(
+            self.writes.get_mut(),
+            "add {} {} {}",
+            table,
+            hex(key),
+            delta
+        );
+        self.txn.add(table, key, delta)
+    }
+
+    fn put_dup(&mut self, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        let _ = writeln!(self.writes.get_mut(), "dup {} {}", hex(key), hex(value));
+        self.txn.put_dup(key, value)
+    }
+
+    fn nested(&mut self) -> anyhow::Result<Box<dyn Tx + '_>> {
+        Ok(Box::new(TracedTx {
+            txn: self.txn.nested()?,
+            out: self.out,
+            writes: RefCell::new(String::new()),
+            parent: Some(&self.writes),
+        }))
+    }
+
+    fn commit(self: Box<Self>) -> anyhow::Result<()> {
+        let TracedTx {
+            txn,
+            out,
+            writes,
+            parent,
+        } = *self;
+        txn.commit()?;
+        if let Some(parent) = parent {
+            parent.borrow_mut().push_str(&writes.into_inner());
+            return Ok(());
+        }
+        let mut out = out.lock().unwrap();
+        out.write_all(writes.into_inner().as_bytes())?;
+        writeln!(out, "commit")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,249 @@
+//! Bulk loading: keys in sorted order, appended to the end of every table or ingested as files
+//! instead of inserted at random places, optionally against a random fill of as many items.
+
+use crate::{
+    backend, interrupt, keys::KeyGen, stats, values, values::ValueDist, Cli, Entry, FillOpts,
+    FillSeqOpts, IngestOpts, ReaderOp, SubCommand,
+};
+use std::{path::PathBuf, time::Instant};
+
+/// Generates the items of a bulk load in sorted order and remembers their keys.
+struct Items {
+    rand: rand_pcg::Pcg64,
+    keys: KeyGen,
+    value_dist: ValueDist,
+    n: usize,
+    remaining: usize,
+}
+
+impl Items {
+    fn new(cli: &Cli, n: usize, value_dist: ValueDist) -> anyhow::Result<Self> {
+        let mut keys = KeyGen::new(cli, 0, 1)?;
+        keys.keys.reserve(n);
+        Ok(Items {
+            rand: cli.rng(0),
+            keys,
+            value_dist,
+            n,
+            remaining: n,
+        })
+    }
+
+    /// Returns up to `items` next items split by table, each part still sorted, and their bytes.
+    fn next(&mut self, cli: &Cli, items: usize) -> (Vec<Vec<Entry>>, usize) {
+        let mut tables = vec![Vec::new(); cli.tables];
+        let mut bytes = 0;
+        for _ in 0..items.min(self.remaining) {
+            let key = self.keys.sorted();
+            let mut value = vec![0; self.value_dist.sample(&mut self.rand)];
+            values::fill(&mut self.rand, &mut value, cli.compressibility);
+            cli.seal(&key, &mut value, self.n - self.remaining);
+            bytes += key.len() + value.len();
+            self.keys.keys.push(&key);
+            tables[cli.table(&key)].push((key, value));
+            self.remaining -= 1;
+        }
+        (tables, bytes)
+    }
+}
+
+pub fn run(cli: &Cli, opts: &FillSeqOpts) -> anyhow::Result<()> {
+    if opts.n == 0 || opts.batch_sz == 0 {
+        anyhow::bail!("Invalid sequential fill options: {:?}", opts);
+    }
+    crate::check_existing(cli)?;
+    interrupt::install();
+
+    tracing::info!(?cli, "Opening database");
+    let env = backend::open(cli)?;
+    let mut stats = cli.stats()?;
+    stats.set_total(opts.n);
+    let ops = stats.counter();
+    let mut items = Items::new(cli, opts.n, opts.value_dist)?;
+
+    while items.remaining > 0 && !interrupt::interrupted() {
+        let _batch = tracing::debug_span!("batch").entered();
+        let start = Instant::now();
+        let batch_sz = opts.batch_sz.min(items.remaining);
+        let (tables, bytes) = items.next(cli, batch_sz);
+
+        let (batch_lat, commit_lat) = if opts.ingest {
+            let batch_lat = start.elapsed();
+            let start = Instant::now();
+            for (table, entries) in tables.iter().enumerate() {
+                if !entries.is_empty() {
+                    tracing::debug_span!("ingest").in_scope(|| env.ingest(table, entries))?;
+                }
+            }
+            (batch_lat, start.elapsed())
+        } else {
+            let mut txn = env.begin()?;
+            for (table, entries) in tables.into_iter().enumerate() {
+                for (key, value) in entries {
+                    txn.append(table, &key, &value)?;
+                }
+            }
+            let batch_lat = start.elapsed();
+            let start = Instant::now();
+            tracing::debug_span!("commit").in_scope(|| txn.commit())?;
+            (batch_lat, start.elapsed())
+        };
+        ops.add(batch_sz);
+        if let Some(sync_lat) = crate::after_commit(cli, &*env, commit_lat)? {
+            stats.record("sync", sync_lat);
+        }
+        stats.record_batch(&*env, batch_sz, bytes, batch_lat, commit_lat)?;
+        stats.maybe_report();
+    }
+
+    stats.done();
+    print!("{}", env.compaction_report()?);
+    drop(env);
+    crate::keys::save_manifest(cli, &items.keys.keys)?;
+    if opts.against_random {
+        let mut random_cli = cli.clone();
+        random_cli.path = format!("{}.random", cli.path.trim_end_matches('/'));
+        random_cli.subcmd = SubCommand::Fill(FillOpts {
+            n: Some(opts.n),
+            fill_to: None,
+            batch_sz: opts.batch_sz,
+            value_dist: opts.value_dist,
+            // Only fresh keys, like the sorted fill.
+            cold: 1.0,
+            threads: 1,
+            readers: 0,
+            reader_op: ReaderOp::Get,
+            reader_txn_ops: 1000,
+            reader_scan_len: 100,
+        });
+        against(cli, &random_cli, ["sorted", "random"])?;
+    }
+    Ok(())
+}
+
+/// Builds files of the generated items in a directory next to the database, then ingests all
+/// of them at once, like importing a snapshot.
+pub fn ingest(cli: &Cli, opts: &IngestOpts) -> anyhow::Result<()> {
+    if opts.n == 0 || opts.file_items == 0 {
+        anyhow::bail!("Invalid ingestion options: {:?}", opts);
+    }
+    crate::check_existing(cli)?;
+    interrupt::install();
+
+    tracing::info!(?cli, "Opening database");
+    let env = backend::open(cli)?;
+    let staging = PathBuf::from(format!("{}.sst", cli.path.trim_end_matches('/')));
+    std::fs::create_dir_all(&staging)?;
+    let mut stats = cli.stats()?;
+    stats.set_total(opts.n);
+    let mut items = Items::new(cli, opts.n, opts.value_dist)?;
+
+    let start = Instant::now();
+    let mut files = vec![Vec::new(); cli.tables];
+    let mut file_bytes = 0;
+    while items.remaining > 0 && !interrupt::interrupted() {
+        let file_items = opts.file_items.min(items.remaining);
+        let (tables, _) = items.next(cli, file_items);
+        for (table, entries) in tables.iter().enumerate() {
+            if entries.is_empty() {
+                continue;
+            }
+            let file = staging.join(format!("{}-{}.sst", table, files[table].len()));
+            let file_start = Instant::now();
+            env.write_file(table, entries, &file)?;
+            stats.record("sst build", file_start.elapsed());
+            file_bytes += std::fs::metadata(&file)?.len();
+            files[table].push(file);
+        }
+        stats.add_ops(file_items);
+        stats.maybe_report();
+    }
+    let built = start.elapsed();
+
+    let start = Instant::now();
+    for (table, files) in files.iter().enumerate() {
+        if !files.is_empty() {
+            let table_start = Instant::now();
+            tracing::debug_span!("ingest").in_scope(|| env.ingest_files(table, files))?;
+            stats.record("ingest", table_start.elapsed());
+        }
+    }
+    let ingested = start.elapsed();
+    std::fs::remove_dir_all(&staging)?;
+
+    stats.done();
+    println!(
+        "Built {} files of {} bytes in {:?} with the data generation, ingested them in {:?}, \
+         {:.0} items/s overall",
+        files.iter().map(Vec::len).sum::<usize>(),
+        file_bytes,
+        built,
+        ingested,
+        (opts.n - items.remaining) as f64 / (built + ingested).as_secs_f64()
+    );
+    print!("{}", env.compaction_report()?);
+    drop(env);
+    crate::keys::save_manifest(cli, &items.keys.keys)?;
+    if opts.against_write {
+        let mut write_cli = cli.clone();
+        write_cli.path = format!("{}.write", cli.path.trim_end_matches('/'));
+        write_cli.subcmd = SubCommand::FillSeq(FillSeqOpts {
+            n: opts.n,
+            batch_sz: opts.batch_sz,
+            value_dist: opts.value_dist,
+            ingest: false,
+            against_random: false,
+        });
+        against(cli, &write_cli, ["ingested", "written"])?;
+    }
+    Ok(())
+}
+
+/// Runs the workload of `other` and prints it side by side with the one that just finished.
+pub fn against(cli: &Cli, other: &Cli, labels: [&str; 2]) -> anyhow::Result<()> {
+    let Some(first) = stats::take_summaries().pop() else {
+        anyhow::bail!("The {} load produced no report", labels[0]);
+    };
+    tracing::info!(path = %other.path, "Loading for comparison");
+    crate::run(other)?;
+    let Some(second) = stats::take_summaries().pop() else {
+        anyhow::bail!("The {} load produced no report", labels[1]);
+    };
+
+    let commit = |summary: &stats::Summary, q: usize| {
+        summary
+            .latencies
+            .iter()
+            .find(|(name, _)| *name == "commit")
+            .map_or(f64::NAN, |(_, lats)| lats[q].as_secs_f64() * 1e6)
+    };
+    let rows = [
+        (
+            "ops/s",
+            [&first, &second].map(|s| s.ops as f64 / s.elapsed.as_secs_f64()),
+        ),
+        (
+            "elapsed s",
+            [&first, &second].map(|s| s.elapsed.as_secs_f64()),
+        ),
+        ("commit p50 us", [commit(&first, 0), commit(&second, 0)]),
+        ("commit p99 us", [commit(&first, 1), commit(&second, 1)]),
+        (
+            "bytes on disk",
+            [
+                crate::db_size(cli.path.as_ref())? as f64,
+                crate::db_size(other.path.as_ref())? as f64,
+            ],
+        ),
+    ];
+    println!("{:<20}{:>16}{:>24}", "", labels[0], labels[1]);
+    for (metric, [first, second]) in rows {
+        println!(
+            "{:<20}{:>16.1}{:>24}",
+            metric,
+            first,
+            format!("{:.1} ({:+.1}%)", second, (second / first - 1.0) * 100.0)
+        );
+    }
+    Ok(())
+}
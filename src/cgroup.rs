@@ -0,0 +1,159 @@
+//! Limiting the resources of the run: the process moves into a cgroup v2 of its own, which needs
+//! root, or falls back to userspace imitations of the limits.
+
+use crate::{mem, sysio, units::ByteSize, Cli};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Where the cgroup v2 hierarchy is mounted.
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// The limits of `--io-limit`, named like the keys of `io.max`: `rbps`, `wbps`, `riops` and
+/// `wiops`, like `wbps=100MiB,wiops=1000`.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct IoLimit {
+    pub rbps: Option<u64>,
+    pub wbps: Option<u64>,
+    pub riops: Option<u64>,
+    pub wiops: Option<u64>,
+}
+
+impl FromStr for IoLimit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut limit = IoLimit::default();
+        for part in s.split(',') {
+            let Some((key, value)) = part.split_once('=') else {
+                anyhow::bail!("Expected <key>=<value>: {}", part);
+            };
+            match key {
+                "rbps" => limit.rbps = Some(value.parse::<ByteSize>()?.0),
+                "wbps" => limit.wbps = Some(value.parse::<ByteSize>()?.0),
+                "riops" => limit.riops = Some(value.parse()?),
+                "wiops" => limit.wiops = Some(value.parse()?),
+                _ => anyhow::bail!("Unknown I/O limit: {}", key),
+            }
+        }
+        Ok(limit)
+    }
+}
+
+impl IoLimit {
+    /// Returns the line of `io.max` for the disk holding `path`.
+    fn io_max(&self, path: &Path) -> anyhow::Result<String> {
+        let Some((major, minor)) = disk(path) else {
+            anyhow::bail!("No block device holds {}", path.display());
+        };
+        let mut line = format!("{}:{}", major, minor);
+        for (key, value) in [
+            ("rbps", self.rbps),
+            ("wbps", self.wbps),
+            ("riops", self.riops),
+            ("wiops", self.wiops),
+        ] {
+            if let Some(value) = value {
+                line += &format!(" {}={}", key, value);
+            }
+        }
+        Ok(line)
+    }
+}
+
+/// Keeps the limits in place until dropped.
+pub struct Limits {
+    cgroup: Option<PathBuf>,
+    _balloon: Option<mem::Balloon>,
+}
+
+/// Applies `--mem-limit` and `--io-limit`. Without a cgroup the memory goes into a balloon and
+/// the writes are throttled by sleeping after commits.
+pub fn apply(cli: &Cli) -> anyhow::Result<Limits> {
+    let mut settings = Vec::new();
+    if let Some(ByteSize(bytes)) = cli.mem_limit {
+        settings.push(("memory.max", bytes.to_string()));
+    }
+    if let Some(limit) = &cli.io_limit {
+        // The database may not exist yet, its directory does.
+        let path = Path::new(&cli.path);
+        let existing = path.ancestors().find(|p| p.exists());
+        let existing = existing.unwrap_or(Path::new("."));
+        match limit.io_max(existing) {
+            Ok(line) => settings.push(("io.max", line)),
+            Err(err) => tracing::info!("No io.max: {:#}", err),
+        }
+    }
+
+    let cgroup = PathBuf::from(CGROUP_ROOT).join(format!("torture-{}", std::process::id()));
+    let created = if settings.is_empty() {
+        None
+    } else {
+        match create(&cgroup, &settings) {
+            Ok(()) => Some(cgroup),
+            Err(err) => {
+                let _ = std::fs::remove_dir(&cgroup);
+                tracing::info!("No cgroup, limiting in userspace instead: {}", err);
+                None
+            }
+        }
+    };
+    let limited = |file: &str| created.is_some() && settings.iter().any(|(f, _)| *f == file);
+    let balloon = match cli.mem_limit {
+        Some(ByteSize(bytes)) if !limited("memory.max") => Some(mem::Balloon::inflate(bytes)?),
+        _ => None,
+    };
+    if let Some(limit) = cli.io_limit.filter(|_| !limited("io.max")) {
+        sysio::throttle_writes(limit)?;
+    }
+    Ok(Limits {
+        cgroup: created,
+        _balloon: balloon,
+    })
+}
+
+/// Creates the cgroup with the given settings and moves the process into it.
+fn create(cgroup: &Path, settings: &[(&str, String)]) -> std::io::Result<()> {
+    // The controllers might not be enabled for the children of the root yet.
+    let _ = std::fs::write(
+        Path::new(CGROUP_ROOT).join("cgroup.subtree_control"),
+        "+memory +io",
+    );
+    std::fs::create_dir(cgroup)?;
+    for (file, value) in settings {
+        std::fs::write(cgroup.join(file), value)?;
+        tracing::info!(cgroup = %cgroup.display(), file, value, "Limited the run");
+    }
+    std::fs::write(cgroup.join("cgroup.procs"), std::process::id().to_string())
+}
+
+impl Drop for Limits {
+    fn drop(&mut self) {
+        let Some(cgroup) = &self.cgroup else {
+            return;
+        };
+        // A cgroup can only be removed once it has no processes left.
+        let moved = std::fs::write(
+            Path::new(CGROUP_ROOT).join("cgroup.procs"),
+            std::process::id().to_string(),
+        );
+        if let Err(err) = moved.and_then(|()| std::fs::remove_dir(cgroup)) {
+            tracing::error!("Removing {} failed: {}", cgroup.display(), err);
+        }
+    }
+}
+
+/// Returns the major and minor number of the whole disk holding `path`, as `io.max` takes no
+/// partitions.
+fn disk(path: &Path) -> Option<(u64, u64)> {
+    let (major, minor) = sysio::device(path)?;
+    let sys = PathBuf::from(format!("/sys/dev/block/{}:{}", major, minor));
+    let dev = if sys.join("partition").exists() {
+        std::fs::read_to_string(sys.join("../dev")).ok()?
+    } else {
+        std::fs::read_to_string(sys.join("dev")).ok()?
+    };
+    let (major, minor) = dev.trim().split_once(':')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
@@ -0,0 +1,100 @@
+//! Running the same seeded workload on several engines and putting the results side by side. Each
+//! engine gets its own subdirectory of the base path, or `<path>.<kind>` with `--exact-path`.
+
+use crate::{backend::EngineKind, stats, Cli, CompareOpts, SubCommand};
+use std::time::Duration;
+
+pub fn run(cli: &Cli, opts: &CompareOpts) -> anyhow::Result<()> {
+    match *opts.workload {
+        SubCommand::Fill(_)
+        | SubCommand::FillSeq(_)
+        | SubCommand::Ingest(_)
+        | SubCommand::Read(_)
+        | SubCommand::Mixed(_)
+        | SubCommand::Scan(_)
+        | SubCommand::PrefixScan(_)
+        | SubCommand::Churn(_)
+        | SubCommand::Update(_)
+        | SubCommand::Dupsort(_)
+        | SubCommand::LargeValues(_)
+        | SubCommand::Nested(_)
+        | SubCommand::Rmw(_)
+        | SubCommand::Expire(_)
+        | SubCommand::Replay(_) => {}
+        ref workload => anyhow::bail!("Only workloads can be compared: {:?}", workload),
+    }
+
+    let mut results = Vec::new();
+    for kind in std::iter::once(cli.kind).chain(opts.with.iter().copied()) {
+        let mut engine_cli = cli.clone();
+        engine_cli.kind = kind;
+        engine_cli.path = cli.engine_path(kind);
+        engine_cli.subcmd = (*opts.workload).clone();
+        engine_cli.profile = cli.profile.as_ref().map(|p| p.for_engine(name(kind)));
+        tracing::info!(
+            engine = name(kind),
+            path = %engine_cli.path,
+            "Running the workload"
+        );
+        crate::run(&engine_cli)?;
+        let Some(summary) = stats::take_summaries().pop() else {
+            anyhow::bail!("The workload on {} produced no report", name(kind));
+        };
+        results.push((kind, summary, crate::db_size(engine_cli.path.as_ref())?));
+    }
+    report(&results);
+    Ok(())
+}
+
+pub fn name(kind: EngineKind) -> String {
+    format!("{:?}", kind).to_lowercase()
+}
+
+/// Prints a row per metric and a column per engine, with the difference to the first one.
+fn report(results: &[(EngineKind, stats::Summary, u64)]) {
+    let (_, base, _) = &results[0];
+    let mut rows: Vec<(String, Vec<f64>)> = Vec::new();
+    rows.push((
+        "ops/s".to_string(),
+        results
+            .iter()
+            .map(|(_, summary, _)| summary.ops as f64 / summary.elapsed.as_secs_f64())
+            .collect(),
+    ));
+    for (i, (name, _)) in base.latencies.iter().enumerate() {
+        for (q, label) in ["p50", "p99", "p999"].iter().enumerate() {
+            let values = results.iter().map(|(_, summary, _)| {
+                // Histograms of the same workload are created in the same order.
+                match summary.latencies.get(i) {
+                    Some((n, lats)) if n == name => lats[q].as_secs_f64() * 1e6,
+                    _ => f64::NAN,
+                }
+            });
+            rows.push((format!("{} {} us", name, label), values.collect()));
+        }
+    }
+    rows.push((
+        "bytes on disk".to_string(),
+        results.iter().map(|(_, _, size)| *size as f64).collect(),
+    ));
+
+    print!("{:<20}", "");
+    for (kind, _, _) in results {
+        print!("{:>24}", name(*kind));
+    }
+    println!();
+    for (metric, values) in rows {
+        print!("{:<20}", metric);
+        for (i, value) in values.iter().enumerate() {
+            let cell = if i == 0 {
+                format!("{:.1}", value)
+            } else {
+                format!("{:.1} ({:+.1}%)", value, (value / values[0] - 1.0) * 100.0)
+            };
+            print!("{:>24}", cell);
+        }
+        println!();
+    }
+    let elapsed: Duration = results.iter().map(|(_, summary, _)| summary.elapsed).sum();
+    println!("Compared {} engines in {:?}", results.len(), elapsed);
+}
@@ -0,0 +1,202 @@
+//! Run configuration files.
+//!
+//! A config is turned into command line arguments in front of the actual ones, so that flags given
+//! on the command line override it. Top-level keys are global flags, `command` picks the
+//! subcommand, a table named after a subcommand holds its flags and any other table prefixes its
+//! keys, so `[rocksdb] compression = "lz4"` becomes `--rocksdb-compression lz4`:
+//!
+//! ```toml
+//! kind = "mdbx"
+//! sync-mode = "safe-nosync"
+//! command = "fill"
+//!
+//! [mdbx]
+//! growth-step = 268435456
+//!
+//! [fill]
+//! n = 100000000
+//! value-dist = "uniform:32..512"
+//! ```
+//!
+//! The `run` subcommand instead executes the `[[phases]]` of the config in order, each a table with
+//! a `command` and its flags. Phases after the first continue on the database and key manifest of
+//! the previous ones.
+
+use crate::Cli;
+use clap::{CommandFactory, FromArgMatches};
+use std::{ffi::OsString, path::PathBuf};
+
+/// Parses the command line on top of the config file given with `--config`, if any.
+pub fn parse() -> anyhow::Result<Cli> {
+    let (cmd, subcommands) = command();
+    let args = merge(std::env::args_os().collect(), &subcommands)?;
+    let mut cli = Cli::from_arg_matches(&cmd.get_matches_from(args))?;
+    cli.resolve_path();
+    Ok(cli)
+}
+
+/// Returns the command line of every phase of the config, with the global flags of the actual
+/// command line applied.
+pub fn phases(cli: &Cli) -> anyhow::Result<Vec<(String, Cli)>> {
+    let Some(path) = &cli.config else {
+        anyhow::bail!("Phases are defined in the config file, pass it with --config.");
+    };
+    let config: toml::Table = std::fs::read_to_string(path)?.parse()?;
+    let Some(toml::Value::Array(phases)) = config.get("phases") else {
+        anyhow::bail!("{} has no [[phases]]", path.display());
+    };
+
+    let (cmd, subcommands) = command();
+    let args: Vec<OsString> = std::env::args_os().collect();
+    let globals = globals(&config, split(&args, &subcommands).0, &subcommands)?;
+    let mut clis = Vec::new();
+    for (i, phase) in phases.iter().enumerate() {
+        let toml::Value::Table(phase) = phase else {
+            anyhow::bail!("Phase {} is not a table", i);
+        };
+        let Some(toml::Value::String(command)) = phase.get("command") else {
+            anyhow::bail!("Phase {} has no command", i);
+        };
+        if command == "run" {
+            anyhow::bail!("Phases can't be nested");
+        }
+
+        let mut args = globals.clone();
+        args.push(command.into());
+        for (key, value) in phase {
+            if key != "command" {
+                push_flag(&mut args, None, key, value)?;
+            }
+        }
+        let mut cli = Cli::from_arg_matches(&cmd.clone().try_get_matches_from(args)?)?;
+        cli.resolve_path();
+        if i > 0 {
+            cli.y = false;
+            cli.cont = true;
+        }
+        clis.push((command.clone(), cli));
+    }
+    Ok(clis)
+}
+
+/// Returns the command, where the last of repeated flags wins, and the names of its subcommands.
+fn command() -> (clap::Command, Vec<String>) {
+    let mut cmd = Cli::command().args_override_self(true);
+    let subcommands: Vec<String> = cmd
+        .get_subcommands()
+        .map(|sub| sub.get_name().to_string())
+        .collect();
+    for name in &subcommands {
+        cmd = cmd.mut_subcommand(name, |sub| sub.args_override_self(true));
+    }
+    (cmd, subcommands)
+}
+
+/// Splits the arguments into the global ones, starting with the binary, and the subcommand ones.
+fn split<'a>(args: &'a [OsString], subcommands: &[String]) -> (&'a [OsString], &'a [OsString]) {
+    let split = args
+        .iter()
+        .position(|arg| {
+            subcommands
+                .iter()
+                .any(|sub| arg.to_str() == Some(sub.as_str()))
+        })
+        .unwrap_or(args.len());
+    args.split_at(split)
+}
+
+/// Returns the global flags of the config followed by the given ones.
+fn globals(
+    config: &toml::Table,
+    args: &[OsString],
+    subcommands: &[String],
+) -> anyhow::Result<Vec<OsString>> {
+    let mut globals = vec![args[0].clone()];
+    for (key, value) in config {
+        match value {
+            _ if key == "command" || key == "phases" || subcommands.contains(key) => {}
+            toml::Value::Table(table) => push_flags(&mut globals, Some(key.as_str()), table)?,
+            value => push_flag(&mut globals, None, key, value)?,
+        }
+    }
+    globals.extend_from_slice(&args[1..]);
+    Ok(globals)
+}
+
+fn merge(args: Vec<OsString>, subcommands: &[String]) -> anyhow::Result<Vec<OsString>> {
+    let Some(path) = config_path(&args) else {
+        return Ok(args);
+    };
+    let config: toml::Table = std::fs::read_to_string(&path)?.parse()?;
+
+    let (globals, sub) = split(&args, subcommands);
+    let mut merged = self::globals(&config, globals, subcommands)?;
+
+    let command = match sub.first() {
+        Some(command) => Some(command.to_string_lossy().into_owned()),
+        None => match config.get("command") {
+            Some(toml::Value::String(command)) => Some(command.clone()),
+            Some(value) => anyhow::bail!("Expected a subcommand name: {}", value),
+            None => None,
+        },
+    };
+    if let Some(command) = command {
+        merged.push(command.as_str().into());
+        match config.get(&command) {
+            Some(toml::Value::Table(table)) => push_flags(&mut merged, None, table)?,
+            Some(_) => anyhow::bail!("Expected a table of {} flags", command),
+            None => {}
+        }
+        merged.extend_from_slice(sub.get(1..).unwrap_or_default());
+    }
+    Ok(merged)
+}
+
+fn config_path(args: &[OsString]) -> Option<PathBuf> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let Some(arg) = arg.to_str() else {
+            continue;
+        };
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(path.into());
+        }
+    }
+    None
+}
+
+fn push_flags(
+    args: &mut Vec<OsString>,
+    prefix: Option<&str>,
+    table: &toml::Table,
+) -> anyhow::Result<()> {
+    for (key, value) in table {
+        push_flag(args, prefix, key, value)?;
+    }
+    Ok(())
+}
+
+fn push_flag(
+    args: &mut Vec<OsString>,
+    prefix: Option<&str>,
+    key: &str,
+    value: &toml::Value,
+) -> anyhow::Result<()> {
+    let key = key.replace('_', "-");
+    let flag = match prefix {
+        Some(prefix) => format!("--{}-{}", prefix, key),
+        None => format!("--{}", key),
+    };
+    match value {
+        toml::Value::Boolean(true) => args.push(flag.into()),
+        toml::Value::Boolean(false) => {}
+        toml::Value::String(value) => args.extend([flag.into(), value.into()]),
+        toml::Value::Integer(value) => args.extend([flag.into(), value.to_string().into()]),
+        toml::Value::Float(value) => args.extend([flag.into(), value.to_string().into()]),
+        value => anyhow::bail!("Unsupported value of {}: {}", flag, value),
+    }
+    Ok(())
+}
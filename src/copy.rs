@@ -0,0 +1,105 @@
+//! Streaming all entries from one database into another, possibly of another engine. Doubles as a
+//! benchmark of a sequential read on one side and a sequential write on the other.
+
+use crate::{backend, backend::EngineKind, digest::Digest, interrupt, Cli, CopyOpts};
+use std::{str::FromStr, time::Instant};
+
+/// An engine and the path of its database, like `mdbx:/tmp/db`.
+#[derive(Debug, Clone)]
+pub struct Location {
+    kind: EngineKind,
+    path: String,
+}
+
+impl FromStr for Location {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((kind, path)) = s.split_once(':') else {
+            anyhow::bail!("Expected <kind>:<path>: {}", s);
+        };
+        Ok(Location {
+            kind: kind.parse()?,
+            path: path.to_string(),
+        })
+    }
+}
+
+impl Location {
+    /// Returns the command line with the engine and path of the location.
+    fn cli(&self, cli: &Cli) -> Cli {
+        let mut cli = cli.clone();
+        cli.kind = self.kind;
+        cli.path = self.path.clone();
+        cli
+    }
+}
+
+/// Copies the tables, not the dupsort table, in batches of whole write transactions.
+pub fn run(cli: &Cli, opts: &CopyOpts) -> anyhow::Result<()> {
+    if opts.batch_sz == 0 {
+        anyhow::bail!("Invalid copy options: {:?}", opts);
+    }
+    let from_cli = match &opts.from {
+        Some(from) => from.cli(cli),
+        None => cli.clone(),
+    };
+    let to_cli = opts.to.cli(cli);
+    if !std::path::Path::new(&from_cli.path).exists() {
+        anyhow::bail!("Database does not exist, aborting.");
+    }
+    crate::check_existing(&to_cli)?;
+    interrupt::install();
+
+    tracing::info!(from = %from_cli.path, to = %to_cli.path, "Opening databases");
+    let from = backend::open_readonly(&from_cli)?;
+    let to = backend::open(&to_cli)?;
+    let txn = from.begin_read()?;
+    let mut stats = to_cli.stats()?;
+    let ops = stats.counter();
+    let mut digest = Digest::default();
+    for table in 0..cli.tables {
+        let mut cursor = txn.cursor(table)?;
+        let mut entry = cursor.first()?;
+        while entry.is_some() && !interrupt::interrupted() {
+            let _batch = tracing::debug_span!("batch").entered();
+            let start = Instant::now();
+            let mut batch = Vec::with_capacity(opts.batch_sz);
+            while let Some(next) = entry.take() {
+                batch.push(next);
+                entry = cursor.next()?;
+                if batch.len() == opts.batch_sz {
+                    break;
+                }
+            }
+            stats.record("read", start.elapsed());
+
+            let items = batch.len();
+            let mut bytes = 0;
+            let mut write = to.begin()?;
+            for (key, value) in batch {
+                digest.add(table, &key, &value);
+                bytes += key.len() + value.len();
+                write.put(table, &key, &value)?;
+            }
+            let batch_lat = start.elapsed();
+            let start = Instant::now();
+            tracing::debug_span!("commit").in_scope(|| write.commit())?;
+            let commit_lat = start.elapsed();
+            ops.add(items);
+            if let Some(sync_lat) = crate::after_commit(&to_cli, &*to, commit_lat)? {
+                stats.record("sync", sync_lat);
+            }
+            stats.record_batch(&*to, items, bytes, batch_lat, commit_lat)?;
+            stats.maybe_report();
+        }
+    }
+
+    stats.done();
+    println!(
+        "Copied {} entries from {} to {}, checksum {}",
+        digest.entries, from_cli.path, to_cli.path, digest
+    );
+    print!("{}", to.compaction_report()?);
+    Ok(())
+}
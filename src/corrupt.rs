@@ -0,0 +1,243 @@
+//! Bitrot: flips random bits or overwrites random pages of the database files, then checks in a
+//! child process what the engine makes of it. Reading a damaged page may well crash the engine,
+//! which the child process turns into a reportable outcome.
+
+use crate::{backend, keys, units::ByteSize, Cli, CorruptOpts};
+use rand::{Rng, RngCore};
+use std::{
+    fs::OpenOptions,
+    os::unix::fs::FileExt,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+
+/// Set in the environment of the child, which then checks instead of corrupting.
+const CHILD_ENV: &str = "TORTURE_CORRUPT_CHILD";
+
+/// The stream of the damage, distinct from the one of the data.
+const CORRUPT_STREAM: u128 = 5 << 64;
+
+/// Lock files hold no data, damaging them tells nothing about the engine.
+const LOCK_FILES: &[&str] = &["mdbx.lck", "lock.mdb", "LOCK"];
+
+/// The byte offsets within every file that may be damaged, like `0..1MiB`.
+#[derive(Debug, Copy, Clone)]
+pub struct Region {
+    start: u64,
+    end: u64,
+}
+
+impl FromStr for Region {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((start, end)) = s.split_once("..") else {
+            anyhow::bail!("Expected a region like 0..1MiB: {}", s);
+        };
+        let ByteSize(start) = start.parse()?;
+        let ByteSize(end) = end.parse()?;
+        if start >= end {
+            anyhow::bail!("Empty region: {}", s);
+        }
+        Ok(Region { start, end })
+    }
+}
+
+pub fn run(cli: &Cli, opts: &CorruptOpts) -> anyhow::Result<()> {
+    if std::env::var_os(CHILD_ENV).is_some() {
+        return check(cli);
+    }
+    if opts.bits == 0 && opts.pages == 0 || opts.page_size == 0 {
+        anyhow::bail!("Invalid corruption options: {:?}", opts);
+    }
+    if !Path::new(&cli.path).exists() {
+        anyhow::bail!("Database does not exist, aborting.");
+    }
+
+    let mut files = Vec::new();
+    list_files(Path::new(&cli.path), opts, &mut files)?;
+    let mut rand = cli.rng(CORRUPT_STREAM);
+    for _ in 0..opts.bits {
+        let Some((path, offset)) = pick(&files, opts.region, 1, &mut rand) else {
+            anyhow::bail!("No file has bytes in the region to damage");
+        };
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut byte = [0];
+        file.read_exact_at(&mut byte, offset)?;
+        let bit = rand.gen_range(0..8);
+        byte[0] ^= 1 << bit;
+        file.write_all_at(&byte, offset)?;
+        println!(
+            "Flipped bit {} of byte {} of {}",
+            bit,
+            offset,
+            path.display()
+        );
+    }
+    for _ in 0..opts.pages {
+        let Some((path, offset)) = pick(&files, opts.region, opts.page_size as u64, &mut rand)
+        else {
+            anyhow::bail!("No file has a whole page in the region to damage");
+        };
+        let file = OpenOptions::new().write(true).open(path)?;
+        let mut page = vec![0; opts.page_size];
+        rand.fill_bytes(&mut page);
+        file.write_all_at(&page, offset)?;
+        println!(
+            "Overwrote the page at byte {} of {} with garbage",
+            offset,
+            path.display()
+        );
+    }
+
+    // The child gets the very same arguments, so it sees the same options.
+    let status = Command::new(std::env::current_exe()?)
+        .args(std::env::args_os().skip(1))
+        .env(CHILD_ENV, "1")
+        .status()?;
+    if !status.success() {
+        println!("The check crashed: {}", status);
+    }
+    Ok(())
+}
+
+/// Collects the data files under `path` with their sizes.
+fn list_files(
+    path: &Path,
+    opts: &CorruptOpts,
+    files: &mut Vec<(PathBuf, u64)>,
+) -> anyhow::Result<()> {
+    let meta = std::fs::metadata(path)?;
+    if meta.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            list_files(&entry?.path(), opts, files)?;
+        }
+        return Ok(());
+    }
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let chosen = match &opts.files {
+        Some(pattern) => name.contains(pattern.as_str()),
+        None => true,
+    };
+    if chosen && !LOCK_FILES.contains(&name.as_ref()) {
+        files.push((path.to_path_buf(), meta.len()));
+    }
+    Ok(())
+}
+
+/// Picks a random `unit` aligned offset of a file within the region, every unit of every file
+/// being equally likely.
+fn pick<'a>(
+    files: &'a [(PathBuf, u64)],
+    region: Option<Region>,
+    unit: u64,
+    rand: &mut impl Rng,
+) -> Option<(&'a Path, u64)> {
+    let units: Vec<_> = files
+        .iter()
+        .map(|(path, size)| {
+            let (start, end) = match region {
+                Some(region) => (region.start, region.end.min(*size)),
+                None => (0, *size),
+            };
+            (path.as_path(), start.div_ceil(unit), end / unit)
+        })
+        .filter(|(_, start, end)| start < end)
+        .collect();
+    let total: u64 = units.iter().map(|(_, start, end)| end - start).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut at = rand.gen_range(0..total);
+    for (path, start, end) in units {
+        if at < end - start {
+            return Some((path, (start + at) * unit));
+        }
+        at -= end - start;
+    }
+    unreachable!("the offset is within the total")
+}
+
+/// Reopens the damaged database, scans every table and looks up the known keys, reporting
+/// whether the engine notices.
+fn check(cli: &Cli) -> anyhow::Result<()> {
+    let env = match panic::catch_unwind(|| backend::open_readonly(cli)) {
+        Ok(Ok(env)) => env,
+        Ok(Err(err)) => {
+            println!("Reopening failed: {:#}", err);
+            return Ok(());
+        }
+        Err(_) => {
+            println!("Reopening panicked");
+            return Ok(());
+        }
+    };
+    println!("Reopened");
+
+    match panic::catch_unwind(AssertUnwindSafe(|| scan(cli, &*env))) {
+        Ok(Ok((entries, damaged))) if cli.checksum_values => println!(
+            "Scanned {} entries, {} of them with a damaged value",
+            entries, damaged
+        ),
+        Ok(Ok((entries, _))) => println!(
+            "Scanned {} entries, damaged values go unnoticed without --checksum-values",
+            entries
+        ),
+        Ok(Err(err)) => println!("Scanning failed: {:#}", err),
+        Err(_) => println!("Scanning panicked"),
+    }
+
+    let keys = keys::load_manifest(cli)?;
+    if keys.is_empty() {
+        return Ok(());
+    }
+    match panic::catch_unwind(AssertUnwindSafe(|| lookup(cli, &*env, &keys))) {
+        Ok(Ok((missing, failed))) => println!(
+            "Looked up {} known keys, {} missing, {} failed",
+            keys.len(),
+            missing,
+            failed
+        ),
+        Ok(Err(err)) => println!("Looking up failed: {:#}", err),
+        Err(_) => println!("Looking up panicked"),
+    }
+    Ok(())
+}
+
+/// Returns the number of entries and of the ones whose value has a damaged seal.
+fn scan(cli: &Cli, env: &dyn backend::Engine) -> anyhow::Result<(usize, usize)> {
+    let txn = env.begin_read()?;
+    let (mut entries, mut damaged) = (0, 0);
+    for table in 0..cli.tables {
+        let mut cursor = txn.cursor(table)?;
+        let mut entry = cursor.first()?;
+        while let Some((key, value)) = &entry {
+            entries += 1;
+            if cli.check_value(table, key, value).is_err() {
+                damaged += 1;
+            }
+            entry = cursor.next()?;
+        }
+    }
+    Ok((entries, damaged))
+}
+
+/// Returns the number of keys that are missing and of the lookups that failed.
+fn lookup(
+    cli: &Cli,
+    env: &dyn backend::Engine,
+    keys: &keys::Keys,
+) -> anyhow::Result<(usize, usize)> {
+    let txn = env.begin_read()?;
+    let (mut missing, mut failed) = (0, 0);
+    for key in keys {
+        match txn.get(cli.table(key), key) {
+            Ok(Some(_)) => {}
+            Ok(None) => missing += 1,
+            Err(_) => failed += 1,
+        }
+    }
+    Ok((missing, failed))
+}
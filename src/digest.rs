@@ -0,0 +1,55 @@
+//! Order-independent digests of the database contents, to tell whether two databases hold the
+//! same entries regardless of how they got there.
+
+use std::fmt;
+
+/// The sum of the hashes of all entries, over two independent hashes so that a collision of one
+/// is not enough.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Digest {
+    sums: [u64; 2],
+    pub entries: u64,
+}
+
+impl Digest {
+    pub fn add(&mut self, table: usize, key: &[u8], value: &[u8]) {
+        for (sum, seed) in self
+            .sums
+            .iter_mut()
+            .zip([0xcbf29ce484222325, 0x84222325cbf29ce4])
+        {
+            *sum = sum.wrapping_add(hash(seed, table, key, value));
+        }
+        self.entries += 1;
+    }
+
+    pub fn merge(&mut self, other: &Digest) {
+        for (sum, other) in self.sums.iter_mut().zip(other.sums) {
+            *sum = sum.wrapping_add(other);
+        }
+        self.entries += other.entries;
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}{:016x}", self.sums[0], self.sums[1])
+    }
+}
+
+/// FNV-1a of the entry, with the key length so that the split between key and value counts,
+/// and a final mix so that the sums of similar entries don't cancel out.
+fn hash(seed: u64, table: usize, key: &[u8], value: &[u8]) -> u64 {
+    let (table, len) = (
+        (table as u64).to_le_bytes(),
+        (key.len() as u64).to_le_bytes(),
+    );
+    let bytes = table.iter().chain(&len).chain(key).chain(value);
+    let hash = bytes.fold(seed, |hash: u64, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    // The finalizer of splitmix64.
+    let hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    let hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+    hash ^ (hash >> 31)
+}
@@ -0,0 +1,98 @@
+//! Filling a size-limited filesystem until the engine runs out of space.
+//!
+//! The filesystem is an ext4 image on a loop device mounted at the database path, so this needs
+//! root. After the first failed batch the database is reopened and checked like after a crash.
+
+use crate::{backend, torture, Cli, DiskFullOpts};
+use std::{
+    ffi::OsStr,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    process::Command,
+};
+
+pub fn run(cli: &Cli, opts: &DiskFullOpts) -> anyhow::Result<()> {
+    if opts.batch_sz == 0 {
+        anyhow::bail!("Invalid disk full options: {:?}", opts);
+    }
+    if Path::new(&cli.path).exists() {
+        anyhow::bail!("{} already exists, the image is mounted there.", cli.path);
+    }
+
+    std::fs::create_dir_all(&cli.path)?;
+    let image = opts.image.as_os_str();
+    let size = opts.image_bytes.to_string();
+    let path = OsStr::new(&cli.path);
+    sh("fallocate", &[OsStr::new("-l"), OsStr::new(&size), image])?;
+    sh("mkfs.ext4", &[OsStr::new("-q"), OsStr::new("-F"), image])?;
+    sh(
+        "mount",
+        &[OsStr::new("-o"), OsStr::new("loop"), image, path],
+    )?;
+    let _mount = Mount(cli);
+
+    let committed = fill(cli, opts)?;
+
+    match backend::open(cli) {
+        Ok(env) => drop(env),
+        Err(err) => {
+            println!("Reopening failed: {:#}", err);
+            return Ok(());
+        }
+    }
+    let batches = torture::Batches::new(cli, usize::MAX, opts.batch_sz, opts.value_dist)?;
+    match torture::check(cli, batches, committed) {
+        Ok(()) => println!("Reopened, all {} committed batches survived", committed),
+        Err(err) => println!("Reopened, but the data is damaged: {:#}", err),
+    }
+    Ok(())
+}
+
+/// Writes batches until one fails and returns the number of committed ones.
+fn fill(cli: &Cli, opts: &DiskFullOpts) -> anyhow::Result<usize> {
+    let env = backend::open(cli)?;
+    let batches = torture::Batches::new(cli, usize::MAX, opts.batch_sz, opts.value_dist)?;
+    for (batch, items) in batches.enumerate() {
+        // Running out of space is where engines tend to panic instead of returning an error.
+        let written = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut txn = env.begin()?;
+            for (key, value) in items {
+                txn.put(cli.table(&key), &key, &value)?;
+            }
+            txn.commit()
+        }));
+        let err = match written {
+            Ok(Ok(())) => continue,
+            Ok(Err(err)) => format!("{:#}", err),
+            Err(_) => "panicked".to_string(),
+        };
+        println!(
+            "Batch {} failed with {} bytes on disk: {}",
+            batch,
+            crate::db_size(cli.path.as_ref())?,
+            err
+        );
+        return Ok(batch);
+    }
+    unreachable!("batches are endless")
+}
+
+fn sh(cmd: &str, args: &[&OsStr]) -> anyhow::Result<()> {
+    let status = Command::new(cmd).args(args).status()?;
+    if !status.success() {
+        anyhow::bail!("{} failed: {}", cmd, status);
+    }
+    Ok(())
+}
+
+/// Unmounts the image on drop, also when the run fails.
+struct Mount<'a>(&'a Cli);
+
+impl Drop for Mount<'_> {
+    fn drop(&mut self) {
+        if let Err(err) = sh("umount", &[OsStr::new(&self.0.path)]) {
+            tracing::error!("{:#}", err);
+        }
+        let _ = std::fs::remove_dir(&self.0.path);
+    }
+}
@@ -0,0 +1,102 @@
+//! Time-series keys with a retention: every batch writes keys of the next timestamp, and every
+//! `--prune-every` batches one transaction deletes all batches older than `--retention` as key
+//! ranges, like the pruning of historical data.
+//!
+//! rocksdb writes a range tombstone per table, mdbx walks the range with a cursor and returns the
+//! pages to its freelist, so the interesting part is how the space and the latencies behave after
+//! a few rounds of pruning.
+
+use crate::{backend, interrupt, stats::OpSampler, values, Cli, ExpireOpts};
+use rand::RngCore;
+use std::time::Instant;
+
+/// The stream of the generated keys and values, distinct from the ones of the other workloads.
+const STREAM: u128 = 7 << 64;
+
+pub fn run(cli: &Cli, opts: &ExpireOpts) -> anyhow::Result<()> {
+    if opts.batch_sz == 0 || opts.prune_every == 0 {
+        anyhow::bail!("Invalid expiry options: {:?}", opts);
+    }
+    crate::check_existing(cli)?;
+    interrupt::install();
+
+    tracing::info!(?cli, "Opening database");
+    let env = backend::open(cli)?;
+    let mut rand = cli.rng(STREAM);
+    let mut stats = cli.stats()?;
+    stats.set_total(opts.n);
+    let deadline = cli.deadline();
+    let mut limiter = cli.limiter(1);
+    let mut sampler = OpSampler::new(cli.sample_ops);
+
+    let (mut remaining, mut batch, mut pruned_to) = (opts.n, 0, 0);
+    while remaining > 0 && !deadline.passed() && !interrupt::interrupted() {
+        let _batch = tracing::debug_span!("batch").entered();
+        let batch_sz = opts.batch_sz.min(remaining);
+        let start = Instant::now();
+        let mut txn = env.begin()?;
+        let mut bytes = 0;
+        for i in 0..batch_sz {
+            let intended = limiter.wait();
+            let key = timestamp_key(cli, batch, &mut rand);
+            let mut value = vec![0; opts.value_dist.sample(&mut rand)];
+            values::fill(&mut rand, &mut value, cli.compressibility);
+            cli.seal(&key, &mut value, opts.n - remaining + i);
+            bytes += key.len() + value.len();
+            let op_start = sampler.start().map(|start| intended.unwrap_or(start));
+            txn.put(cli.table(&key), &key, &value)?;
+            if let Some(start) = op_start {
+                stats.record("put", start.elapsed());
+            }
+        }
+        let batch_lat = start.elapsed();
+        let start = Instant::now();
+        tracing::debug_span!("commit").in_scope(|| txn.commit())?;
+        let commit_lat = start.elapsed();
+        if let Some(sync_lat) = crate::after_commit(cli, &*env, commit_lat)? {
+            stats.record("sync", sync_lat);
+        }
+        stats.record_batch(&*env, batch_sz, bytes, batch_lat, commit_lat)?;
+        stats.add_ops(batch_sz);
+        remaining -= batch_sz;
+        batch += 1;
+
+        let expired = batch.saturating_sub(opts.retention);
+        if batch % opts.prune_every == 0 && expired > pruned_to {
+            let _prune = tracing::debug_span!("prune").entered();
+            let start = Instant::now();
+            let mut txn = env.begin()?;
+            let (from, to) = (pruned_to.to_be_bytes(), expired.to_be_bytes());
+            for table in 0..cli.tables {
+                txn.delete_range(table, &from, &to)?;
+            }
+            stats.record("prune", start.elapsed());
+            let start = Instant::now();
+            txn.commit()?;
+            stats.record("prune commit", start.elapsed());
+            tracing::debug!(from = pruned_to, to = expired, "Pruned");
+            pruned_to = expired;
+        }
+        stats.maybe_report();
+    }
+
+    println!(
+        "Wrote {} batches, pruned the first {} of them, {} bytes on disk",
+        batch,
+        pruned_to,
+        crate::db_size(cli.path.as_ref())?
+    );
+    stats.done();
+    print!("{}", env.compaction_report()?);
+    Ok(())
+}
+
+/// Returns a key of `--key-sz` bytes, at least 8, the timestamp big-endian followed by random
+/// bytes. The timestamp is the index of the batch, so that runs are reproducible.
+fn timestamp_key(cli: &Cli, timestamp: u64, rand: &mut impl RngCore) -> Vec<u8> {
+    let mut key = vec![0; cli.key_sz.max(8)];
+    let (head, tail) = key.split_at_mut(8);
+    head.copy_from_slice(&timestamp.to_be_bytes());
+    rand.fill_bytes(tail);
+    key
+}
@@ -0,0 +1,127 @@
+//! The environment of a run, recorded along with its results: the machine, the kernel and the
+//! filesystem and device holding the database. What can't be read is left out.
+
+use std::path::{Path, PathBuf};
+
+/// Returns the environment of a database at `db_path`, as named values.
+pub fn collect(db_path: &Path) -> Vec<(&'static str, String)> {
+    let mut env = Vec::new();
+    let mut push = |name: &'static str, value: Option<String>| {
+        if let Some(value) = value {
+            env.push((name, value));
+        }
+    };
+    push("host", read("/proc/sys/kernel/hostname"));
+    push("kernel", read("/proc/sys/kernel/osrelease"));
+    push("cpu", cpu_model());
+    push(
+        "cpus",
+        std::thread::available_parallelism()
+            .ok()
+            .map(|n| n.to_string()),
+    );
+    push(
+        "ram",
+        crate::mem::meminfo("MemTotal").ok().map(|b| b.to_string()),
+    );
+
+    // The database may not exist yet, its filesystem is the one of the closest existing parent.
+    let Ok(absolute) = std::env::current_dir().map(|dir| dir.join(db_path)) else {
+        return env;
+    };
+    let Some(path) = absolute.ancestors().find_map(|p| p.canonicalize().ok()) else {
+        return env;
+    };
+    if let Some(mount) = mount(&path) {
+        push("fs", Some(mount.fs));
+        push("mount", Some(mount.point));
+        push("mount_options", Some(mount.options));
+        push("source", Some(mount.source));
+    }
+    if let Some(disk) = disk(&path) {
+        push("device_model", read(disk.join("device/model")));
+        push(
+            "scheduler",
+            read(disk.join("queue/scheduler")).map(|scheduler| selected(&scheduler)),
+        );
+        push("rotational", read(disk.join("queue/rotational")));
+    }
+    env
+}
+
+pub struct Mount {
+    pub point: String,
+    pub fs: String,
+    pub source: String,
+    /// The options of the mount followed by the ones of the filesystem.
+    pub options: String,
+}
+
+/// Returns the mount holding `path`, the last one at the deepest mount point containing it.
+pub fn mount(path: &Path) -> Option<Mount> {
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+    let mut found: Option<Mount> = None;
+    for line in mountinfo.lines() {
+        // `id parent major:minor root point options [optional...] - fs source super-options`
+        let Some((mount, filesystem)) = line.split_once(" - ") else {
+            continue;
+        };
+        let (mount, filesystem): (Vec<&str>, Vec<&str>) =
+            (mount.split(' ').collect(), filesystem.split(' ').collect());
+        let ([_, _, _, _, point, options, ..], [fs, source, super_options, ..]) =
+            (mount.as_slice(), filesystem.as_slice())
+        else {
+            continue;
+        };
+        let point = point.replace("\\040", " ");
+        let deeper = found
+            .as_ref()
+            .is_none_or(|found| point.len() >= found.point.len());
+        if path.starts_with(&point) && deeper {
+            found = Some(Mount {
+                point,
+                fs: fs.to_string(),
+                source: source.to_string(),
+                options: format!("{},{}", options, super_options),
+            });
+        }
+    }
+    found
+}
+
+/// Returns the sysfs directory of the disk holding `path`, the parent of a partition.
+pub fn disk(path: &Path) -> Option<PathBuf> {
+    let (major, minor) = crate::sysio::device(path)?;
+    let dev = Path::new("/sys/dev/block")
+        .join(format!("{}:{}", major, minor))
+        .canonicalize()
+        .ok()?;
+    if dev.join("partition").exists() {
+        return dev.parent().map(Path::to_path_buf);
+    }
+    Some(dev)
+}
+
+fn cpu_model() -> Option<String> {
+    let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").ok()?;
+    cpuinfo.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        (key.trim() == "model name").then(|| value.trim().to_string())
+    })
+}
+
+/// Returns the scheduler in brackets, the one in use, of a list like `none [mq-deadline] kyber`.
+fn selected(schedulers: &str) -> String {
+    match schedulers
+        .split_once('[')
+        .and_then(|(_, rest)| rest.split_once(']'))
+    {
+        Some((selected, _)) => selected.to_string(),
+        None => schedulers.to_string(),
+    }
+}
+
+fn read(path: impl AsRef<Path>) -> Option<String> {
+    let value = std::fs::read_to_string(path).ok()?;
+    Some(value.trim().to_string()).filter(|value| !value.is_empty())
+}
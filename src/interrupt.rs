@@ -0,0 +1,45 @@
+//! Stopping runs early. The first SIGINT or SIGTERM lets the workloads finish their current batch
+//! and report, a second one kills the process as usual. The `--duration` deadline instead cuts
+//! the batch in flight short and commits what it has.
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle(signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+    // Storing to an atomic and signal() are both async-signal-safe.
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+    }
+}
+
+/// Installs the handler. Only workloads that check [`interrupted`] should install it.
+pub fn install() {
+    unsafe {
+        libc::signal(libc::SIGINT, handle as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, handle as libc::sighandler_t);
+    }
+}
+
+/// Whether the run was interrupted and should stop at the end of the current batch.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// The end of a run bounded by `--duration`, or of a reader process once its writer is done.
+pub struct Deadline(Option<Instant>);
+
+impl Deadline {
+    /// Starts now, `None` for no deadline.
+    pub fn new(duration: Option<Duration>) -> Self {
+        Deadline(duration.map(|duration| Instant::now() + duration))
+    }
+
+    pub fn passed(&self) -> bool {
+        self.0.is_some_and(|deadline| Instant::now() >= deadline) || crate::procs::writer_done()
+    }
+}
@@ -0,0 +1,428 @@
+//! Key generation.
+
+use crate::Cli;
+use rand::{Rng, RngCore};
+use rand_distr::{Distribution, Zipf};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    ops::Index,
+    path::PathBuf,
+    slice::ChunksExact,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// How fresh keys are generated and how known keys are picked.
+#[derive(Debug, Copy, Clone)]
+pub enum KeyDist {
+    /// Random keys, known keys are picked uniformly.
+    Uniform,
+    /// Random keys, known keys are picked with a zipfian skew of the given exponent towards the
+    /// oldest ones.
+    Zipf(f64),
+    /// Random keys, known keys are picked with a zipfian skew of the given exponent towards the
+    /// newest ones.
+    Latest(f64),
+    /// A big-endian counter, so every insertion goes to the end of the key space (of its prefix).
+    Sequential,
+    /// The current time in nanoseconds followed by random bytes. Mostly increasing, but threads
+    /// interleave.
+    MonotonicTimestamp,
+}
+
+impl FromStr for KeyDist {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "uniform" => Ok(KeyDist::Uniform),
+            None if s == "zipf" => Ok(KeyDist::Zipf(0.99)),
+            None if s == "latest" => Ok(KeyDist::Latest(0.99)),
+            None if s == "sequential" => Ok(KeyDist::Sequential),
+            None if s == "monotonic-timestamp" => Ok(KeyDist::MonotonicTimestamp),
+            Some(("zipf", theta)) => {
+                let theta: f64 = theta.parse()?;
+                if theta < 0.0 {
+                    anyhow::bail!("Zipf exponent must not be negative: {}", theta);
+                }
+                Ok(KeyDist::Zipf(theta))
+            }
+            Some(("latest", theta)) => {
+                let theta: f64 = theta.parse()?;
+                if theta < 0.0 {
+                    anyhow::bail!("Zipf exponent must not be negative: {}", theta);
+                }
+                Ok(KeyDist::Latest(theta))
+            }
+            _ => anyhow::bail!("Unknown key distribution: {}", s),
+        }
+    }
+}
+
+/// Generates keys of one stream and remembers them for later lookups.
+///
+/// A key is an optional prefix, one of `prefix_cardinality` big-endian numbers, followed by a
+/// suffix generated according to the distribution. Sequential keys of concurrent streams are
+/// interleaved: stream `i` of `n` produces `i, i + n, i + 2n, ...`.
+///
+/// Known keys are picked from the hot set, a window over them that moves on by `--hot-set-churn`
+/// keys per pick.
+pub struct KeyGen {
+    dist: KeyDist,
+    key_sz: usize,
+    prefix_bytes: usize,
+    prefix_cardinality: u64,
+    next: u64,
+    step: u64,
+    hot_set_size: Option<usize>,
+    hot_set_churn: f64,
+    /// The index of the oldest key of the hot set, with the fraction of churn towards the next.
+    hot_set_start: f64,
+    pub keys: Keys,
+}
+
+impl KeyGen {
+    pub fn new(cli: &Cli, stream: usize, streams: usize) -> anyhow::Result<Self> {
+        if cli.key_sz == 0 || cli.key_prefix_bytes > cli.key_sz {
+            anyhow::bail!(
+                "Invalid key layout: {} bytes with a {} byte prefix",
+                cli.key_sz,
+                cli.key_prefix_bytes
+            );
+        }
+        if cli.prefix_cardinality == 0
+            || (0 < cli.key_prefix_bytes
+                && cli.key_prefix_bytes < 8
+                && cli.prefix_cardinality > 1 << (8 * cli.key_prefix_bytes))
+        {
+            anyhow::bail!(
+                "{} prefixes do not fit into {} bytes",
+                cli.prefix_cardinality,
+                cli.key_prefix_bytes
+            );
+        }
+        if cli.hot_set_size == Some(0) {
+            anyhow::bail!("The hot set needs at least one key");
+        }
+        if !cli.hot_set_churn.is_finite() || cli.hot_set_churn < 0.0 {
+            anyhow::bail!("Invalid hot set churn: {}", cli.hot_set_churn);
+        }
+        Ok(KeyGen {
+            dist: cli.key_dist,
+            key_sz: cli.key_sz,
+            prefix_bytes: cli.key_prefix_bytes,
+            prefix_cardinality: cli.prefix_cardinality,
+            next: stream as u64,
+            step: streams as u64,
+            hot_set_size: cli.hot_set_size,
+            hot_set_churn: cli.hot_set_churn,
+            hot_set_start: 0.0,
+            keys: Keys::new(cli.key_sz),
+        })
+    }
+
+    /// The counter of the sequential keys, to resume the stream with [`KeyGen::set_next`].
+    pub fn next(&self) -> u64 {
+        self.next
+    }
+
+    pub fn set_next(&mut self, next: u64) {
+        self.next = next;
+    }
+
+    /// Replaces the distribution of the configuration, like a workload preset does.
+    pub fn set_dist(&mut self, dist: KeyDist) {
+        self.dist = dist;
+    }
+
+    /// Generates a fresh key with probability `cold`, otherwise picks one of the known keys.
+    pub fn gen(&mut self, rand: &mut impl Rng, cold: f32) -> Vec<u8> {
+        let mut key = Vec::new();
+        self.gen_into(rand, cold, &mut key);
+        key
+    }
+
+    /// Like [`KeyGen::gen`], but into `key`, so that a known key costs no allocation.
+    pub fn gen_into(&mut self, rand: &mut impl Rng, cold: f32, key: &mut Vec<u8>) {
+        key.clear();
+        if self.keys.is_empty() || rand.gen_bool(cold as f64) {
+            *key = self.fresh(rand);
+            self.keys.push(key);
+        } else {
+            key.extend_from_slice(self.pick(rand).unwrap());
+        }
+    }
+
+    /// Generates a key without remembering it.
+    pub fn fresh(&mut self, rand: &mut impl Rng) -> Vec<u8> {
+        let mut key = self.probe(rand);
+        let suffix = &mut key[self.prefix_bytes..];
+        match self.dist {
+            KeyDist::Uniform | KeyDist::Zipf(_) | KeyDist::Latest(_) => {}
+            KeyDist::Sequential => {
+                put_be(suffix, self.next);
+                self.next += self.step;
+            }
+            KeyDist::MonotonicTimestamp => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+                // Keep the most significant bytes if the suffix is too short for all of them.
+                let n = suffix.len().min(8);
+                let now = (now.as_nanos() as u64).checked_shr(64 - 8 * n as u32);
+                put_be(&mut suffix[..n], now.unwrap_or(0));
+            }
+        }
+        key
+    }
+
+    /// Generates the next key of the stream in the order of the whole key space, ignoring the
+    /// prefix and the distribution, for bulk loads. Not remembered either.
+    pub fn sorted(&mut self) -> Vec<u8> {
+        let mut key = vec![0; self.key_sz];
+        put_be(&mut key, self.next);
+        self.next += self.step;
+        key
+    }
+
+    /// Generates a random key with the configured layout, to be used as a seek target or a lookup
+    /// of a most likely absent key.
+    pub fn probe(&self, rand: &mut impl Rng) -> Vec<u8> {
+        let mut key = vec![0; self.key_sz];
+        let (prefix, suffix) = key.split_at_mut(self.prefix_bytes);
+        if !prefix.is_empty() {
+            put_be(prefix, rand.gen_range(0..self.prefix_cardinality));
+        }
+        rand.fill_bytes(suffix);
+        key
+    }
+
+    /// Picks one of the known keys of the hot set, then moves the hot set on.
+    pub fn pick(&mut self, rand: &mut impl Rng) -> Option<&[u8]> {
+        let known = self.keys.len();
+        if known == 0 {
+            return None;
+        }
+        let len = self.hot_set_size.map_or(known, |size| size.min(known));
+        let index = match self.dist {
+            KeyDist::Zipf(theta) => {
+                let zipf = Zipf::new(len as u64, theta).unwrap();
+                zipf.sample(rand) as usize - 1
+            }
+            KeyDist::Latest(theta) => {
+                let zipf = Zipf::new(len as u64, theta).unwrap();
+                len - zipf.sample(rand) as usize
+            }
+            _ => rand.gen_range(0..len),
+        };
+        let start = self.hot_set_start as usize % known;
+        self.hot_set_start = (self.hot_set_start + self.hot_set_churn) % known as f64;
+        Some(&self.keys[(start + index) % known])
+    }
+}
+
+/// Keys of one size back to back in a single buffer. Billions of them fit in memory this way, at
+/// their size each instead of an allocation and a `Vec` of 24 bytes.
+#[derive(Clone)]
+pub struct Keys {
+    key_sz: usize,
+    bytes: Vec<u8>,
+}
+
+impl Keys {
+    /// Keys of `key_sz` bytes, which must not be zero.
+    pub fn new(key_sz: usize) -> Self {
+        assert!(key_sz > 0, "keys can't be empty");
+        Keys {
+            key_sz,
+            bytes: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len() / self.key_sz
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Panics unless the key has the size of the others.
+    pub fn push(&mut self, key: &[u8]) {
+        assert_eq!(key.len(), self.key_sz, "a key of a different size");
+        self.bytes.extend_from_slice(key);
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.bytes.reserve(additional * self.key_sz);
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.bytes.truncate(len * self.key_sz);
+    }
+
+    /// Keeps only the keys `keep` returns true for, in their order.
+    pub fn retain(&mut self, mut keep: impl FnMut(&[u8]) -> bool) {
+        let mut kept = 0;
+        for i in 0..self.len() {
+            if keep(&self[i]) {
+                let at = i * self.key_sz;
+                self.bytes
+                    .copy_within(at..at + self.key_sz, kept * self.key_sz);
+                kept += 1;
+            }
+        }
+        self.truncate(kept);
+    }
+
+    /// Removes a key and moves the last one to its place, like `Vec::swap_remove`.
+    pub fn swap_remove(&mut self, index: usize) -> Vec<u8> {
+        let key = self[index].to_vec();
+        let last = self.bytes.len() - self.key_sz;
+        self.bytes.copy_within(last.., index * self.key_sz);
+        self.bytes.truncate(last);
+        key
+    }
+
+    pub fn iter(&self) -> ChunksExact<'_, u8> {
+        self.bytes.chunks_exact(self.key_sz)
+    }
+}
+
+impl Index<usize> for Keys {
+    type Output = [u8];
+
+    fn index(&self, index: usize) -> &[u8] {
+        &self.bytes[index * self.key_sz..][..self.key_sz]
+    }
+}
+
+impl<'a> IntoIterator for &'a Keys {
+    type Item = &'a [u8];
+    type IntoIter = ChunksExact<'a, u8>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> Extend<&'a [u8]> for Keys {
+    fn extend<I: IntoIterator<Item = &'a [u8]>>(&mut self, keys: I) {
+        for key in keys {
+            self.push(key);
+        }
+    }
+}
+
+/// The sidecar file next to the database listing the written keys, so that follow-up runs can
+/// target them.
+pub fn manifest_path(cli: &Cli) -> PathBuf {
+    format!("{}.keys", cli.path.trim_end_matches('/')).into()
+}
+
+/// Replaces the manifest with the given keys, each prefixed by its length as a little-endian u32.
+pub fn save_manifest<'a>(
+    cli: &Cli,
+    keys: impl IntoIterator<Item = &'a [u8]>,
+) -> anyhow::Result<()> {
+    if cli.dry_run {
+        return Ok(());
+    }
+    let mut out = BufWriter::new(File::create(manifest_path(cli))?);
+    let mut count = 0;
+    for key in keys {
+        out.write_all(&(key.len() as u32).to_le_bytes())?;
+        out.write_all(key)?;
+        count += 1;
+    }
+    out.flush()?;
+    tracing::info!(count, path = %manifest_path(cli).display(), "Saved the keys");
+    Ok(())
+}
+
+/// Loads the keys saved by a previous run, none if there is no manifest. They must all have the
+/// size of `--key-sz`.
+pub fn load_manifest(cli: &Cli) -> anyhow::Result<Keys> {
+    if cli.key_sz == 0 {
+        anyhow::bail!("Invalid key size: 0");
+    }
+    let mut keys = Keys::new(cli.key_sz);
+    let file = match File::open(manifest_path(cli)) {
+        Ok(file) => file,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(keys),
+        Err(err) => return Err(err.into()),
+    };
+    let mut file = BufReader::new(file);
+    let mut key = vec![0; cli.key_sz];
+    let mut len = [0; 4];
+    loop {
+        match file.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        }
+        let len = u32::from_le_bytes(len) as usize;
+        if len != cli.key_sz {
+            anyhow::bail!(
+                "The manifest has a key of {} bytes, run with the --key-sz of its fill",
+                len
+            );
+        }
+        file.read_exact(&mut key)?;
+        keys.push(&key);
+    }
+    Ok(keys)
+}
+
+/// Writes the low bytes of `value` big-endian into the whole of `buf`, zero padded on the left.
+fn put_be(buf: &mut [u8], value: u64) {
+    let n = buf.len().min(8);
+    let start = buf.len() - n;
+    buf[..start].fill(0);
+    buf[start..].copy_from_slice(&value.to_be_bytes()[8 - n..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(keys: &[&[u8]]) -> Keys {
+        let mut arena = Keys::new(2);
+        arena.extend(keys.iter().copied());
+        arena
+    }
+
+    #[test]
+    fn parses_key_dists() -> anyhow::Result<()> {
+        assert!(matches!("uniform".parse()?, KeyDist::Uniform));
+        assert!(matches!("sequential".parse()?, KeyDist::Sequential));
+        assert!(matches!(
+            "monotonic-timestamp".parse()?,
+            KeyDist::MonotonicTimestamp
+        ));
+        assert!(matches!("zipf".parse()?, KeyDist::Zipf(theta) if theta == 0.99));
+        assert!(matches!("latest:1.5".parse()?, KeyDist::Latest(theta) if theta == 1.5));
+        assert!("zipf:-1".parse::<KeyDist>().is_err());
+        assert!("latest:hot".parse::<KeyDist>().is_err());
+        assert!("hotspot".parse::<KeyDist>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn retain_keeps_the_order() {
+        let mut arena = keys(&[b"aa", b"bb", b"cc", b"dd"]);
+        arena.retain(|key| key != b"bb" && key != b"dd");
+        assert_eq!(arena.iter().collect::<Vec<_>>(), [b"aa", b"cc"]);
+        arena.retain(|_| false);
+        assert!(arena.is_empty());
+    }
+
+    #[test]
+    fn swap_remove_moves_the_last_key() {
+        let mut arena = keys(&[b"aa", b"bb", b"cc"]);
+        assert_eq!(arena.swap_remove(0), b"aa");
+        assert_eq!(arena.iter().collect::<Vec<_>>(), [b"cc", b"bb"]);
+        assert_eq!(arena.swap_remove(1), b"bb");
+        assert_eq!(arena.len(), 1);
+        assert_eq!(&arena[0], b"cc");
+    }
+}
@@ -0,0 +1,145 @@
+//! Large values, hundreds of KiB to tens of MiB, which mdbx keeps on overflow pages and rocksdb in
+//! huge blocks or blob files.
+//!
+//! Values are inserted, rewritten with a new size and deleted, so that the space they free has to
+//! be reused by values of other sizes. Afterwards all live values are read back and the space on
+//! disk is compared with the bytes that are live.
+
+use crate::{backend, interrupt, keys::KeyGen, stats::OpSampler, values, Cli, LargeValuesOpts};
+use rand::Rng;
+use std::time::Instant;
+
+pub fn run(cli: &Cli, opts: &LargeValuesOpts) -> anyhow::Result<()> {
+    if opts.batch_sz == 0 || opts.rewrite_pct + opts.delete_pct > 1.0 {
+        anyhow::bail!("Invalid large value options: {:?}", opts);
+    }
+    crate::check_existing(cli)?;
+    interrupt::install();
+
+    tracing::info!(?cli, "Opening database");
+    let env = backend::open(cli)?;
+
+    let mut rand = cli.rng(0);
+    let mut keys = KeyGen::new(cli, 0, 1)?;
+    // The size of every live value, by the index of its key.
+    let mut sizes: Vec<usize> = Vec::new();
+    let mut stats = cli.stats()?;
+    stats.set_total(opts.n);
+
+    let deadline = cli.deadline();
+    let mut limiter = cli.limiter(1);
+    let mut sampler = OpSampler::new(cli.sample_ops);
+    let mut done = 0;
+    while done < opts.n {
+        let _batch = tracing::debug_span!("batch").entered();
+        let mut txn = env.begin()?;
+        let batch_sz = opts.batch_sz.min(opts.n - done);
+        let mut bytes = 0;
+        let start = Instant::now();
+        for _ in 0..batch_sz {
+            let intended = limiter.wait();
+            let op = rand.gen::<f32>();
+            if !sizes.is_empty() && op < opts.delete_pct {
+                let index = rand.gen_range(0..sizes.len());
+                let key = keys.keys.swap_remove(index);
+                sizes.swap_remove(index);
+                let op_start = sampler.start().map(|start| intended.unwrap_or(start));
+                txn.delete(cli.table(&key), &key)?;
+                if let Some(start) = op_start {
+                    stats.record("delete", start.elapsed());
+                }
+            } else {
+                let rewrite = !sizes.is_empty() && op < opts.delete_pct + opts.rewrite_pct;
+                let index = if rewrite {
+                    rand.gen_range(0..sizes.len())
+                } else {
+                    let key = keys.fresh(&mut rand);
+                    keys.keys.push(&key);
+                    sizes.push(0);
+                    sizes.len() - 1
+                };
+                let key = keys.keys[index].to_vec();
+                sizes[index] = opts.value_dist.sample(&mut rand);
+                let mut data = vec![0; sizes[index]];
+                values::fill(&mut rand, &mut data, cli.compressibility);
+                cli.seal(&key, &mut data, done);
+                bytes += key.len() + data.len();
+                let op_start = sampler.start().map(|start| intended.unwrap_or(start));
+                txn.put(cli.table(&key), &key, &data)?;
+                if let Some(start) = op_start {
+                    stats.record(if rewrite { "rewrite" } else { "put" }, start.elapsed());
+                }
+            }
+            done += 1;
+            stats.add_ops(1);
+        }
+        let batch_lat = start.elapsed();
+
+        let start = Instant::now();
+        tracing::debug_span!("commit").in_scope(|| txn.commit())?;
+        let commit_lat = start.elapsed();
+        if let Some(sync_lat) = crate::after_commit(cli, &*env, commit_lat)? {
+            stats.record("sync", sync_lat);
+        }
+        stats.record_batch(&*env, batch_sz, bytes, batch_lat, commit_lat)?;
+        stats.maybe_report();
+        if interrupt::interrupted() {
+            tracing::info!(done, "Interrupted");
+            break;
+        }
+        if deadline.passed() {
+            tracing::info!(done, "Reached the deadline");
+            break;
+        }
+    }
+
+    let live: u64 = sizes.iter().map(|&size| (cli.key_sz + size) as u64).sum();
+    let on_disk = crate::db_size(cli.path.as_ref())?;
+    println!(
+        "{} live values of {} bytes take {} bytes on disk ({:.2}x)",
+        sizes.len(),
+        live,
+        on_disk,
+        on_disk as f64 / live.max(1) as f64
+    );
+    if let Some(gc) = env.gc_stat()? {
+        println!(
+            "{} of the {} pages in use are free",
+            gc.free_pages, gc.last_page
+        );
+    }
+
+    // Reads back every live value in a random order.
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    rand::seq::SliceRandom::shuffle(&mut order[..], &mut rand);
+    let txn = env.begin_read()?;
+    let start = Instant::now();
+    let mut read = 0;
+    for index in order {
+        let key = &keys.keys[index];
+        let op_start = Instant::now();
+        let Some(value) = txn.get(cli.table(key), key)? else {
+            anyhow::bail!("A live value is missing: {:02x?}", key);
+        };
+        stats.record("get", op_start.elapsed());
+        if value.len() != sizes[index] {
+            anyhow::bail!(
+                "A value of {} bytes was read back with {}",
+                sizes[index],
+                value.len()
+            );
+        }
+        cli.check_value(cli.table(key), key, &value)?;
+        read += value.len();
+    }
+    println!(
+        "Read back {} bytes at {:.1} MiB/s",
+        read,
+        read as f64 / start.elapsed().as_secs_f64() / (1 << 20) as f64
+    );
+    drop(txn);
+
+    stats.done();
+    print!("{}", env.compaction_report()?);
+    crate::keys::save_manifest(cli, &keys.keys)
+}
@@ -0,0 +1,273 @@
+//! A ledger of runs, one JSON object per line, and the comparison of two of its runs.
+//!
+//! Every run records its id, command line, the versions of the engine crates it was built with, its
+//! environment and the summaries of its workloads as flat metrics, `ops_per_s`, `<histogram>_<quantile>_us` and
+//! `db_size`. Workloads after the first of a run prefix their metrics with their index, like
+//! `1.commit_p99_us`.
+
+use crate::{compare, stats::Summary, Cli, CompareRunsOpts};
+use std::{
+    fmt::Write as _,
+    fs::OpenOptions,
+    io::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The crates whose versions a run records, the engine bindings and their native libraries.
+const ENGINE_CRATES: &[&str] = &[
+    "reth-libmdbx",
+    "reth-mdbx-sys",
+    "rocksdb",
+    "librocksdb-sys",
+    "sled",
+    "lmdb-rkv",
+    "lmdb-rkv-sys",
+    "redb",
+    "rusqlite",
+    "libsqlite3-sys",
+];
+
+const LOCKFILE: &str = include_str!("../Cargo.lock");
+
+/// A run read back from the ledger.
+struct Run {
+    id: String,
+    engines: Vec<(String, String)>,
+    env: Vec<(String, String)>,
+    metrics: Vec<(String, f64)>,
+}
+
+/// Appends the run to the ledger of `--ledger`, with the summaries of its workloads.
+pub fn record(cli: &Cli, summaries: &[Summary]) -> anyhow::Result<()> {
+    let Some(path) = &cli.ledger else {
+        return Ok(());
+    };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+    let id = cli
+        .run_id
+        .clone()
+        .unwrap_or_else(|| now.as_secs().to_string());
+    let args: Vec<String> = std::env::args()
+        .skip(1)
+        .map(|arg| format!("\"{}\"", escape(&arg)))
+        .collect();
+    let engines = engines()?;
+    let env = crate::fingerprint::collect(cli.path.as_ref());
+
+    let mut metrics = Vec::new();
+    for (i, summary) in summaries.iter().enumerate() {
+        let prefix = if i == 0 {
+            String::new()
+        } else {
+            format!("{}.", i)
+        };
+        let ops_per_s = summary.ops as f64 / summary.elapsed.as_secs_f64();
+        metrics.push(format!("\"{}ops_per_s\":{:.1}", prefix, ops_per_s));
+        for (name, lats) in &summary.latencies {
+            for (q, lat) in ["p50", "p99", "p999"].iter().zip(lats) {
+                let us = lat.as_secs_f64() * 1e6;
+                metrics.push(format!("\"{}{}_{}_us\":{:.1}", prefix, name, q, us));
+            }
+        }
+    }
+    if let Ok(size) = crate::db_size(cli.path.as_ref()) {
+        metrics.push(format!("\"db_size\":{}", size));
+    }
+
+    let mut ledger = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(
+        ledger,
+        "{{\"run\":\"{}\",\"timestamp_ms\":{},\"kind\":\"{}\",\"args\":[{}],\"engines\":{},\
+         \"env\":{},\"metrics\":{{{}}}}}",
+        escape(&id),
+        now.as_millis(),
+        compare::name(cli.kind),
+        args.join(","),
+        object_json(&engines),
+        object_json(&env),
+        metrics.join(",")
+    )?;
+    println!("Recorded run {} in {}", id, path.display());
+    Ok(())
+}
+
+/// Prints the metrics of two runs side by side and fails if any regressed by more than the
+/// threshold: throughput dropped, or a latency or the size on disk grew.
+pub fn compare_runs(cli: &Cli, opts: &CompareRunsOpts) -> anyhow::Result<()> {
+    let Some(path) = &cli.ledger else {
+        anyhow::bail!("Runs are compared from the ledger, pass it with --ledger.");
+    };
+    let ledger = std::fs::read_to_string(path)?;
+    let runs = ledger
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(parse)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    // The last run wins if an id was recorded twice.
+    let find = |id: &str| -> anyhow::Result<&Run> {
+        match runs.iter().rev().find(|run| run.id == id) {
+            Some(run) => Ok(run),
+            None => anyhow::bail!(
+                "No run {} among the {} in {}",
+                id,
+                runs.len(),
+                path.display()
+            ),
+        }
+    };
+    let (base, new) = (find(&opts.base)?, find(&opts.new)?);
+
+    for (base, new) in [(&base.engines, &new.engines), (&base.env, &new.env)] {
+        for (name, value) in new {
+            match base.iter().find(|(n, _)| n == name) {
+                Some((_, old)) if old != value => println!("{}: {} -> {}", name, old, value),
+                Some(_) => {}
+                None => println!("{}: {} (new)", name, value),
+            }
+        }
+    }
+
+    println!("{:<28}{:>16}{:>16}{:>10}", "", base.id, new.id, "change");
+    let mut regressions = 0;
+    for (metric, value) in &new.metrics {
+        let Some((_, old)) = base.metrics.iter().find(|(m, _)| m == metric) else {
+            continue;
+        };
+        let change = value / old - 1.0;
+        // The throughput is better higher, everything else lower.
+        let worse = if metric.ends_with("ops_per_s") {
+            -change
+        } else {
+            change
+        };
+        let flag = if worse > opts.threshold {
+            regressions += 1;
+            "  REGRESSION"
+        } else {
+            ""
+        };
+        println!(
+            "{:<28}{:>16.1}{:>16.1}{:>+9.1}%{}",
+            metric,
+            old,
+            value,
+            change * 100.0,
+            flag
+        );
+    }
+    if regressions > 0 {
+        anyhow::bail!(
+            "{} metrics regressed by more than {:.1}%",
+            regressions,
+            opts.threshold * 100.0
+        );
+    }
+    Ok(())
+}
+
+/// Returns the versions of the engine crates in the lockfile the binary was built with, with the
+/// commit of git dependencies.
+fn engines() -> anyhow::Result<Vec<(&'static str, String)>> {
+    let lockfile: toml::Table = LOCKFILE.parse()?;
+    let Some(toml::Value::Array(packages)) = lockfile.get("package") else {
+        return Ok(Vec::new());
+    };
+    let mut engines = Vec::new();
+    for package in packages {
+        let field = |key: &str| package.get(key).and_then(|v| v.as_str());
+        let (Some(name), Some(version)) = (field("name"), field("version")) else {
+            continue;
+        };
+        let Some(name) = ENGINE_CRATES.iter().find(|engine| **engine == name) else {
+            continue;
+        };
+        let version = match field("source").and_then(|source| source.split_once('#')) {
+            Some((_, rev)) => format!("{}@{}", version, rev),
+            None => version.to_string(),
+        };
+        engines.push((*name, version));
+    }
+    Ok(engines)
+}
+
+/// Parses a line of the ledger. Only the flat objects written by [`record`] are understood.
+fn parse(line: &str) -> anyhow::Result<Run> {
+    let Some(id) = string_field(line, "run") else {
+        anyhow::bail!("Not a ledger entry: {}", line);
+    };
+    let metrics = object(line, "metrics")
+        .into_iter()
+        .map(|(name, value)| Ok((name, value.parse()?)))
+        .collect::<anyhow::Result<_>>()?;
+    Ok(Run {
+        id,
+        engines: object(line, "engines"),
+        env: object(line, "env"),
+        metrics,
+    })
+}
+
+fn string_field(line: &str, field: &str) -> Option<String> {
+    let start = line.find(&format!("\"{}\":\"", field))? + field.len() + 4;
+    let len = line[start..].find('"')?;
+    Some(line[start..start + len].to_string())
+}
+
+/// Returns the members of a flat object, with the keys and string values unquoted.
+pub fn object(line: &str, field: &str) -> Vec<(String, String)> {
+    let Some(start) = line.find(&format!("\"{}\":{{", field)) else {
+        return Vec::new();
+    };
+    let mut members = Vec::new();
+    let mut member = String::new();
+    let (mut quoted, mut escaped) = (false, false);
+    for c in line[start + field.len() + 4..].chars() {
+        match c {
+            _ if escaped => {
+                member.push(c);
+                escaped = false;
+            }
+            '\\' if quoted => escaped = true,
+            '"' => {
+                quoted = !quoted;
+                member.push(c);
+            }
+            ',' | '}' if !quoted => {
+                if let Some((key, value)) = member.split_once(':') {
+                    let unquote = |s: &str| s.trim_matches('"').to_string();
+                    members.push((unquote(key), unquote(value)));
+                }
+                member.clear();
+                if c == '}' {
+                    break;
+                }
+            }
+            c => member.push(c),
+        }
+    }
+    members
+}
+
+/// Returns a flat object of string values.
+pub fn object_json(members: &[(&str, String)]) -> String {
+    let members: Vec<String> = members
+        .iter()
+        .map(|(name, value)| format!("\"{}\":\"{}\"", name, escape(value)))
+        .collect();
+    format!("{{{}}}", members.join(","))
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
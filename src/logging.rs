@@ -0,0 +1,45 @@
+//! Timestamped, structured events of a run via `tracing`, to line them up with kernel and I/O
+//! logs.
+//!
+//! Events like opening the database or an interruption are logged at info, every committed batch
+//! at debug, within `batch` and `commit` spans. `RUST_LOG` overrides the level, like
+//! `RUST_LOG=debug`. The reports with the statistics of a run stay plain text on stdout.
+
+use std::{fs::File, path::Path, str::FromStr, sync::Mutex};
+use tracing_subscriber::{fmt::writer::BoxMakeWriter, EnvFilter};
+
+#[derive(Debug, Copy, Clone)]
+pub enum LogFormat {
+    Pretty,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => anyhow::bail!("Unknown log format: {}", s),
+        }
+    }
+}
+
+/// Logs to the given file, or to stdout, from here on.
+pub fn init(format: LogFormat, file: Option<&Path>) -> anyhow::Result<()> {
+    let writer = match file {
+        Some(path) => BoxMakeWriter::new(Mutex::new(File::create(path)?)),
+        None => BoxMakeWriter::new(std::io::stdout),
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(file.is_none());
+    let result = match format {
+        LogFormat::Pretty => builder.try_init(),
+        LogFormat::Json => builder.json().try_init(),
+    };
+    result.map_err(|err| anyhow::anyhow!("Setting up logging failed: {}", err))
+}
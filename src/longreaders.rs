@@ -0,0 +1,155 @@
+//! Long-lived readers while a writer overwrites: the snapshots the readers hold keep the pages
+//! of the old values from being reused, so the free space and the database grow until the map is
+//! full. This measures the classic stuck reader failure on purpose.
+
+use crate::{backend, interrupt, rmw, values, Cli, LongReadersOpts};
+use rand::Rng;
+use std::{
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+/// The stream of the readers, distinct from the writer one.
+const READER_STREAM: u128 = 4 << 64;
+
+pub fn run(cli: &Cli, opts: &LongReadersOpts) -> anyhow::Result<()> {
+    if opts.batch_sz == 0 || opts.hot_keys == 0 {
+        anyhow::bail!("Invalid long reader options: {:?}", opts);
+    }
+    crate::check_existing(cli)?;
+    interrupt::install();
+
+    tracing::info!(?cli, "Opening database");
+    let env = backend::open(cli)?;
+    let stop = AtomicBool::new(false);
+    let slots_full = AtomicUsize::new(0);
+
+    let written = std::thread::scope(|s| {
+        let readers: Vec<_> = (0..opts.readers)
+            .map(|reader| {
+                let (env, stop, slots_full) = (&*env, &stop, &slots_full);
+                s.spawn(move || hold(env, cli, opts, reader, stop, slots_full))
+            })
+            .collect();
+        let written = write(&*env, cli, opts, &slots_full);
+        stop.store(true, Ordering::Relaxed);
+        let read = readers
+            .into_iter()
+            .try_for_each(|reader| reader.join().expect("long reader panicked"));
+        read.and(written)
+    })?;
+
+    println!(
+        "Wrote {} batches, {} bytes on disk, {} reader slot exhaustions",
+        written,
+        crate::db_size(cli.path.as_ref())?,
+        slots_full.load(Ordering::Relaxed)
+    );
+    print!("{}", env.diagnostics()?);
+    Ok(())
+}
+
+/// Opens a read transaction, keeps it for the hold time and starts over until stopped.
+fn hold(
+    env: &dyn backend::Engine,
+    cli: &Cli,
+    opts: &LongReadersOpts,
+    reader: usize,
+    stop: &AtomicBool,
+    slots_full: &AtomicUsize,
+) -> anyhow::Result<()> {
+    let mut rand = cli.rng(READER_STREAM + reader as u128);
+    while !stop.load(Ordering::Relaxed) {
+        let txn = match env.begin_read() {
+            Ok(txn) => txn,
+            Err(err) if is_readers_full(&err) => {
+                slots_full.fetch_add(1, Ordering::Relaxed);
+                std::thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        // Staggered, so that the readers don't all let go at once.
+        let hold = Duration::from_millis(rand.gen_range(opts.hold_ms / 2..=opts.hold_ms));
+        let start = Instant::now();
+        while start.elapsed() < hold && !stop.load(Ordering::Relaxed) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        drop(txn);
+    }
+    Ok(())
+}
+
+/// Overwrites the hot keys in batches and returns the number of committed batches. A batch that
+/// fails because the map is full is counted and retried later.
+fn write(
+    env: &dyn backend::Engine,
+    cli: &Cli,
+    opts: &LongReadersOpts,
+    slots_full: &AtomicUsize,
+) -> anyhow::Result<usize> {
+    let mut rand = cli.rng(0);
+    let keys: Vec<Vec<u8>> = (0..opts.hot_keys)
+        .map(|key| rmw::counter_key(cli, key))
+        .collect();
+    let mut stats = cli.stats()?;
+    let (mut committed, mut map_full) = (0, 0);
+    let mut last_report = Instant::now();
+    let deadline = cli.deadline();
+    while committed < opts.batches && !deadline.passed() && !interrupt::interrupted() {
+        let start = Instant::now();
+        let written = (|| {
+            let mut txn = env.begin()?;
+            for _ in 0..opts.batch_sz {
+                let key = &keys[rand.gen_range(0..keys.len())];
+                let mut value = vec![0; opts.value_dist.sample(&mut rand)];
+                values::fill(&mut rand, &mut value, cli.compressibility);
+                cli.seal(key, &mut value, committed);
+                txn.put(cli.table(key), key, &value)?;
+            }
+            txn.commit()
+        })();
+        match written {
+            Ok(()) => {
+                committed += 1;
+                stats.record("batch", start.elapsed());
+                stats.add_ops(opts.batch_sz);
+            }
+            Err(err) if is_map_full(&err) => {
+                map_full += 1;
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            Err(err) => return Err(err),
+        }
+
+        if last_report.elapsed() >= Duration::from_secs(cli.report_interval) {
+            let size = crate::db_size(cli.path.as_ref())?;
+            let free = match env.used_bytes()? {
+                Some(used) => format!(", {} free", size.saturating_sub(used)),
+                None => String::new(),
+            };
+            println!(
+                "{} batches, {} bytes on disk{}, {} map full, {} reader slots full",
+                committed,
+                size,
+                free,
+                map_full,
+                slots_full.load(Ordering::Relaxed)
+            );
+            last_report = Instant::now();
+        }
+    }
+    stats.done();
+    println!("The map was full {} times", map_full);
+    Ok(committed)
+}
+
+fn is_map_full(err: &anyhow::Error) -> bool {
+    let err = format!("{:#}", err);
+    err.contains("MAP_FULL") || err.contains("MapFull")
+}
+
+fn is_readers_full(err: &anyhow::Error) -> bool {
+    let err = format!("{:#}", err);
+    err.contains("READERS_FULL") || err.contains("ReadersFull")
+}
@@ -1,284 +1,2683 @@
+use anyhow::Context;
+use backend::{EngineKind, Entry, MdbxOpts, OnDuplicate, RocksdbOpts, SyncEvery, SyncMode};
 use clap::Parser;
-use rand::{Rng, RngCore};
-use reth_libmdbx::{
-    DatabaseFlags, Environment, EnvironmentFlags, Geometry, Mode, PageSize, WriteFlags, RW,
+use keys::{KeyDist, KeyGen, Keys};
+use output::{Output, OutputFormat};
+use progress::{Progress, WorkerProgress};
+use rand::Rng;
+use stats::{OpCounter, OpSampler, Stats, Warmup};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
 };
-use std::{path::PathBuf, rc::Rc, str::FromStr};
+use units::{ByteSize, Span};
+use values::ValueDist;
+
+mod backend;
+mod bulk;
+mod cgroup;
+mod compare;
+mod config;
+mod copy;
+mod corrupt;
+mod digest;
+mod diskfull;
+mod expire;
+mod fingerprint;
+mod interrupt;
+mod keys;
+mod large;
+mod ledger;
+mod logging;
+mod longreaders;
+mod mem;
+mod metrics;
+mod nested;
+mod openbench;
+mod output;
+mod procs;
+mod profile;
+mod progress;
+mod rate;
+mod replay;
+mod report;
+mod rmw;
+mod safety;
+mod stats;
+mod sweep;
+mod sysio;
+mod torture;
+mod units;
+mod values;
+mod ycsb;
 
 const GIGABYTE: usize = 1024 * 1024 * 1024;
 const TERABYTE: usize = GIGABYTE * 1024;
 
-const PATH: &str = "/mnt/mdbx-torture";
-
-#[derive(Debug, Copy, Clone)]
-enum EngineKind {
-    Mdbx,
-    Rocksdb,
-}
-
-impl FromStr for EngineKind {
-    type Err = anyhow::Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "mdbx" => Ok(EngineKind::Mdbx),
-            "rocksdb" | "rdb" => Ok(EngineKind::Rocksdb),
-            _ => anyhow::bail!("Unknown engine kind: {}", s),
-        }
-    }
-}
+const PATH: &str = "torture-db";
 
-#[derive(Debug, Parser)]
+#[derive(Debug, Clone, Parser)]
 struct Cli {
     #[clap(subcommand)]
     subcmd: SubCommand,
 
+    /// A TOML file with the options of the run, flags on the command line override it.
+    #[clap(long)]
+    config: Option<PathBuf>,
+
     #[clap(short, long)]
     kind: EngineKind,
 
+    /// The directory of the databases, each engine in a subdirectory named after it, like
+    /// `torture-db/mdbx`, so that engines never share files.
     #[clap(short, long, default_value = PATH)]
     path: String,
 
+    /// Keep the database at `--path` itself instead of the subdirectory of the engine.
+    #[clap(long)]
+    exact_path: bool,
+
+    /// The directory of `--path` before it was resolved to the subdirectory of the engine.
+    #[clap(skip)]
+    base_path: Option<PathBuf>,
+
     #[clap(short, long)]
     y: bool,
 
+    /// The seed of all generated keys and values, decimal or `0x` prefixed hex.
+    #[clap(long, default_value = "0xcafef00dd15ea5e5", value_parser = parse_seed)]
+    seed: u64,
+
     /// Whether to continue filling the database if it already exists.
     #[clap(long, default_value = "false")]
     cont: bool,
 
-    /// Whether to not sync the database after each batch.
-    #[clap(short, long, default_value = "false")]
-    yolo: bool,
+    /// How written keys are generated: `uniform`, `zipf[:<exponent>]` (skewed rewrites of known
+    /// keys), `sequential` or `monotonic-timestamp`.
+    #[clap(long, default_value = "uniform")]
+    key_dist: KeyDist,
+
+    /// The size of every key in bytes.
+    #[clap(long, default_value = "32")]
+    key_sz: usize,
+
+    /// The number of leading key bytes that hold a prefix shared with other keys, like a table id.
+    /// 0 disables prefixes.
+    #[clap(long, default_value = "0")]
+    key_prefix_bytes: usize,
+
+    /// The number of distinct prefixes keys are spread over.
+    #[clap(long, default_value = "16")]
+    prefix_cardinality: u64,
+
+    /// The number of known keys that rewrites and reads pick from, a window over the keys in the
+    /// order they were written. All of them by default.
+    #[clap(long)]
+    hot_set_size: Option<usize>,
+
+    /// How many keys the hot set moves on by per pick, dropping its oldest key for the next one,
+    /// wrapping around at the newest known key. 0 keeps it in place.
+    #[clap(long, default_value = "0")]
+    hot_set_churn: f64,
+
+    /// The number of tables the keys are spread over, each an mdbx named database, a rocksdb
+    /// column family and so on.
+    #[clap(long, default_value = "1")]
+    tables: usize,
+
+    /// The number of independent environments the keyspace is split over, in `shard-<i>`
+    /// subdirectories of the path. Fill writer `i` writes to shard `i % shards`.
+    #[clap(long, default_value = "1")]
+    shards: usize,
+
+    /// Syncs explicitly between commits, every given number of batches or after a duration like
+    /// `1s`, and records the latency of the syncs. Models a no-sync mode with periodic syncs
+    /// done by the writer itself.
+    #[clap(long)]
+    sync_every: Option<SyncEvery>,
+
+    /// Ends every value of at least 16 bytes with the sequence number of its write and a
+    /// checksum of the key and the value, and checks it on every read. Needs to be given to the
+    /// run that wrote the values too.
+    #[clap(long)]
+    checksum_values: bool,
+
+    /// The fraction of the keys, chosen by their hash, that every write transaction reads back
+    /// before it commits, failing the run unless they read as written. The reads are timed as
+    /// `read_back_us` of the commits.
+    #[clap(long, default_value = "0", value_parser = parse_fraction)]
+    read_your_writes: f64,
+
+    /// The fraction of every value that compresses away, from 0 (random bytes) to 1 (zeroes).
+    #[clap(long, default_value = "0", value_parser = parse_fraction)]
+    compressibility: f64,
+
+    /// When commits are made durable: `durable`, `safe-nosync`, `utterly-nosync` or
+    /// `periodic:<secs>`, which syncs from a background thread.
+    #[clap(long, default_value = "durable")]
+    sync_mode: SyncMode,
+
+    #[clap(flatten)]
+    rocksdb: RocksdbOpts,
+
+    #[clap(flatten)]
+    mdbx: MdbxOpts,
+
+    /// Stop the workload after this long, like `8h`, even if it has operations left. The batch in
+    /// flight is cut short and committed. Pass a large `-n` to only bound the run by time.
+    #[clap(long)]
+    duration: Option<Span>,
+
+    /// Limit the workload to this many operations per second, to measure latencies at a fixed
+    /// offered load. Fill threads get an equal share each. Latencies are measured from when an
+    /// operation was scheduled to start, so that falling behind shows in them.
+    #[clap(long)]
+    rate: Option<f64>,
+
+    /// The number of operations that may start at once under `--rate`, ahead of their schedule.
+    #[clap(long, default_value = "1")]
+    rate_burst: u32,
+
+    /// Record the latency of every N-th put and delete inside write batches, to see the tail of
+    /// single operations. 0 times none of them, only whole batches.
+    #[clap(long, default_value = "0")]
+    sample_ops: u64,
+
+    /// Run this many operations, or for a duration with a unit like `30s`, before the statistics
+    /// start, so that cold caches and an empty database don't skew them.
+    #[clap(long)]
+    warmup: Option<Warmup>,
+
+    /// Evict the database from the page cache before the run and so before every phase. Drops
+    /// the caches of the whole system as root, only the clean pages of the database otherwise.
+    #[clap(long)]
+    drop_caches: bool,
+
+    /// Limit the memory of the run, page cache included, like `4GiB`. With a memory cgroup as
+    /// root, otherwise by allocating and locking all available memory but that.
+    #[clap(long)]
+    mem_limit: Option<ByteSize>,
+
+    /// Limit the I/O of the run to the disk of the database, like `wbps=100MiB,wiops=1000`, with
+    /// the keys of cgroup `io.max`. As root with a cgroup, otherwise only the writes are
+    /// throttled by sleeping after commits.
+    #[clap(long)]
+    io_limit: Option<cgroup::IoLimit>,
+
+    /// Share an mdbx or lmdb environment with other processes of this tool, one `writer` and
+    /// any number of `reader`s, which wait for the writer to open the environment and stop once
+    /// it is done or gone. They coordinate through `<path>.coord` and `<path>.coord.lock`.
+    #[clap(long)]
+    process_role: Option<procs::ProcessRole>,
+
+    /// Open the files with O_DIRECT, bypassing the page cache. Only rocksdb supports it.
+    #[clap(long)]
+    direct_io: bool,
+
+    /// Run the workload against an engine that drops all writes, touching no files, to measure
+    /// what generating the keys and values and batching them costs on its own.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Run on tmpfs, which the checks before the run refuse as it keeps the database in memory.
+    #[clap(long)]
+    allow_tmpfs: bool,
+
+    /// Run even if the filesystem has less space free than the fill is expected to write.
+    #[clap(long)]
+    allow_low_space: bool,
+
+    /// Advise the kernel to drop the clean cached pages of the database after every commit.
+    #[clap(long)]
+    fadvise_dontneed: bool,
+
+    /// What inserts of `fill`, `mixed` and `churn` do with a key that exists already: `upsert`,
+    /// `skip` or `error`. Updates always overwrite.
+    #[clap(long, default_value = "upsert")]
+    on_duplicate: OnDuplicate,
+
+    /// On Ctrl-C, roll back the batch in flight instead of committing it.
+    #[clap(long)]
+    rollback_on_interrupt: bool,
+
+    /// The fraction of fill batches that are aborted once populated instead of committed, to
+    /// measure the rollback. Which ones only depends on the seed, so `verify` with the same
+    /// fraction checks that none of them leaked into the database.
+    #[clap(long, default_value = "0", value_parser = parse_fraction)]
+    abort_pct: f64,
+
+    /// Dump the engine diagnostics whenever a commit takes longer than this many milliseconds.
+    #[clap(long)]
+    spike_threshold_ms: Option<u64>,
+
+    /// How often to print intermediate statistics, in seconds.
+    #[clap(long, default_value = "10")]
+    report_interval: u64,
+
+    /// How often to print the current throughput from a background thread, in seconds. 0
+    /// disables it.
+    #[clap(long, default_value = "5")]
+    throughput_interval: u64,
+
+    /// Write the metrics of every batch in the given format, `json` or `csv`.
+    #[clap(long)]
+    output: Option<OutputFormat>,
+
+    /// Where to write the batch metrics. Defaults to `results.<format>`.
+    #[clap(long)]
+    out_file: Option<PathBuf>,
+
+    /// The format of the log: `pretty` or `json`.
+    #[clap(long, default_value = "pretty")]
+    log_format: logging::LogFormat,
+
+    /// Where to write the log instead of stdout.
+    #[clap(long)]
+    log_file: Option<PathBuf>,
+
+    /// Writes a CPU profile of the run, `cpu:<path>`. A `.svg` path gets a flamegraph, any other
+    /// a pprof protobuf. Needs the `profile` feature.
+    #[clap(long)]
+    profile: Option<profile::Profile>,
+
+    /// Serves Prometheus metrics of the run on this address, like `0.0.0.0:9100`.
+    #[clap(long)]
+    metrics_addr: Option<std::net::SocketAddr>,
+
+    /// Records every operation into this file, to be executed again with `replay`.
+    #[clap(long)]
+    record_trace: Option<PathBuf>,
+
+    /// Writes a report with charts of the batch metrics here at the end of the run, Markdown if
+    /// the path ends with `.md` and HTML otherwise. Implies `--output json` unless given.
+    #[clap(long)]
+    report: Option<PathBuf>,
+
+    /// Appends the command line, the engine versions and the summary of the run to this JSON
+    /// lines file, to compare runs with `compare-runs`.
+    #[clap(long)]
+    ledger: Option<PathBuf>,
+
+    /// The id of the run in the ledger. Defaults to the Unix time of the end of the run.
+    #[clap(long)]
+    run_id: Option<String>,
+}
+
+impl Cli {
+    fn stats(&self) -> anyhow::Result<Stats> {
+        let interval = std::time::Duration::from_secs(self.report_interval);
+        let mut stats = Stats::new(interval, self.path.as_ref()).with_warmup(self.warmup);
+        if self.throughput_interval > 0 {
+            stats = stats.with_reporter(std::time::Duration::from_secs(self.throughput_interval));
+        }
+        if let Some(format) = self.output_format() {
+            let out_file = self.out_file(format);
+            stats = stats.with_output(Output::create(format, &out_file, self.path.as_ref())?);
+        }
+        Ok(stats)
+    }
+
+    /// Moves the database into the subdirectory of its engine, unless `--exact-path` is given.
+    fn resolve_path(&mut self) {
+        if self.exact_path || self.base_path.is_some() {
+            return;
+        }
+        let base = PathBuf::from(&self.path);
+        self.path = base
+            .join(compare::name(self.kind))
+            .to_string_lossy()
+            .into_owned();
+        self.base_path = Some(base);
+    }
+
+    /// Returns the path of the database of another engine in the same run, its subdirectory or,
+    /// with `--exact-path`, `<path>.<kind>`.
+    fn engine_path(&self, kind: EngineKind) -> String {
+        match &self.base_path {
+            Some(base) => base
+                .join(compare::name(kind))
+                .to_string_lossy()
+                .into_owned(),
+            None => format!(
+                "{}.{}",
+                self.path.trim_end_matches('/'),
+                compare::name(kind)
+            ),
+        }
+    }
+
+    fn output_format(&self) -> Option<OutputFormat> {
+        match self.report {
+            Some(_) => Some(self.output.unwrap_or(OutputFormat::Json)),
+            None => self.output,
+        }
+    }
+
+    fn out_file(&self, format: OutputFormat) -> PathBuf {
+        self.out_file
+            .clone()
+            .unwrap_or_else(|| format!("results.{}", format.extension()).into())
+    }
+
+    /// Returns the random number generator of the given stream. Streams of the same seed are
+    /// independent, stream `i` of fill worker `i` and `READER_STREAM + i` of reader `i`.
+    fn rng(&self, stream: u128) -> rand_pcg::Pcg64 {
+        rand_pcg::Pcg64::new(
+            self.seed as u128 + stream,
+            0x60e11a7bf9cb254560e11a7bf9cb2545,
+        )
+    }
+
+    /// Returns the deadline of the workload, starting now.
+    fn deadline(&self) -> interrupt::Deadline {
+        interrupt::Deadline::new(self.duration.map(|Span(duration)| duration))
+    }
+
+    /// Returns the rate limiter of one of `threads` threads sharing the rate.
+    fn limiter(&self, threads: usize) -> rate::Limiter {
+        rate::Limiter::new(self.rate.map(|rate| rate / threads as f64), self.rate_burst)
+    }
+
+    /// Seals a generated value, see `--checksum-values`.
+    fn seal(&self, key: &[u8], value: &mut [u8], seq: usize) {
+        if self.checksum_values {
+            values::seal(key, value, seq as u64);
+        }
+    }
+
+    /// Checks a value read from the database, see `--checksum-values`.
+    fn check_value(&self, table: usize, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        if self.checksum_values {
+            values::check_seal(key, value).with_context(|| format!("In table {}", table))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the table of a key. Keys are spread over the tables by their FNV-1a hash, so every
+    /// table gets its own share of the key space.
+    fn table(&self, key: &[u8]) -> usize {
+        if self.tables == 1 {
+            return 0;
+        }
+        let hash = key.iter().fold(0xcbf29ce484222325, |hash: u64, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+        (hash % self.tables as u64) as usize
+    }
+}
+
+#[cfg(test)]
+impl Cli {
+    /// The options of a `stat` of the memory engine with the given flags on top.
+    fn for_test(flags: &[&str]) -> Self {
+        let mut args = vec!["torture-mdbx", "--kind", "memory"];
+        args.extend(flags);
+        args.push("stat");
+        Cli::parse_from(args)
+    }
+}
+
+const READER_STREAM: u128 = 1 << 64;
+
+/// The streams of the abort decisions, one per batch.
+const ABORT_STREAM: u128 = 6 << 64;
+
+/// Whether the fill batch of a worker starting at its `seq`-th item is aborted, see
+/// `--abort-pct`.
+fn aborted(cli: &Cli, worker: usize, seq: usize) -> bool {
+    let stream = ABORT_STREAM + ((worker as u128) << 32) + seq as u128;
+    cli.abort_pct > 0.0 && cli.rng(stream).gen_bool(cli.abort_pct)
 }
 
-#[derive(Debug, Parser)]
+#[derive(Debug, Clone, Parser)]
 enum SubCommand {
     Fill(FillOpts),
+    /// Loads keys in sorted order, appended to the end of the tables or ingested as files.
+    FillSeq(FillSeqOpts),
+    /// Builds files of sorted items and ingests them all at once, for engines that can.
+    Ingest(IngestOpts),
+    Read(ReadOpts),
+    Mixed(MixedOpts),
+    Scan(ScanOpts),
+    /// Seeks to random prefixes and iterates all entries under each.
+    PrefixScan(PrefixScanOpts),
+    Churn(ChurnOpts),
+    Update(UpdateOpts),
+    /// Inserts many values per key into a dupsort table. Engines without dupsort tables encode
+    /// the values as key suffixes.
+    Dupsort(DupsortOpts),
+    /// Inserts, rewrites and deletes values of hundreds of KiB to tens of MiB, then reads all of
+    /// them back and reports the space they take.
+    LargeValues(LargeValuesOpts),
+    /// Writes every batch in sub-batches of nested transactions, each committed or aborted, for
+    /// engines that have them. mdbx needs `--no-writemap` for them.
+    Nested(NestedOpts),
+    /// Checks that the database contains exactly what `fill` with the same options wrote.
+    Verify(FillOpts),
+    /// Repeatedly kills a filling child process and checks that no committed batch was lost.
+    Torture(TortureOpts),
+    /// Fills a size-limited filesystem until the engine fails, then checks what survived.
+    DiskFull(DiskFullOpts),
+    /// Flips random bits or overwrites random pages of the database files, then checks how the
+    /// engine copes with reopening, scanning and looking up the known keys.
+    Corrupt(CorruptOpts),
+    /// Runs the phases of the config file in order.
+    Run,
     Stat,
+    /// Prints the free pages of the GC and the lag of the oldest reader, for engines like mdbx.
+    GcStat(GcStatOpts),
+    /// Prints an order-independent digest of all entries of the tables, to compare databases.
+    Checksum,
+    /// Compacts the whole database and reports the time it took and the size before and after.
+    Compact,
+    /// Times an online backup of the database.
+    Backup(BackupOpts),
+    /// Streams all entries into another database, possibly of another engine.
+    Copy(CopyOpts),
+    /// Repeatedly opens and closes the database, optionally killing a writer before every open.
+    OpenBench(OpenBenchOpts),
+    /// Holds long-lived read transactions while overwriting, and reports how the database grows.
+    LongReaders(LongReadersOpts),
+    /// Increments counters in read-modify-write transactions and reports the lost updates.
+    Rmw(RmwOpts),
+    /// Writes keys of increasing timestamps and periodically deletes the expired ones as ranges.
+    Expire(ExpireOpts),
+    /// Executes a trace recorded with `--record-trace` or imported from another tool.
+    Replay(ReplayOpts),
+    /// Removes the database and the files next to it, asking first unless `-y` is given.
+    Wipe,
+    /// Runs the same workload on the engine of `--kind` and others, each in the subdirectory of
+    /// its engine, and reports them side by side.
+    Compare(CompareOpts),
+    /// Writes a report with charts of results files written with `--output`.
+    Report(ReportOpts),
+    /// Compares two runs of the ledger of `--ledger` and fails if any metric regressed.
+    CompareRuns(CompareRunsOpts),
+    /// Runs a write workload once per batch size and reports throughput and commit latency
+    /// against it.
+    SweepBatch(SweepBatchOpts),
+    /// Runs a write workload once per value size, each writing the same total bytes, and reports
+    /// the results against the value size.
+    SweepValue(SweepValueOpts),
 }
 
 impl SubCommand {
     fn as_fill_opts(&self) -> Option<&FillOpts> {
         match self {
-            SubCommand::Fill(opts) => Some(opts),
+            SubCommand::Fill(opts) | SubCommand::Verify(opts) => Some(opts),
             _ => None,
         }
     }
 }
 
-#[derive(Debug, Parser)]
+#[derive(Debug, Clone, Parser)]
 struct FillOpts {
     /// The number of items to insert into the database.
-    #[clap(short, long)]
-    n: usize,
+    #[clap(short, long, required_unless_present = "fill_to")]
+    n: Option<usize>,
+
+    /// Insert until the database takes this much space on disk, like `2TiB`, instead of a fixed
+    /// number of items. With `-n` too, whichever is reached first ends the fill.
+    #[clap(long, alias = "fill-to-bytes")]
+    fill_to: Option<ByteSize>,
 
     /// The size of each batch of items to insert.
     #[clap(short, long, default_value = "1000")]
     batch_sz: usize,
 
-    #[clap(short, long, default_value = "32")]
-    value_sz: usize,
+    /// The distribution of value sizes: `fixed:<size>`, `uniform:<min>..<max>`,
+    /// `lognormal:<median>,<sigma>` or `bimodal:<small>,<large>,<large fraction>`.
+    #[clap(short, long, default_value = "fixed:32")]
+    value_dist: ValueDist,
 
     #[clap(short, long, default_value = "0.3")]
     cold: f32,
-}
 
-enum Engine {
-    Mdbx(Environment),
-    Rocksdb(Rc<rocksdb::DB>),
+    /// The number of threads inserting concurrently. Engines with a single writer serialize them.
+    #[clap(short, long, default_value = "1")]
+    threads: usize,
+
+    /// The number of threads reading from the database while it is being filled.
+    #[clap(long, default_value = "0")]
+    readers: usize,
+
+    /// What the readers do, `get` for point lookups or `scan` for range scans.
+    #[clap(long, default_value = "get")]
+    reader_op: ReaderOp,
+
+    /// The number of operations a reader performs in one read transaction. Longer transactions
+    /// keep older snapshots alive.
+    #[clap(long, default_value = "1000")]
+    reader_txn_ops: usize,
+
+    /// The number of entries visited by each reader scan.
+    #[clap(long, default_value = "100")]
+    reader_scan_len: usize,
 }
 
-impl Engine {
-    fn open(cli: &Cli) -> anyhow::Result<Engine> {
-        match cli.kind {
-            EngineKind::Mdbx => Engine::open_mdbx(cli),
-            EngineKind::Rocksdb => Engine::open_rocksdb(cli),
-        }
+impl FillOpts {
+    /// The number of items inserted by the given worker, the first `n % threads` insert one more.
+    fn worker_items(&self, worker: usize) -> usize {
+        let n = self.n.unwrap_or(usize::MAX);
+        n / self.threads + usize::from(worker < n % self.threads)
     }
+}
 
-    fn open_mdbx(cli: &Cli) -> anyhow::Result<Engine> {
-        let env = Environment::builder()
-            .set_max_dbs(256)
-            .write_map()
-            .set_flags(EnvironmentFlags {
-                mode: Mode::ReadWrite {
-                    sync_mode: if cli.yolo {
-                        reth_libmdbx::SyncMode::UtterlyNoSync
-                    } else {
-                        reth_libmdbx::SyncMode::Durable
-                    },
-                },
-                ..Default::default()
-            })
-            .set_geometry(Geometry {
-                // Maximum database size of 4 terabytes
-                size: Some(0..(4 * TERABYTE)),
-                // We grow the database in increments of 1 gigabytes
-                growth_step: Some(1 * GIGABYTE as isize),
-                // The database never shrinks
-                shrink_threshold: Some(0),
-                page_size: Some(PageSize::Set(4096)),
-            })
-            .open(&PathBuf::from(&cli.path))?;
-        Ok(Engine::Mdbx(env))
-    }
+#[derive(Debug, Clone, Parser)]
+struct FillSeqOpts {
+    /// The number of items to load.
+    #[clap(short, long)]
+    n: usize,
 
-    fn open_rocksdb(cli: &Cli) -> anyhow::Result<Engine> {
-        let db = rocksdb::DB::open_default(&cli.path)?;
-        Ok(Engine::Rocksdb(Rc::new(db)))
-    }
+    #[clap(short, long, default_value = "1000")]
+    batch_sz: usize,
 
-    fn begin(&self) -> anyhow::Result<Tx> {
-        match self {
-            Engine::Mdbx(env) => {
-                let txn = env.begin_rw_txn()?;
-                let db = txn.create_db(None, DatabaseFlags::CREATE)?;
-                Ok(Tx::Mdbx { txn, db })
-            }
-            Engine::Rocksdb(db) => {
-                let batch = rocksdb::WriteBatch::default();
-                Ok(Tx::Rocksdb {
-                    db: db.clone(),
-                    batch,
-                })
-            }
-        }
-    }
+    /// The distribution of value sizes, like for `fill`.
+    #[clap(short, long, default_value = "fixed:32")]
+    value_dist: ValueDist,
 
-    fn print_stat(&self) -> anyhow::Result<String> {
-        match self {
-            Engine::Mdbx(env) => {
-                let txn = env.begin_ro_txn()?;
-                let main = txn.open_db(None).unwrap();
-                let stat = txn.db_stat(&main).unwrap();
-                Ok(format!("{:?}", stat))
-            }
-            _ => Ok("".to_string()),
-        }
-    }
+    /// Ingests every batch as files instead of committing it, for engines that can, like rocksdb.
+    #[clap(long)]
+    ingest: bool,
+
+    /// Afterwards fills as many random keys at `<path>.random` and compares the two.
+    #[clap(long)]
+    against_random: bool,
 }
 
-enum Tx {
-    Mdbx {
-        txn: reth_libmdbx::Transaction<RW>,
-        db: reth_libmdbx::Database,
-    },
-    Rocksdb {
-        db: Rc<rocksdb::DB>,
-        batch: rocksdb::WriteBatch,
-    },
+#[derive(Debug, Clone, Parser)]
+struct IngestOpts {
+    /// The number of items to ingest.
+    #[clap(short, long)]
+    n: usize,
+
+    /// The number of items of every file, split among the tables.
+    #[clap(long, default_value = "1000000")]
+    file_items: usize,
+
+    /// The distribution of value sizes, like for `fill`.
+    #[clap(short, long, default_value = "fixed:32")]
+    value_dist: ValueDist,
+
+    /// Afterwards writes the same items through transactions at `<path>.write`, like `fill-seq`
+    /// does, and compares the two.
+    #[clap(long)]
+    against_write: bool,
+
+    /// The batch size of the comparison.
+    #[clap(short, long, default_value = "1000")]
+    batch_sz: usize,
 }
 
-impl Tx {
-    fn put(&mut self, key: Vec<u8>, value: Vec<u8>) -> anyhow::Result<()> {
-        match self {
-            Tx::Mdbx { txn, db } => {
-                txn.put(db.dbi(), key, value, WriteFlags::empty())?;
-            }
-            Tx::Rocksdb { batch, .. } => {
-                batch.put(key, value);
-            }
-        }
-        Ok(())
-    }
+#[derive(Debug, Clone, Parser)]
+struct CorruptOpts {
+    /// The number of random bits to flip.
+    #[clap(long, default_value = "1")]
+    bits: usize,
 
-    fn commit(self) -> anyhow::Result<()> {
-        match self {
-            Tx::Mdbx { txn, .. } => {
-                txn.commit()?;
-                Ok(())
-            }
-            Tx::Rocksdb { db, batch } => {
-                db.write_without_wal(batch)?; // TODO: write wal = false?
-                Ok(())
-            }
+    /// The number of random pages to overwrite with garbage.
+    #[clap(long, default_value = "0")]
+    pages: usize,
+
+    #[clap(long, default_value = "4096")]
+    page_size: usize,
+
+    /// Only damages the bytes of every file in this region, like `0..1MiB`.
+    #[clap(long)]
+    region: Option<corrupt::Region>,
+
+    /// Only damages the files whose name contains this, like `sst`.
+    #[clap(long)]
+    files: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum ReaderOp {
+    Get,
+    Scan,
+}
+
+impl FromStr for ReaderOp {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "get" => Ok(ReaderOp::Get),
+            "scan" => Ok(ReaderOp::Scan),
+            _ => anyhow::bail!("Unknown reader op: {}", s),
         }
     }
 }
 
-fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
-    match cli.subcmd {
-        SubCommand::Fill(_) => fill_database(&cli),
-        SubCommand::Stat => stat_database(&cli),
-    }
+#[derive(Debug, Clone, Parser)]
+struct MixedOpts {
+    /// The number of operations to perform.
+    #[clap(short, long)]
+    n: usize,
+
+    /// The number of operations in each batch.
+    #[clap(short, long, default_value = "1000")]
+    batch_sz: usize,
+
+    /// The distribution of value sizes: `fixed:<size>`, `uniform:<min>..<max>`,
+    /// `lognormal:<median>,<sigma>` or `bimodal:<small>,<large>,<large fraction>`.
+    #[clap(short, long, default_value = "fixed:32")]
+    value_dist: ValueDist,
+
+    #[clap(short, long, default_value = "0.3")]
+    cold: f32,
+
+    /// The fraction of operations that are point lookups of previously written keys.
+    #[clap(short, long, default_value = "0.5")]
+    read_pct: f32,
+
+    /// A YCSB core workload, `ycsb-a` to `ycsb-f`, that sets the operation mix and the key
+    /// distribution instead of `--read-pct`, `--cold` and `--key-dist`.
+    #[clap(long)]
+    workload: Option<ycsb::Workload>,
 }
 
-fn stat_database(cli: &Cli) -> anyhow::Result<()> {
-    let env = Engine::open(cli)?;
-    env.print_stat()?;
-    Ok(())
+#[derive(Debug, Clone, Parser)]
+struct ReadOpts {
+    /// The number of point lookups to perform.
+    #[clap(short, long)]
+    n: usize,
+
+    /// The number of existing keys to sample from the database before the run. Hot lookups pick
+    /// one of these.
+    #[clap(short, long, default_value = "100000")]
+    sample_sz: usize,
+
+    /// The probability of looking up a fresh random key, which is most likely absent.
+    #[clap(short, long, default_value = "0.3")]
+    cold: f32,
+
+    /// Looks keys up in batches of this size with a single multi-get, 1 for single gets. The
+    /// latency is reported per key.
+    #[clap(long, default_value = "1")]
+    multi_get: usize,
 }
 
-fn fill_database(cli: &Cli) -> anyhow::Result<()> {
-    if std::path::Path::new(&cli.path).exists() {
-        if cli.y {
-            println!("Database already exists, removing.");
-            let _ = std::fs::remove_file(&cli.path);
-        } else if cli.cont {
-            println!("Database already exists, continuing filling.");
-        } else {
-            anyhow::bail!("Database already exists, aborting.");
-        }
-    }
+#[derive(Debug, Clone, Parser)]
+struct ScanOpts {
+    /// The number of range scans to perform.
+    #[clap(short, long)]
+    n: usize,
 
-    let fill_ops = cli.subcmd.as_fill_opts().unwrap();
+    /// The number of entries visited by each scan.
+    #[clap(short, long, default_value = "100")]
+    len: usize,
 
-    println!("Opening database, {:?}", cli);
-    let env = Engine::open(cli)?;
+    /// Whether to scan in descending key order.
+    #[clap(short, long, default_value = "false")]
+    reverse: bool,
+}
 
-    let mut rand = rand_pcg::Pcg64::new(0xcafef00dd15ea5e5, 0x60e11a7bf9cb254560e11a7bf9cb2545);
+#[derive(Debug, Clone, Parser)]
+struct PrefixScanOpts {
+    /// The number of prefixes to iterate.
+    #[clap(short, long)]
+    n: usize,
 
-    let mut keys = Vec::with_capacity(fill_ops.n);
+    /// The number of leading key bytes that make up a prefix. Defaults to `--key-prefix-bytes`,
+    /// every byte beyond it divides the entries under a prefix by 256.
+    #[clap(long)]
+    prefix_len: Option<usize>,
+}
 
-    let mut remaining = fill_ops.n;
-    loop {
-        let mut txn = env.begin().unwrap();
+#[derive(Debug, Clone, Parser)]
+struct ChurnOpts {
+    /// The number of items to insert into the database.
+    #[clap(short, long)]
+    n: usize,
 
-        let start = std::time::Instant::now();
-        for _ in 0..fill_ops.batch_sz {
-            if remaining == 0 {
-                break;
-            }
+    /// The number of items inserted in each batch.
+    #[clap(short, long, default_value = "1000")]
+    batch_sz: usize,
 
-            let key = if keys.is_empty() || rand.gen_bool(fill_ops.cold as f64) {
-                let mut key = vec![0; 32];
-                rand.fill_bytes(&mut key);
-                keys.push(key.clone());
-                key
-            } else {
-                keys[rand.gen_range(0..keys.len())].clone()
-            };
+    /// The distribution of value sizes: `fixed:<size>`, `uniform:<min>..<max>`,
+    /// `lognormal:<median>,<sigma>` or `bimodal:<small>,<large>,<large fraction>`.
+    #[clap(short, long, default_value = "fixed:32")]
+    value_dist: ValueDist,
 
-            let mut data = vec![0; fill_ops.value_sz];
-            rand.fill_bytes(&mut data);
-            txn.put(key, data).unwrap();
-            remaining -= 1;
-        }
+    /// The fraction of the live keys that is deleted in each batch.
+    #[clap(short, long, default_value = "0.01", value_parser = parse_fraction)]
+    delete_pct: f64,
 
-        let batch_lat = start.elapsed();
-        // let stat = txn.db_stat(&main).unwrap();
+    /// Delete the keys of a random range of about `--delete-pct` of the key space with a single
+    /// range delete per table, instead of one by one. Ends with timing the reclamation of their
+    /// space by a full compaction.
+    #[clap(long)]
+    delete_range: bool,
+}
 
-        let start = std::time::Instant::now();
-        txn.commit()?;
-        let commit_lat = start.elapsed();
+#[derive(Debug, Clone, Parser)]
+struct UpdateOpts {
+    /// The number of writes to perform.
+    #[clap(short, long)]
+    n: usize,
 
-        if remaining == 0 {
-            break;
-        }
+    /// The number of writes in each batch.
+    #[clap(short, long, default_value = "1000")]
+    batch_sz: usize,
 
-        println!(
-            "Commit {} items in {} ms",
-            fill_ops.batch_sz,
-            commit_lat.as_millis()
-        );
-        println!(
-            "Inserted {} items in {} ms",
-            fill_ops.n - remaining,
-            batch_lat.as_millis()
-        );
-        // println!("{:#?}", stat);
+    /// The number of keys that are repeatedly overwritten.
+    #[clap(long, default_value = "1000")]
+    hot_keys: usize,
+
+    /// The distribution of value sizes, see `fill`.
+    #[clap(short, long, default_value = "uniform:16..4096")]
+    value_dist: ValueDist,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct LongReadersOpts {
+    /// The number of batches the writer commits.
+    #[clap(long, default_value = "10000")]
+    batches: usize,
+
+    /// The number of overwrites in each batch.
+    #[clap(short, long, default_value = "1000")]
+    batch_sz: usize,
+
+    /// The number of keys that are overwritten.
+    #[clap(long, default_value = "10000")]
+    hot_keys: usize,
+
+    /// The distribution of value sizes, see `fill`.
+    #[clap(short, long, default_value = "fixed:256")]
+    value_dist: ValueDist,
+
+    /// The number of threads holding read transactions. More than `--mdbx-max-readers` exhaust
+    /// the reader slots.
+    #[clap(long, default_value = "4")]
+    readers: usize,
+
+    /// How long a reader holds its transaction before it opens a new one, in milliseconds.
+    #[clap(long, default_value = "10000")]
+    hold_ms: u64,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct ReplayOpts {
+    /// The trace to execute.
+    #[clap(long)]
+    trace: PathBuf,
+
+    /// The format of the trace: `native`, as recorded by `--record-trace`, `csv` or `ycsb`.
+    #[clap(long, default_value = "native")]
+    format: replay::TraceFormat,
+
+    /// The number of writes per commit of traces without transactions.
+    #[clap(short, long, default_value = "1000")]
+    batch_sz: usize,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct RmwOpts {
+    /// The number of increments to perform.
+    #[clap(short, long)]
+    n: usize,
+
+    /// The number of increments in each transaction.
+    #[clap(short, long, default_value = "1")]
+    batch_sz: usize,
+
+    /// The number of counters, fewer make conflicts more likely.
+    #[clap(long, default_value = "1000")]
+    counters: usize,
+
+    /// The number of threads incrementing concurrently.
+    #[clap(short, long, default_value = "1")]
+    threads: usize,
+
+    /// Increment with blind adds, a merge operator in rocksdb, instead of reading and writing.
+    #[clap(long)]
+    merge: bool,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct ExpireOpts {
+    /// The number of items to insert.
+    #[clap(short, long)]
+    n: usize,
+
+    /// The number of items in each batch, all of the same timestamp.
+    #[clap(short, long, default_value = "1000")]
+    batch_sz: usize,
+
+    /// The distribution of value sizes, see `fill`.
+    #[clap(short, long, default_value = "fixed:32")]
+    value_dist: ValueDist,
+
+    /// The number of most recent batches that are kept, older ones expire.
+    #[clap(long, default_value = "100")]
+    retention: u64,
+
+    /// The number of batches between prunings, each deleting all expired batches at once.
+    #[clap(long, default_value = "10")]
+    prune_every: u64,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct DupsortOpts {
+    /// The number of operations to perform.
+    #[clap(short, long)]
+    n: usize,
+
+    /// The number of operations in each batch.
+    #[clap(short, long, default_value = "1000")]
+    batch_sz: usize,
+
+    /// The distribution of value sizes, see `fill`. mdbx limits dupsort values to about half a
+    /// page.
+    #[clap(short, long, default_value = "fixed:32")]
+    value_dist: ValueDist,
+
+    /// The probability of a value going to a fresh key instead of one of the known keys.
+    #[clap(short, long, default_value = "0.01")]
+    cold: f32,
+
+    /// The fraction of operations that read all values of a known key.
+    #[clap(short, long, default_value = "0.1")]
+    read_pct: f32,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct LargeValuesOpts {
+    /// The number of writes, each an insert, a rewrite or a delete.
+    #[clap(short, long)]
+    n: usize,
+
+    #[clap(short, long, default_value = "10")]
+    batch_sz: usize,
+
+    /// The distribution of value sizes, like for `fill`.
+    #[clap(short, long, default_value = "uniform:256KiB..16MiB")]
+    value_dist: ValueDist,
+
+    /// The fraction of writes that replace a live value with one of a new size.
+    #[clap(long, default_value = "0.3")]
+    rewrite_pct: f32,
+
+    /// The fraction of writes that delete a live value.
+    #[clap(long, default_value = "0.1")]
+    delete_pct: f32,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct NestedOpts {
+    /// The number of items to insert.
+    #[clap(short, long)]
+    n: usize,
+
+    /// The number of items of every top-level transaction.
+    #[clap(short, long, default_value = "1000")]
+    batch_sz: usize,
+
+    /// The number of items of every nested transaction.
+    #[clap(long, default_value = "100")]
+    sub_batch_sz: usize,
+
+    /// The distribution of value sizes, like for `fill`.
+    #[clap(short, long, default_value = "fixed:32")]
+    value_dist: ValueDist,
+
+    /// The fraction of nested transactions that are aborted instead of committed.
+    #[clap(long, default_value = "0", value_parser = parse_fraction)]
+    abort_pct: f64,
+
+    /// Writes the sub-batches into the top-level transaction without nesting.
+    #[clap(long)]
+    flat: bool,
+
+    /// Afterwards writes the same flat at `<path>.flat` and compares the two.
+    #[clap(long)]
+    against_flat: bool,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct BackupOpts {
+    /// Where to write the backup. Defaults to the database path with a `.backup` suffix.
+    #[clap(long)]
+    dest: Option<PathBuf>,
+
+    /// Whether to leave out free pages, for the engines that copy page by page.
+    #[clap(long)]
+    compact: bool,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct GcStatOpts {
+    /// Keeps printing the GC every given duration, like `10s`, with the pages allocated and
+    /// reclaimed in between, also by other processes.
+    #[clap(long)]
+    watch: Option<Span>,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct CopyOpts {
+    /// The database to copy, like `mdbx:/tmp/db`. Defaults to the one of `--kind` and `--path`.
+    #[clap(long)]
+    from: Option<copy::Location>,
+
+    /// The database to create, like `rocksdb:/tmp/db-rocksdb`.
+    #[clap(long)]
+    to: copy::Location,
+
+    /// The number of entries of every write transaction.
+    #[clap(short, long, default_value = "100000")]
+    batch_sz: usize,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct OpenBenchOpts {
+    /// The number of times the database is opened.
+    #[clap(short, long, default_value = "10")]
+    rounds: usize,
+
+    /// Kill a child writing to the database before every open, so the engine has to recover.
+    #[clap(long)]
+    unclean: bool,
+
+    /// How long the child writes before it is killed, in milliseconds.
+    #[clap(long, default_value = "1000")]
+    kill_after_ms: u64,
+
+    /// The number of items in each batch of the child.
+    #[clap(short, long, default_value = "1000")]
+    batch_sz: usize,
+
+    /// The distribution of value sizes, see `fill`.
+    #[clap(short, long, default_value = "fixed:32")]
+    value_dist: ValueDist,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct CompareOpts {
+    /// The engines to compare with the one of `--kind`, comma separated.
+    #[clap(long, value_delimiter = ',', required = true)]
+    with: Vec<EngineKind>,
+
+    /// The workload to run on every engine.
+    #[clap(subcommand)]
+    workload: Box<SubCommand>,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct CompareRunsOpts {
+    /// The id of the run to compare against.
+    base: String,
+
+    /// The id of the run to check.
+    new: String,
+
+    /// The relative change of a metric for the worse, as a fraction, above which it counts as a
+    /// regression.
+    #[clap(long, default_value = "0.05", value_parser = parse_fraction)]
+    threshold: f64,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct ReportOpts {
+    /// The results files, drawn over each other.
+    #[clap(required = true)]
+    results: Vec<PathBuf>,
+
+    /// Where to write the report, Markdown if the path ends with `.md` and HTML otherwise.
+    #[clap(long, default_value = "report.html")]
+    out: PathBuf,
+
+    /// The seconds over which the charts aggregate the batches.
+    #[clap(long, default_value = "1")]
+    bucket_secs: u64,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct SweepBatchOpts {
+    /// The batch sizes to run the workload with, comma separated.
+    #[clap(long, value_delimiter = ',', default_value = "100,1000,10000,100000")]
+    sizes: Vec<usize>,
+
+    /// Starts every step on an empty database instead of the one the previous step left.
+    #[clap(long)]
+    reset: bool,
+
+    /// The workload to run at every batch size.
+    #[clap(subcommand)]
+    workload: Box<SubCommand>,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct SweepValueOpts {
+    /// The fixed value sizes to run the workload with, comma separated.
+    #[clap(
+        long,
+        value_delimiter = ',',
+        default_value = "32,256,2KiB,16KiB,128KiB,1MiB"
+    )]
+    sizes: Vec<ByteSize>,
+
+    /// The bytes of keys and values every step writes, which sets its number of items.
+    #[clap(long, default_value = "1GiB")]
+    bytes: ByteSize,
+
+    /// Also runs every step on these engines, comma separated, each at its own path like `compare`
+    /// does.
+    #[clap(long, value_delimiter = ',')]
+    with: Vec<EngineKind>,
+
+    /// Starts every step on an empty database instead of the one the previous step left.
+    #[clap(long)]
+    reset: bool,
+
+    /// The workload to run at every value size.
+    #[clap(subcommand)]
+    workload: Box<SubCommand>,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct TortureOpts {
+    /// The number of times the child is killed.
+    #[clap(short, long, default_value = "10")]
+    rounds: usize,
+
+    /// The number of items to insert over all rounds.
+    #[clap(short, long)]
+    n: usize,
+
+    /// The number of items in each batch.
+    #[clap(short, long, default_value = "1000")]
+    batch_sz: usize,
+
+    /// The distribution of value sizes, see `fill`.
+    #[clap(short, long, default_value = "fixed:32")]
+    value_dist: ValueDist,
+
+    /// The child is killed at a uniformly random time up to this many milliseconds after it was
+    /// started.
+    #[clap(long, default_value = "2000")]
+    max_kill_delay_ms: u64,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct DiskFullOpts {
+    /// Where to create the filesystem image.
+    #[clap(long, default_value = "/tmp/torture-diskfull.img")]
+    image: PathBuf,
+
+    /// The size of the filesystem image.
+    #[clap(long, default_value = "268435456")]
+    image_bytes: u64,
+
+    /// The number of items in each batch.
+    #[clap(short, long, default_value = "1000")]
+    batch_sz: usize,
+
+    /// The distribution of value sizes, see `fill`.
+    #[clap(short, long, default_value = "fixed:32")]
+    value_dist: ValueDist,
+}
+
+fn parse_fraction(s: &str) -> anyhow::Result<f64> {
+    let fraction: f64 = s.parse()?;
+    if !(0.0..=1.0).contains(&fraction) {
+        anyhow::bail!("Expected a fraction within 0..=1: {}", fraction);
+    }
+    Ok(fraction)
+}
+
+fn parse_seed(s: &str) -> anyhow::Result<u64> {
+    Ok(match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16)?,
+        None => s.parse()?,
+    })
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = config::parse()?;
+    logging::init(cli.log_format, cli.log_file.as_deref())?;
+    tracing::info!(seed = format_args!("{:#x}", cli.seed), "Starting");
+    if let Some(addr) = cli.metrics_addr {
+        metrics::serve(addr)?;
+    }
+    let _limits = cgroup::apply(&cli)?;
+    if cli.dry_run {
+        if cli.output_format().is_some() || cli.fadvise_dontneed || cli.process_role.is_some() {
+            anyhow::bail!("--dry-run has no database files to measure, share or advise on.");
+        }
+        tracing::info!("Dry run, nothing is written");
+    } else if !matches!(
+        cli.subcmd,
+        SubCommand::Report(_) | SubCommand::CompareRuns(_) | SubCommand::Wipe
+    ) {
+        safety::check(&cli)?;
+    }
+    procs::start(&cli)?;
+    let result = run(&cli);
+    procs::finish(&cli)?;
+    result?;
+    if !matches!(
+        cli.subcmd,
+        SubCommand::Report(_) | SubCommand::CompareRuns(_)
+    ) {
+        ledger::record(&cli, &stats::take_summaries())?;
+    }
+    if let (Some(report), Some(format)) = (&cli.report, cli.output_format()) {
+        report::write(&[cli.out_file(format)], report, 1)?;
+    }
+    Ok(())
+}
+
+fn run(cli: &Cli) -> anyhow::Result<()> {
+    if let Some(rate) = cli.rate.filter(|rate| !rate.is_finite() || *rate <= 0.0) {
+        anyhow::bail!("Invalid rate: {}", rate);
+    }
+    if cli.drop_caches && Path::new(&cli.path).exists() {
+        mem::drop_cache(cli.path.as_ref())?;
+    }
+    if cli.shards != 1 && !matches!(cli.subcmd, SubCommand::Fill(_) | SubCommand::Wipe) {
+        anyhow::bail!("--shards is only supported by fill.");
+    }
+    // Compare profiles every engine on its own, a sweep every step.
+    let profiler = match &cli.profile {
+        Some(_)
+            if matches!(
+                cli.subcmd,
+                SubCommand::Compare(_) | SubCommand::SweepBatch(_) | SubCommand::SweepValue(_)
+            ) =>
+        {
+            None
+        }
+        Some(profile) => Some(profile::start(profile, cli.kind)?),
+        None => None,
+    };
+    let result = run_subcommand(cli);
+    if let Some(profiler) = profiler {
+        profiler.finish()?;
+    }
+    result
+}
+
+fn run_subcommand(cli: &Cli) -> anyhow::Result<()> {
+    match &cli.subcmd {
+        SubCommand::Fill(_) => fill_database(cli),
+        SubCommand::FillSeq(fill_seq_opts) => bulk::run(cli, fill_seq_opts),
+        SubCommand::Ingest(ingest_opts) => bulk::ingest(cli, ingest_opts),
+        SubCommand::Read(read_opts) => read_database(cli, read_opts),
+        SubCommand::Mixed(mixed_opts) => mixed_database(cli, mixed_opts),
+        SubCommand::Scan(scan_opts) => scan_database(cli, scan_opts),
+        SubCommand::PrefixScan(prefix_scan_opts) => prefix_scan_database(cli, prefix_scan_opts),
+        SubCommand::Churn(churn_opts) => churn_database(cli, churn_opts),
+        SubCommand::Update(update_opts) => update_database(cli, update_opts),
+        SubCommand::Dupsort(dupsort_opts) => dupsort_database(cli, dupsort_opts),
+        SubCommand::LargeValues(large_opts) => large::run(cli, large_opts),
+        SubCommand::Nested(nested_opts) => nested::run(cli, nested_opts),
+        SubCommand::Verify(fill_opts) => verify_database(cli, fill_opts),
+        SubCommand::Torture(torture_opts) => torture::run(cli, torture_opts),
+        SubCommand::DiskFull(disk_full_opts) => diskfull::run(cli, disk_full_opts),
+        SubCommand::Corrupt(corrupt_opts) => corrupt::run(cli, corrupt_opts),
+        SubCommand::Run => run_phases(cli),
+        SubCommand::Stat => stat_database(cli),
+        SubCommand::GcStat(gc_stat_opts) => gc_stat_database(cli, gc_stat_opts),
+        SubCommand::Checksum => checksum_database(cli),
+        SubCommand::Compact => compact_database(cli),
+        SubCommand::Backup(backup_opts) => backup_database(cli, backup_opts),
+        SubCommand::Copy(copy_opts) => copy::run(cli, copy_opts),
+        SubCommand::OpenBench(open_bench_opts) => openbench::run(cli, open_bench_opts),
+        SubCommand::Compare(compare_opts) => compare::run(cli, compare_opts),
+        SubCommand::Report(report_opts) => report::write(
+            &report_opts.results,
+            &report_opts.out,
+            report_opts.bucket_secs,
+        ),
+        SubCommand::CompareRuns(compare_runs_opts) => ledger::compare_runs(cli, compare_runs_opts),
+        SubCommand::SweepBatch(sweep_opts) => sweep::batch(cli, sweep_opts),
+        SubCommand::SweepValue(sweep_opts) => sweep::value(cli, sweep_opts),
+        SubCommand::Rmw(rmw_opts) => rmw::run(cli, rmw_opts),
+        SubCommand::Expire(expire_opts) => expire::run(cli, expire_opts),
+        SubCommand::LongReaders(long_readers_opts) => longreaders::run(cli, long_readers_opts),
+        SubCommand::Replay(replay_opts) => replay::run(cli, replay_opts),
+        SubCommand::Wipe => wipe_database(cli),
+    }
+}
+
+fn run_phases(cli: &Cli) -> anyhow::Result<()> {
+    let phases = config::phases(cli)?;
+
+    let start = std::time::Instant::now();
+    let mut summary = Vec::with_capacity(phases.len());
+    for (i, (command, phase)) in phases.iter().enumerate() {
+        tracing::info!(phase = i, %command, "Running phase");
+        let phase_start = std::time::Instant::now();
+        // The profile covers all phases.
+        let phase = &Cli {
+            profile: None,
+            ..phase.clone()
+        };
+        run(phase)?;
+        summary.push((phase_start.elapsed(), db_size(phase.path.as_ref())?));
+    }
+
+    println!("Ran {} phases in {:?}", phases.len(), start.elapsed());
+    for (i, ((command, _), (elapsed, size))) in phases.iter().zip(summary).enumerate() {
+        println!(
+            "  {}: {} took {:?}, {} bytes on disk after",
+            i, command, elapsed, size
+        );
+    }
+    Ok(())
+}
+
+fn stat_database(cli: &Cli) -> anyhow::Result<()> {
+    let env = backend::open_readonly(cli)?;
+    print!("{}", env.print_stat()?);
+    Ok(())
+}
+
+fn gc_stat_database(cli: &Cli, opts: &GcStatOpts) -> anyhow::Result<()> {
+    if !std::path::Path::new(&cli.path).exists() {
+        anyhow::bail!("Database does not exist, aborting.");
+    }
+
+    let env = backend::open_readonly(cli)?;
+    let Some(mut before) = env.gc_stat()? else {
+        anyhow::bail!("The engine has no GC");
+    };
+    println!(
+        "{} free pages, last page {}, reader lag {} txns",
+        before.free_pages, before.last_page, before.reader_lag
+    );
+    let Some(Span(interval)) = opts.watch else {
+        return Ok(());
+    };
+    interrupt::install();
+    while !interrupt::interrupted() {
+        std::thread::sleep(interval);
+        let gc = env.gc_stat()?.unwrap_or_default();
+        let (allocated, reclaimed) = gc.allocated_since(&before);
+        println!(
+            "{} free pages, {} allocated ({} reclaimed), reader lag {} txns",
+            gc.free_pages, allocated, reclaimed, gc.reader_lag
+        );
+        before = gc;
+    }
+    Ok(())
+}
+
+fn checksum_database(cli: &Cli) -> anyhow::Result<()> {
+    if !std::path::Path::new(&cli.path).exists() {
+        anyhow::bail!("Database does not exist, aborting.");
+    }
+
+    let env = backend::open_readonly(cli)?;
+    let txn = env.begin_read()?;
+    let mut stats = cli.stats()?;
+    let mut total = digest::Digest::default();
+    for table in 0..cli.tables {
+        let mut digest = digest::Digest::default();
+        let mut cursor = txn.cursor(table)?;
+        let mut entry = cursor.first()?;
+        while let Some((key, value)) = &entry {
+            digest.add(table, key, value);
+            stats.add_ops(1);
+            stats.maybe_report();
+            entry = cursor.next()?;
+        }
+        if cli.tables > 1 {
+            println!("Table {}: {} of {} entries", table, digest, digest.entries);
+        }
+        total.merge(&digest);
+    }
+    println!("Checksum: {} of {} entries", total, total.entries);
+    Ok(())
+}
+
+fn compact_database(cli: &Cli) -> anyhow::Result<()> {
+    if !std::path::Path::new(&cli.path).exists() {
+        anyhow::bail!("Database does not exist, aborting.");
+    }
+
+    let env = backend::open(cli)?;
+    let before = db_size(cli.path.as_ref())?;
+    let start = std::time::Instant::now();
+    env.compact()?;
+    println!(
+        "Compacted in {:?}, {} bytes on disk before, {} after",
+        start.elapsed(),
+        before,
+        db_size(cli.path.as_ref())?
+    );
+    print!("{}", env.compaction_report()?);
+    Ok(())
+}
+
+fn backup_database(cli: &Cli, backup_opts: &BackupOpts) -> anyhow::Result<()> {
+    if !std::path::Path::new(&cli.path).exists() {
+        anyhow::bail!("Database does not exist, aborting.");
+    }
+    let dest = backup_opts
+        .dest
+        .clone()
+        .unwrap_or_else(|| format!("{}.backup", cli.path.trim_end_matches('/')).into());
+    if dest.exists() {
+        if !cli.y {
+            anyhow::bail!("{} already exists, aborting.", dest.display());
+        }
+        tracing::info!("Backup already exists, removing");
+        std::fs::remove_dir_all(&dest)?;
+    }
+
+    let env = backend::open(cli)?;
+    let start = std::time::Instant::now();
+    env.backup(&dest, backup_opts.compact)?;
+    println!(
+        "Backed up {} bytes on disk to {} bytes in {:?}",
+        db_size(cli.path.as_ref())?,
+        db_size(&dest)?,
+        start.elapsed()
+    );
+    Ok(())
+}
+
+fn check_existing(cli: &Cli) -> anyhow::Result<()> {
+    if std::path::Path::new(&cli.path).exists() && !cli.dry_run {
+        if cli.y {
+            tracing::info!("Database already exists, removing");
+            remove_database(cli)?;
+        } else if cli.cont {
+            tracing::info!("Database already exists, continuing");
+        } else {
+            anyhow::bail!("Database already exists, aborting.");
+        }
+    }
+    Ok(())
+}
+
+/// Removes the database and the files next to it that describe its content. Every engine keeps
+/// its files in the directory at the path.
+fn remove_database(cli: &Cli) -> anyhow::Result<()> {
+    let path = Path::new(&cli.path);
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => std::fs::remove_dir_all(path)?,
+        // Every engine keeps a directory, a file there is someone else's.
+        Ok(_) => anyhow::bail!(
+            "{} is not a database directory, not removing it",
+            path.display()
+        ),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err.into()),
+    }
+    for sidecar in [
+        keys::manifest_path(cli),
+        progress::path(cli),
+        torture::markers_path(cli),
+    ] {
+        match std::fs::remove_file(&sidecar) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Removes the database after asking for confirmation, unless `-y` is given.
+fn wipe_database(cli: &Cli) -> anyhow::Result<()> {
+    if !cli.y {
+        print!("Remove {} and the files next to it? [y/N] ", cli.path);
+        std::io::Write::flush(&mut std::io::stdout())?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            println!("Not removing anything.");
+            return Ok(());
+        }
+    }
+    remove_database(cli)?;
+    tracing::info!(path = %cli.path, "Removed the database");
+    Ok(())
+}
+
+/// Returns the total size of the files under `path`.
+fn db_size(path: &Path) -> anyhow::Result<u64> {
+    let meta = std::fs::metadata(path)?;
+    if !meta.is_dir() {
+        return Ok(meta.len());
+    }
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        size += db_size(&entry?.path())?;
+    }
+    Ok(size)
+}
+
+/// The commits since the last sync of `--sync-every` and when it happened, shared by all writers.
+static SYNCED: Mutex<(u64, Option<std::time::Instant>)> = Mutex::new((0, None));
+
+/// What happens after every commit of a write workload. Returns the latency of the sync if
+/// `--sync-every` made one.
+fn after_commit(
+    cli: &Cli,
+    env: &dyn backend::Engine,
+    commit_lat: std::time::Duration,
+) -> anyhow::Result<Option<std::time::Duration>> {
+    check_spike(cli, env, commit_lat)?;
+    if cli.fadvise_dontneed {
+        mem::advise_dontneed(cli.path.as_ref())?;
+    }
+    sysio::throttle()?;
+    let Some(every) = cli.sync_every else {
+        return Ok(None);
+    };
+    // Held while syncing, so that concurrent writers don't sync twice.
+    let mut synced = SYNCED.lock().unwrap();
+    synced.0 += 1;
+    let due = match (every, synced.1) {
+        (SyncEvery::Batches(batches), _) => synced.0 >= batches,
+        (SyncEvery::Time(interval), Some(last)) => last.elapsed() >= interval,
+        (SyncEvery::Time(_), None) => {
+            synced.1 = Some(std::time::Instant::now());
+            false
+        }
+    };
+    if !due {
+        return Ok(None);
+    }
+    let start = std::time::Instant::now();
+    tracing::debug_span!("sync").in_scope(|| env.sync())?;
+    *synced = (0, Some(std::time::Instant::now()));
+    Ok(Some(start.elapsed()))
+}
+
+/// Dumps what the engine is up to if a commit took longer than `--spike-threshold-ms`.
+fn check_spike(
+    cli: &Cli,
+    env: &dyn backend::Engine,
+    commit_lat: std::time::Duration,
+) -> anyhow::Result<()> {
+    let Some(threshold) = cli.spike_threshold_ms else {
+        return Ok(());
+    };
+    if commit_lat < std::time::Duration::from_millis(threshold) {
+        return Ok(());
+    }
+    tracing::warn!(
+        ?commit_lat,
+        db_size = db_size(cli.path.as_ref())?,
+        engine = ?backend::last_commit_info(),
+        "Commit latency spike"
+    );
+    print!("{}", env.diagnostics()?);
+    Ok(())
+}
+
+fn fill_database(cli: &Cli) -> anyhow::Result<()> {
+    check_existing(cli)?;
+
+    let fill_ops = cli.subcmd.as_fill_opts().unwrap();
+    if fill_ops.threads == 0 {
+        anyhow::bail!("At least one thread is required.");
+    }
+    if fill_ops.readers > 0 && fill_ops.reader_txn_ops == 0 {
+        anyhow::bail!("Readers need at least one operation per transaction.");
+    }
+    if cli.shards == 0 || fill_ops.threads % cli.shards != 0 {
+        anyhow::bail!(
+            "Every shard needs its own writer, use a multiple of {} threads.",
+            cli.shards
+        );
+    }
+
+    let resumed = if cli.cont {
+        progress::load(cli)?
+    } else {
+        progress::clear(cli)?;
+        None
+    };
+
+    tracing::info!(?cli, "Opening database");
+    let envs = backend::open_shards(cli)?;
+    interrupt::install();
+
+    let mut keys = (0..fill_ops.threads)
+        .map(|worker| KeyGen::new(cli, worker, fill_ops.threads))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let manifest = if cli.cont {
+        keys::load_manifest(cli)?
+    } else {
+        Keys::new(cli.key_sz)
+    };
+    let mut manifest = manifest.iter();
+    let workers = match &resumed {
+        Some(resumed) => {
+            if resumed.workers.len() != fill_ops.threads {
+                anyhow::bail!(
+                    "The interrupted fill had {} threads, resume it with as many",
+                    resumed.workers.len()
+                );
+            }
+            // Every worker gets back its own keys, so it picks the same ones to rewrite.
+            for (keys, worker) in keys.iter_mut().zip(&resumed.workers) {
+                keys.keys.extend(manifest.by_ref().take(worker.keys));
+                keys.set_next(worker.next);
+            }
+            tracing::info!(
+                items = resumed.workers.iter().map(|w| w.items).sum::<usize>(),
+                "Resuming the interrupted fill"
+            );
+            resumed.workers.clone()
+        }
+        None => {
+            // Spread the keys of the previous runs over the workers, so they can rewrite them.
+            for (i, key) in manifest.enumerate() {
+                keys[i % fill_ops.threads].keys.push(key);
+            }
+            vec![WorkerProgress::default(); fill_ops.threads]
+        }
+    };
+    let probes = (0..fill_ops.readers)
+        .map(|_| KeyGen::new(cli, 0, 1))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let mut stats = cli.stats()?;
+    stats.set_total(fill_ops.n.unwrap_or(0));
+    if let Some(resumed) = &resumed {
+        let items = resumed.workers.iter().map(|w| w.items).sum();
+        stats.resume(items, resumed.written, resumed.elapsed);
+    }
+    let stats = Mutex::new(stats);
+    let ops = stats.lock().unwrap().counter();
+    let stop = AtomicBool::new(false);
+
+    let keys = std::thread::scope(|s| {
+        let readers: Vec<_> = probes
+            .into_iter()
+            .enumerate()
+            .map(|(reader, probes)| {
+                let rand = cli.rng(READER_STREAM + reader as u128);
+                let env = &*envs[reader % envs.len()];
+                let (stop, stats) = (&stop, &stats);
+                s.spawn(move || read_worker(env, cli, rand, probes, stop, stats))
+            })
+            .collect();
+        let workers: Vec<_> = keys
+            .into_iter()
+            .zip(workers)
+            .enumerate()
+            .map(|(worker, (keys, progress))| {
+                let n = fill_ops.worker_items(worker);
+                // Every worker gets its own stream, the first one matches the single-threaded fill.
+                let rand = progress::Tracked::new(cli.rng(worker as u128), progress.steps);
+                let env = &*envs[worker % envs.len()];
+                let (stats, ops) = (&stats, &ops);
+                s.spawn(move || {
+                    fill_worker(env, cli, rand, (worker, n, progress), keys, stats, ops)
+                })
+            })
+            .collect();
+        let filled = workers
+            .into_iter()
+            .map(|worker| worker.join().expect("fill worker panicked"))
+            .collect::<anyhow::Result<Vec<_>>>();
+        // Readers run for as long as the writers do.
+        stop.store(true, Ordering::Relaxed);
+        let read = readers
+            .into_iter()
+            .try_for_each(|reader| reader.join().expect("read worker panicked"));
+        read.and(filled)
+    })?;
+
+    let stats = stats.into_inner().unwrap();
+    let (keys, workers): (Vec<_>, Vec<_>) = keys.into_iter().unzip();
+    let done: usize = workers.iter().map(|w| w.items).sum();
+    if done < fill_ops.n.unwrap_or(usize::MAX) {
+        // Also after --fill-to, so that a continued fill goes on with the same streams.
+        tracing::info!(items = done, "Stopped early, continue with --cont");
+        let progress = Progress {
+            elapsed: stats.elapsed(),
+            written: stats.written(),
+            workers,
+        };
+        progress::save(cli, &progress)?;
+    } else {
+        progress::clear(cli)?;
+    }
+    stats.done();
+    for (shard, env) in envs.iter().enumerate() {
+        if envs.len() > 1 {
+            let size = db_size(&backend::shard_path(cli, shard))?;
+            println!("Shard {}: {} bytes on disk", shard, size);
+        }
+        print!("{}", env.compaction_report()?);
+    }
+    keys::save_manifest(cli, keys.iter().flat_map(|keys| &keys.keys))
+}
+
+/// Inserts the `n` items of a worker, of which `progress` were already inserted by an interrupted
+/// fill. Returns the keys and how far it got. Every item is generated into the same buffers.
+fn fill_worker(
+    env: &dyn backend::Engine,
+    cli: &Cli,
+    mut rand: progress::Tracked,
+    (worker, n, mut progress): (usize, usize, WorkerProgress),
+    mut keys: KeyGen,
+    stats: &Mutex<Stats>,
+    ops: &OpCounter,
+) -> anyhow::Result<(KeyGen, WorkerProgress)> {
+    let fill_ops = cli.subcmd.as_fill_opts().unwrap();
+    let deadline = cli.deadline();
+    let mut limiter = cli.limiter(fill_ops.threads);
+    let mut sampler = OpSampler::new(cli.sample_ops);
+    // Recorded once per batch, to keep the stats lock uncontended.
+    let mut lats = Vec::new();
+
+    let mut remaining = n.saturating_sub(progress.items);
+    // Without a number of items the fill is only bounded by its size.
+    if fill_ops.n.is_some() {
+        keys.keys.reserve(remaining);
+    }
+    let mut item = Entry::default();
+
+    loop {
+        let _batch = tracing::debug_span!("batch").entered();
+        let mut txn = env.begin()?;
+
+        let batch_remaining = remaining;
+        let known = keys.keys.len();
+        let next = keys.next();
+        let mut bytes = 0;
+        let start = std::time::Instant::now();
+        let mut batch_start = None;
+        for _ in 0..fill_ops.batch_sz {
+            if remaining == 0 || deadline.passed() {
+                break;
+            }
+            let intended = limiter.wait();
+            batch_start = batch_start.or(intended);
+
+            let seq = n - remaining;
+            fill_item(cli, fill_ops, &mut rand, &mut keys, seq, &mut item);
+            let (key, data) = &item;
+            bytes += key.len() + data.len();
+            let op_start = sampler.start().map(|start| intended.unwrap_or(start));
+            txn.insert(cli.table(key), key, data, cli.on_duplicate)?;
+            lats.extend(op_start.map(|start| start.elapsed()));
+            remaining -= 1;
+            ops.add(1);
+        }
+
+        let batch_lat = batch_start.unwrap_or(start).elapsed();
+        // let stat = txn.db_stat(&main).unwrap();
+
+        let interrupted = interrupt::interrupted();
+        if interrupted && cli.rollback_on_interrupt {
+            keys.keys.truncate(known);
+            keys.set_next(next);
+            break;
+        }
+
+        // The generated items are skipped, as if they had been written and deleted again.
+        let abort = aborted(cli, worker, n - batch_remaining);
+        if abort {
+            keys.keys.truncate(known);
+            keys.set_next(next);
+        }
+        let start = std::time::Instant::now();
+        if abort {
+            tracing::debug_span!("abort").in_scope(|| drop(txn));
+        } else {
+            tracing::debug_span!("commit").in_scope(|| txn.commit())?;
+        }
+        let commit_lat = start.elapsed();
+        progress = WorkerProgress {
+            items: n - remaining,
+            keys: keys.keys.len(),
+            steps: rand.steps(),
+            next: keys.next(),
+        };
+        let sync_lat = if abort {
+            None
+        } else {
+            after_commit(cli, env, commit_lat)?
+        };
+
+        let mut stats = stats.lock().unwrap();
+        for lat in lats.drain(..) {
+            stats.record("put", lat);
+        }
+        if let Some(sync_lat) = sync_lat {
+            stats.record("sync", sync_lat);
+        }
+        if abort {
+            stats.record("abort", commit_lat);
+        } else {
+            stats.record_batch(
+                env,
+                batch_remaining - remaining,
+                bytes,
+                batch_lat,
+                commit_lat,
+            )?;
+        }
+
+        if remaining == 0 || interrupted || deadline.passed() {
+            break;
+        }
+
+        stats.maybe_report();
+        // println!("{:#?}", stat);
+        drop(stats);
+
+        if let Some(ByteSize(target)) = fill_ops.fill_to {
+            let size = db_size(cli.path.as_ref())?;
+            if size >= target {
+                tracing::info!(size, "Reached the target size");
+                break;
+            }
+        }
+    }
+
+    Ok((keys, progress))
+}
+
+/// Generates the `seq`-th item of a fill worker. Only depends on the random stream, so `verify`
+/// can replay it.
+fn fill_item(
+    cli: &Cli,
+    fill_ops: &FillOpts,
+    rand: &mut impl Rng,
+    keys: &mut KeyGen,
+    seq: usize,
+    (key, data): &mut Entry,
+) {
+    keys.gen_into(rand, fill_ops.cold, key);
+    // Every byte is overwritten, the buffer only keeps its allocation.
+    data.resize(fill_ops.value_dist.sample(rand), 0);
+    values::fill(rand, data, cli.compressibility);
+    cli.seal(key, data, seq);
+}
+
+fn verify_database(cli: &Cli, fill_opts: &FillOpts) -> anyhow::Result<()> {
+    if !std::path::Path::new(&cli.path).exists() {
+        anyhow::bail!("Database does not exist, aborting.");
+    }
+    if fill_opts.threads == 0 {
+        anyhow::bail!("At least one thread is required.");
+    }
+    if let KeyDist::MonotonicTimestamp = cli.key_dist {
+        return verify_manifest(cli);
+    }
+    if fill_opts.n.is_none() {
+        anyhow::bail!("Verify regenerates the fill, it needs its number of items.");
+    }
+
+    // Workers only rewrite their own keys, so the final value of every key doesn't depend on how
+    // the workers interleaved.
+    let mut expected = vec![BTreeMap::new(); cli.tables];
+    for worker in 0..fill_opts.threads {
+        let mut rand = cli.rng(worker as u128);
+        let mut keys = KeyGen::new(cli, worker, fill_opts.threads)?;
+        let n = fill_opts.worker_items(worker);
+        let mut item = Entry::default();
+        for first in (0..n).step_by(fill_opts.batch_sz.max(1)) {
+            let (known, next) = (keys.keys.len(), keys.next());
+            let batch: Vec<Entry> = (first..n.min(first + fill_opts.batch_sz))
+                .map(|seq| {
+                    fill_item(cli, fill_opts, &mut rand, &mut keys, seq, &mut item);
+                    item.clone()
+                })
+                .collect();
+            if aborted(cli, worker, first) {
+                keys.keys.truncate(known);
+                keys.set_next(next);
+                continue;
+            }
+            for (key, value) in batch {
+                expected[cli.table(&key)].insert(key, value);
+            }
+        }
+    }
+    println!(
+        "Regenerated {} entries",
+        expected.iter().map(BTreeMap::len).sum::<usize>()
+    );
+
+    tracing::info!(?cli, "Opening database");
+    let env = backend::open_readonly(cli)?;
+    let txn = env.begin_read()?;
+
+    // Both sides are sorted by key, so every table is compared in a single pass.
+    let (mut missing, mut mismatched, mut unexpected) = (0, 0, 0);
+    for (table, expected) in expected.into_iter().enumerate() {
+        let mut cursor = txn.cursor(table)?;
+        let mut expected = expected.into_iter().peekable();
+        let mut entry = cursor.first()?;
+        loop {
+            match (entry.as_ref(), expected.peek()) {
+                (None, None) => break,
+                (Some((key, value)), Some((expected_key, expected_value)))
+                    if key == expected_key =>
+                {
+                    cli.check_value(table, key, value)?;
+                    if value != expected_value {
+                        mismatched += 1;
+                    }
+                    expected.next();
+                    entry = cursor.next()?;
+                }
+                (Some((key, _)), Some((expected_key, _))) if key > expected_key => {
+                    missing += 1;
+                    expected.next();
+                }
+                (None, Some(_)) => {
+                    missing += 1;
+                    expected.next();
+                }
+                (Some(_), _) => {
+                    unexpected += 1;
+                    entry = cursor.next()?;
+                }
+            }
+        }
+    }
+
+    if missing + mismatched + unexpected > 0 {
+        anyhow::bail!(
+            "Verification failed: {} missing, {} mismatched, {} unexpected entries",
+            missing,
+            mismatched,
+            unexpected
+        );
+    }
+    println!("Verified, the database matches the fill");
+    Ok(())
+}
+
+/// Timestamp keys can't be regenerated, so only checks that all keys of the manifest are present.
+fn verify_manifest(cli: &Cli) -> anyhow::Result<()> {
+    let keys = keys::load_manifest(cli)?;
+    if keys.is_empty() {
+        anyhow::bail!("Timestamp keys can't be regenerated and there is no key manifest.");
+    }
+
+    tracing::info!(?cli, "Opening database");
+    let env = backend::open_readonly(cli)?;
+    let txn = env.begin_read()?;
+    let mut missing = 0;
+    for key in &keys {
+        if txn.get(cli.table(key), key)?.is_none() {
+            missing += 1;
+        }
+    }
+
+    if missing > 0 {
+        anyhow::bail!(
+            "Verification failed: {} of {} keys missing",
+            missing,
+            keys.len()
+        );
+    }
+    println!(
+        "Verified, all {} keys of the manifest are present",
+        keys.len()
+    );
+    Ok(())
+}
+
+/// Reads from the database until `stop` is set. Reads are not counted as fill operations, only
+/// their latencies are recorded, once per read transaction to keep the lock uncontended.
+fn read_worker(
+    env: &dyn backend::Engine,
+    cli: &Cli,
+    mut rand: rand_pcg::Pcg64,
+    probes: KeyGen,
+    stop: &AtomicBool,
+    stats: &Mutex<Stats>,
+) -> anyhow::Result<()> {
+    let fill_ops = cli.subcmd.as_fill_opts().unwrap();
+    let name = match fill_ops.reader_op {
+        ReaderOp::Get => "reader get",
+        ReaderOp::Scan => "reader scan",
+    };
+
+    let mut lats = Vec::with_capacity(fill_ops.reader_txn_ops);
+    while !stop.load(Ordering::Relaxed) {
+        let txn = env.begin_read()?;
+        for _ in 0..fill_ops.reader_txn_ops {
+            let probe = probes.probe(&mut rand);
+            let table = cli.table(&probe);
+            match fill_ops.reader_op {
+                ReaderOp::Get => {
+                    // Look up a key that exists in this snapshot, if there is any yet.
+                    let Some(key) = txn.seek(table, &probe)? else {
+                        break;
+                    };
+                    let start = std::time::Instant::now();
+                    let value = txn.get(table, &key)?;
+                    lats.push(start.elapsed());
+                    if let Some(value) = value {
+                        cli.check_value(table, &key, &value)?;
+                    }
+                }
+                ReaderOp::Scan => {
+                    let start = std::time::Instant::now();
+                    let mut cursor = txn.cursor(table)?;
+                    let mut entry = cursor.seek(&probe)?;
+                    let mut visited = 0;
+                    while entry.is_some() && visited < fill_ops.reader_scan_len {
+                        visited += 1;
+                        entry = cursor.next()?;
+                    }
+                    lats.push(start.elapsed());
+                }
+            }
+        }
+        drop(txn);
+
+        let mut stats = stats.lock().unwrap();
+        for lat in lats.drain(..) {
+            stats.record(name, lat);
+        }
+    }
+
+    Ok(())
+}
+
+fn mixed_database(cli: &Cli, mixed_opts: &MixedOpts) -> anyhow::Result<()> {
+    check_existing(cli)?;
+    interrupt::install();
+
+    tracing::info!(?cli, "Opening database");
+    let env = backend::open(cli)?;
+
+    let mut rand = cli.rng(0);
+
+    let mut keys = KeyGen::new(cli, 0, 1)?;
+    if cli.cont {
+        keys.keys = keys::load_manifest(cli)?;
+    }
+    if let Some(workload) = mixed_opts.workload {
+        keys.set_dist(workload.key_dist());
+    }
+    let mut stats = cli.stats()?;
+
+    let deadline = cli.deadline();
+    let mut limiter = cli.limiter(1);
+    let mut sampler = OpSampler::new(cli.sample_ops);
+    let mut remaining = mixed_opts.n;
+    while remaining > 0 {
+        let _batch = tracing::debug_span!("batch").entered();
+        let mut txn = env.begin()?;
+
+        let batch_sz = mixed_opts.batch_sz.min(remaining);
+        let batch_remaining = remaining;
+        let known = keys.keys.len();
+        let mut bytes = 0;
+        let start = std::time::Instant::now();
+        let mut batch_start = None;
+        for _ in 0..batch_sz {
+            if deadline.passed() {
+                break;
+            }
+            let intended = limiter.wait();
+            batch_start = batch_start.or(intended);
+            let op = match mixed_opts.workload {
+                // Everything but inserts needs a known key.
+                _ if keys.keys.is_empty() => ycsb::Op::Insert,
+                Some(workload) => workload.pick(&mut rand),
+                None if rand.gen_bool(mixed_opts.read_pct as f64) => ycsb::Op::Read,
+                None if rand.gen_bool(mixed_opts.cold as f64) => ycsb::Op::Insert,
+                None => ycsb::Op::Update,
+            };
+            match op {
+                ycsb::Op::Read => {
+                    let key = keys.pick(&mut rand).unwrap();
+                    let read_start = intended.unwrap_or_else(std::time::Instant::now);
+                    let value = txn.get(cli.table(key), key)?;
+                    stats.record("get", read_start.elapsed());
+                    if let Some(value) = value {
+                        cli.check_value(cli.table(key), key, &value)?;
+                    }
+                }
+                ycsb::Op::Scan => {
+                    let key = keys.pick(&mut rand).unwrap();
+                    let len = rand.gen_range(1..=ycsb::MAX_SCAN_LEN);
+                    let scan_start = intended.unwrap_or_else(std::time::Instant::now);
+                    let mut cursor = txn.cursor(cli.table(key))?;
+                    let mut entry = cursor.seek(key)?;
+                    for _ in 1..len {
+                        if entry.is_none() {
+                            break;
+                        }
+                        entry = cursor.next()?;
+                    }
+                    stats.record("scan", scan_start.elapsed());
+                }
+                ycsb::Op::Insert | ycsb::Op::Update | ycsb::Op::ReadModifyWrite => {
+                    let key = match op {
+                        ycsb::Op::Insert => {
+                            let key = keys.fresh(&mut rand);
+                            keys.keys.push(&key);
+                            key
+                        }
+                        _ => keys.pick(&mut rand).unwrap().to_vec(),
+                    };
+                    let mut data = vec![0; mixed_opts.value_dist.sample(&mut rand)];
+                    values::fill(&mut rand, &mut data, cli.compressibility);
+                    cli.seal(&key, &mut data, mixed_opts.n - remaining);
+                    bytes += key.len() + data.len();
+                    let op_start = sampler.start().map(|start| intended.unwrap_or(start));
+                    if let ycsb::Op::ReadModifyWrite = op {
+                        txn.get(cli.table(&key), &key)?;
+                    }
+                    txn.insert(cli.table(&key), &key, &data, cli.on_duplicate)?;
+                    if let Some(start) = op_start {
+                        let name = match op {
+                            ycsb::Op::ReadModifyWrite => "rmw",
+                            _ => "put",
+                        };
+                        stats.record(name, start.elapsed());
+                    }
+                }
+            }
+            remaining -= 1;
+            stats.add_ops(1);
+        }
+        let batch_lat = batch_start.unwrap_or(start).elapsed();
+
+        let interrupted = interrupt::interrupted();
+        if interrupted && cli.rollback_on_interrupt {
+            keys.keys.truncate(known);
+            break;
+        }
+
+        let start = std::time::Instant::now();
+        tracing::debug_span!("commit").in_scope(|| txn.commit())?;
+        let commit_lat = start.elapsed();
+        if let Some(sync_lat) = after_commit(cli, &*env, commit_lat)? {
+            stats.record("sync", sync_lat);
+        }
+        stats.record_batch(
+            &*env,
+            batch_remaining - remaining,
+            bytes,
+            batch_lat,
+            commit_lat,
+        )?;
+        stats.maybe_report();
+        if interrupted {
+            tracing::info!(remaining, "Interrupted");
+            break;
+        }
+        if deadline.passed() {
+            tracing::info!(remaining, "Reached the deadline");
+            break;
+        }
+    }
+
+    stats.done();
+    print!("{}", env.compaction_report()?);
+    keys::save_manifest(cli, &keys.keys)
+}
+
+fn churn_database(cli: &Cli, churn_opts: &ChurnOpts) -> anyhow::Result<()> {
+    check_existing(cli)?;
+    interrupt::install();
+
+    tracing::info!(?cli, "Opening database");
+    let env = backend::open(cli)?;
+
+    let mut rand = cli.rng(0);
+
+    let mut keys = KeyGen::new(cli, 0, 1)?;
+    if cli.cont {
+        keys.keys = keys::load_manifest(cli)?;
+    }
+    let mut stats = cli.stats()?;
+
+    let deadline = cli.deadline();
+    let mut limiter = cli.limiter(1);
+    let mut sampler = OpSampler::new(cli.sample_ops);
+    let mut remaining = churn_opts.n;
+    while remaining > 0 {
+        let _batch = tracing::debug_span!("batch").entered();
+        let mut txn = env.begin()?;
+
+        let start = std::time::Instant::now();
+        let mut batch_start = None;
+        let deletes = if churn_opts.delete_range {
+            let live = keys.keys.len();
+            let (from, to) = random_range(&mut rand, churn_opts.delete_pct);
+            for table in 0..cli.tables {
+                txn.delete_range(table, &from, &to)?;
+            }
+            keys.keys.retain(|key| key < &from[..] || key >= &to[..]);
+            let deleted = live - keys.keys.len();
+            stats.add_ops(deleted);
+            deleted
+        } else {
+            let deletes = (keys.keys.len() as f64 * churn_opts.delete_pct).round() as usize;
+            for _ in 0..deletes {
+                let intended = limiter.wait();
+                batch_start = batch_start.or(intended);
+                let key = keys.keys.swap_remove(rand.gen_range(0..keys.keys.len()));
+                let op_start = sampler.start().map(|start| intended.unwrap_or(start));
+                txn.delete(cli.table(&key), &key)?;
+                if let Some(start) = op_start {
+                    stats.record("delete", start.elapsed());
+                }
+                stats.add_ops(1);
+            }
+            deletes
+        };
+        // Apart from the single deletes, the phase holds thousands of them and the waits between.
+        let phase = if churn_opts.delete_range {
+            "delete range"
+        } else {
+            "delete phase"
+        };
+        stats.record(phase, start.elapsed());
+
+        let batch_sz = churn_opts.batch_sz.min(remaining);
+        let batch_remaining = remaining;
+        // Keys deleted by a rolled back batch stay forgotten, the manifest is a subset then.
+        let known = keys.keys.len();
+        let mut bytes = 0;
+        for _ in 0..batch_sz {
+            if deadline.passed() {
+                break;
+            }
+            let intended = limiter.wait();
+            batch_start = batch_start.or(intended);
+            let key = keys.gen(&mut rand, 1.0);
+            let mut data = vec![0; churn_opts.value_dist.sample(&mut rand)];
+            values::fill(&mut rand, &mut data, cli.compressibility);
+            cli.seal(&key, &mut data, churn_opts.n - remaining);
+            bytes += key.len() + data.len();
+            let op_start = sampler.start().map(|start| intended.unwrap_or(start));
+            txn.insert(cli.table(&key), &key, &data, cli.on_duplicate)?;
+            if let Some(start) = op_start {
+                stats.record("put", start.elapsed());
+            }
+            remaining -= 1;
+            stats.add_ops(1);
+        }
+        let batch_lat = batch_start.unwrap_or(start).elapsed();
+
+        let interrupted = interrupt::interrupted();
+        if interrupted && cli.rollback_on_interrupt {
+            keys.keys.truncate(known);
+            break;
+        }
+
+        let start = std::time::Instant::now();
+        tracing::debug_span!("commit").in_scope(|| txn.commit())?;
+        let commit_lat = start.elapsed();
+        if let Some(sync_lat) = after_commit(cli, &*env, commit_lat)? {
+            stats.record("sync", sync_lat);
+        }
+        stats.record_batch(
+            &*env,
+            batch_remaining - remaining + deletes,
+            bytes,
+            batch_lat,
+            commit_lat,
+        )?;
+        stats.maybe_report();
+        if interrupted {
+            tracing::info!(remaining, "Interrupted");
+            break;
+        }
+        if deadline.passed() {
+            tracing::info!(remaining, "Reached the deadline");
+            break;
+        }
+    }
+
+    println!("{} live keys", keys.keys.len());
+    stats.done();
+    if churn_opts.delete_range {
+        reclaim(cli, &*env)?;
+    }
+    print!("{}", env.compaction_report()?);
+    keys::save_manifest(cli, &keys.keys)
+}
+
+/// Returns the bounds of a random range of keys, the given fraction of the key space by their
+/// first 8 bytes.
+fn random_range(rand: &mut impl Rng, fraction: f64) -> ([u8; 8], [u8; 8]) {
+    let width = (fraction * u64::MAX as f64) as u64;
+    let from = rand.gen_range(0..=u64::MAX - width);
+    (from.to_be_bytes(), (from + width).to_be_bytes())
+}
+
+/// Times a full compaction, which reclaims the space of deleted ranges in engines that compact.
+/// The others reuse the freed pages and don't shrink.
+fn reclaim(cli: &Cli, env: &dyn backend::Engine) -> anyhow::Result<()> {
+    let before = db_size(cli.path.as_ref())?;
+    let start = std::time::Instant::now();
+    if let Err(err) = env.compact() {
+        tracing::info!("Not reclaiming the deleted ranges: {:#}", err);
+        return Ok(());
+    }
+    let after = db_size(cli.path.as_ref())?;
+    println!(
+        "Reclaimed {} bytes in {:?}, {} bytes on disk",
+        before.saturating_sub(after),
+        start.elapsed(),
+        after
+    );
+    Ok(())
+}
+
+fn update_database(cli: &Cli, update_opts: &UpdateOpts) -> anyhow::Result<()> {
+    check_existing(cli)?;
+    if update_opts.hot_keys == 0 {
+        anyhow::bail!("Invalid update options: {:?}", update_opts);
+    }
+    interrupt::install();
+
+    tracing::info!(?cli, "Opening database");
+    let env = backend::open(cli)?;
+
+    let mut rand = cli.rng(0);
+
+    let previous = if cli.cont {
+        keys::load_manifest(cli)?
+    } else {
+        Keys::new(cli.key_sz)
+    };
+    let mut keys = KeyGen::new(cli, 0, 1)?;
+    for _ in 0..update_opts.hot_keys {
+        keys.gen(&mut rand, 1.0);
+    }
+    let mut stats = cli.stats()?;
+
+    let mut written = 0;
+    let deadline = cli.deadline();
+    let mut limiter = cli.limiter(1);
+    let mut sampler = OpSampler::new(cli.sample_ops);
+    let mut remaining = update_opts.n;
+    while remaining > 0 {
+        let _batch = tracing::debug_span!("batch").entered();
+        let mut txn = env.begin()?;
+
+        let batch_sz = update_opts.batch_sz.min(remaining);
+        let batch_remaining = remaining;
+        let mut bytes = 0;
+        let start = std::time::Instant::now();
+        let mut batch_start = None;
+        for _ in 0..batch_sz {
+            if deadline.passed() {
+                break;
+            }
+            let intended = limiter.wait();
+            batch_start = batch_start.or(intended);
+            let key = keys.pick(&mut rand).unwrap().to_vec();
+            let value_sz = update_opts.value_dist.sample(&mut rand);
+            let mut data = vec![0; value_sz];
+            values::fill(&mut rand, &mut data, cli.compressibility);
+            cli.seal(&key, &mut data, update_opts.n - remaining);
+            written += value_sz;
+            bytes += key.len() + value_sz;
+            let op_start = sampler.start().map(|start| intended.unwrap_or(start));
+            txn.put(cli.table(&key), &key, &data)?;
+            if let Some(start) = op_start {
+                stats.record("put", start.elapsed());
+            }
+            remaining -= 1;
+            stats.add_ops(1);
+        }
+        let batch_lat = batch_start.unwrap_or(start).elapsed();
+
+        let interrupted = interrupt::interrupted();
+        if interrupted && cli.rollback_on_interrupt {
+            break;
+        }
+
+        let start = std::time::Instant::now();
+        tracing::debug_span!("commit").in_scope(|| txn.commit())?;
+        let commit_lat = start.elapsed();
+        if let Some(sync_lat) = after_commit(cli, &*env, commit_lat)? {
+            stats.record("sync", sync_lat);
+        }
+        stats.record_batch(
+            &*env,
+            batch_remaining - remaining,
+            bytes,
+            batch_lat,
+            commit_lat,
+        )?;
+        stats.maybe_report();
+        if interrupted {
+            tracing::info!(remaining, "Interrupted");
+            break;
+        }
+        if deadline.passed() {
+            tracing::info!(remaining, "Reached the deadline");
+            break;
+        }
+    }
+
+    println!("Wrote {} value bytes", written);
+    stats.done();
+    print!("{}", env.compaction_report()?);
+    keys::save_manifest(cli, previous.iter().chain(&keys.keys))
+}
+
+fn dupsort_database(cli: &Cli, dupsort_opts: &DupsortOpts) -> anyhow::Result<()> {
+    check_existing(cli)?;
+    interrupt::install();
+
+    tracing::info!(?cli, "Opening database");
+    let env = backend::open(cli)?;
+
+    let mut rand = cli.rng(0);
+
+    let mut keys = KeyGen::new(cli, 0, 1)?;
+    let mut stats = cli.stats()?;
+
+    let (mut inserted, mut read) = (0, 0);
+    let deadline = cli.deadline();
+    let mut limiter = cli.limiter(1);
+    let mut sampler = OpSampler::new(cli.sample_ops);
+    let mut remaining = dupsort_opts.n;
+    while remaining > 0 {
+        let _batch = tracing::debug_span!("batch").entered();
+        let mut txn = env.begin()?;
+
+        let batch_sz = dupsort_opts.batch_sz.min(remaining);
+        let batch_remaining = remaining;
+        let known = keys.keys.len();
+        let mut bytes = 0;
+        let start = std::time::Instant::now();
+        let mut batch_start = None;
+        for _ in 0..batch_sz {
+            if deadline.passed() {
+                break;
+            }
+            let intended = limiter.wait();
+            batch_start = batch_start.or(intended);
+            if !keys.keys.is_empty() && rand.gen_bool(dupsort_opts.read_pct as f64) {
+                let key = keys.pick(&mut rand).unwrap();
+                let read_start = intended.unwrap_or_else(std::time::Instant::now);
+                read += txn.dups(key)?.len();
+                stats.record("dups", read_start.elapsed());
+            } else {
+                let key = keys.gen(&mut rand, dupsort_opts.cold);
+                let mut value = vec![0; dupsort_opts.value_dist.sample(&mut rand)];
+                values::fill(&mut rand, &mut value, cli.compressibility);
+                bytes += key.len() + value.len();
+                let op_start = sampler.start().map(|start| intended.unwrap_or(start));
+                txn.put_dup(&key, &value)?;
+                if let Some(start) = op_start {
+                    stats.record("put", start.elapsed());
+                }
+                inserted += 1;
+            }
+            remaining -= 1;
+            stats.add_ops(1);
+        }
+        let batch_lat = batch_start.unwrap_or(start).elapsed();
+
+        let interrupted = interrupt::interrupted();
+        if interrupted && cli.rollback_on_interrupt {
+            keys.keys.truncate(known);
+            break;
+        }
+
+        let start = std::time::Instant::now();
+        tracing::debug_span!("commit").in_scope(|| txn.commit())?;
+        let commit_lat = start.elapsed();
+        if let Some(sync_lat) = after_commit(cli, &*env, commit_lat)? {
+            stats.record("sync", sync_lat);
+        }
+        stats.record_batch(
+            &*env,
+            batch_remaining - remaining,
+            bytes,
+            batch_lat,
+            commit_lat,
+        )?;
+        stats.maybe_report();
+        if interrupted {
+            tracing::info!(remaining, "Interrupted");
+            break;
+        }
+        if deadline.passed() {
+            tracing::info!(remaining, "Reached the deadline");
+            break;
+        }
+    }
+
+    println!(
+        "Inserted {} values under {} keys, read {} values",
+        inserted,
+        keys.keys.len(),
+        read
+    );
+    stats.done();
+    print!("{}", env.compaction_report()?);
+    Ok(())
+}
+
+fn read_database(cli: &Cli, read_opts: &ReadOpts) -> anyhow::Result<()> {
+    if !std::path::Path::new(&cli.path).exists() {
+        anyhow::bail!("Database does not exist, aborting.");
+    }
+
+    tracing::info!(?cli, "Opening database");
+    let env = backend::open_readonly(cli)?;
+    let txn = env.begin_read()?;
+
+    let mut rand = cli.rng(0);
+
+    let probes = KeyGen::new(cli, 0, 1)?;
+    let mut keys = keys::load_manifest(cli)?;
+    if keys.is_empty() {
+        // Keys are random, so seeking to a random position gives a reasonably uniform sample.
+        for _ in 0..read_opts.sample_sz {
+            let probe = probes.probe(&mut rand);
+            match txn.seek(cli.table(&probe), &probe)? {
+                Some(key) if key.len() != cli.key_sz => anyhow::bail!(
+                    "Sampled a key of {} bytes, run with the --key-sz of the fill",
+                    key.len()
+                ),
+                Some(key) => keys.push(&key),
+                None => anyhow::bail!("Database is empty, aborting."),
+            }
+        }
+        tracing::info!(keys = keys.len(), "Sampled keys");
+    } else {
+        tracing::info!(keys = keys.len(), "Loaded keys from the manifest");
+    }
+
+    if read_opts.multi_get == 0 {
+        anyhow::bail!("Invalid multi-get batch size: 0");
+    }
+    if keys.is_empty() {
+        anyhow::bail!("No keys to read");
+    }
+    let (mut reads, mut hits) = (0, 0);
+    let mut stats = cli.stats()?;
+    let deadline = cli.deadline();
+    let mut limiter = cli.limiter(1);
+    let mut batch = Vec::with_capacity(read_opts.multi_get);
+    while reads < read_opts.n && !deadline.passed() {
+        batch.clear();
+        // A multi get is due once its first key is.
+        let mut intended = None;
+        while batch.len() < read_opts.multi_get.min(read_opts.n - reads) {
+            intended = intended.or(limiter.wait());
+            batch.push(if rand.gen_bool(read_opts.cold as f64) {
+                probes.probe(&mut rand)
+            } else {
+                keys[rand.gen_range(0..keys.len())].to_vec()
+            });
+        }
+        reads += batch.len();
+
+        let start = intended.unwrap_or_else(std::time::Instant::now);
+        if read_opts.multi_get == 1 {
+            let key = &batch[0];
+            let value = txn.get(cli.table(key), key)?;
+            stats.record("get", start.elapsed());
+            if let Some(value) = value {
+                cli.check_value(cli.table(key), key, &value)?;
+                hits += 1;
+            }
+        } else {
+            let lookups: Vec<_> = batch
+                .iter()
+                .map(|key| (cli.table(key), key.as_slice()))
+                .collect();
+            let values = txn.multi_get(&lookups)?;
+            let lat = start.elapsed();
+            stats.record("multi get", lat);
+            stats.record("get", lat / batch.len() as u32);
+            for ((table, key), value) in lookups.iter().zip(&values) {
+                if let Some(value) = value {
+                    cli.check_value(*table, key, value)?;
+                    hits += 1;
+                }
+            }
+        }
+        stats.add_ops(batch.len());
+        stats.maybe_report();
+    }
+
+    println!("Read {} items ({} hits)", reads, hits);
+    stats.done();
+    Ok(())
+}
+
+fn scan_database(cli: &Cli, scan_opts: &ScanOpts) -> anyhow::Result<()> {
+    if !std::path::Path::new(&cli.path).exists() {
+        anyhow::bail!("Database does not exist, aborting.");
+    }
+
+    tracing::info!(?cli, "Opening database");
+    let env = backend::open_readonly(cli)?;
+    let txn = env.begin_read()?;
+    let mut cursors = (0..cli.tables)
+        .map(|table| txn.cursor(table))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut rand = cli.rng(0);
+
+    let probes = KeyGen::new(cli, 0, 1)?;
+    let (mut scans, mut entries) = (0, 0);
+    let mut stats = cli.stats()?;
+    let deadline = cli.deadline();
+    let mut limiter = cli.limiter(1);
+    while scans < scan_opts.n && !deadline.passed() {
+        scans += 1;
+        let intended = limiter.wait();
+        let probe = probes.probe(&mut rand);
+        let table = cli.table(&probe);
+        let cursor = &mut cursors[table];
+
+        let start = intended.unwrap_or_else(std::time::Instant::now);
+        let mut entry = if scan_opts.reverse {
+            cursor.seek_for_prev(&probe)?
+        } else {
+            cursor.seek(&probe)?
+        };
+        stats.record("seek", start.elapsed());
+
+        let mut visited = 0;
+        while let Some((key, value)) = &entry {
+            if visited == scan_opts.len {
+                break;
+            }
+            cli.check_value(table, key, value)?;
+            visited += 1;
+            entry = if scan_opts.reverse {
+                cursor.prev()?
+            } else {
+                cursor.next()?
+            };
+        }
+        stats.record("scan", start.elapsed());
+        stats.add_ops(visited);
+        entries += visited;
+        stats.maybe_report();
+    }
+
+    println!("Scanned {} ranges ({} entries)", scans, entries);
+    stats.done();
+    Ok(())
+}
+
+fn prefix_scan_database(cli: &Cli, opts: &PrefixScanOpts) -> anyhow::Result<()> {
+    let prefix_len = opts.prefix_len.unwrap_or(cli.key_prefix_bytes);
+    if prefix_len == 0 || prefix_len > cli.key_sz {
+        anyhow::bail!(
+            "Invalid prefix length {} for {} byte keys, set --prefix-len or --key-prefix-bytes",
+            prefix_len,
+            cli.key_sz
+        );
+    }
+    if !std::path::Path::new(&cli.path).exists() {
+        anyhow::bail!("Database does not exist, aborting.");
+    }
+
+    tracing::info!(?cli, "Opening database");
+    let env = backend::open_readonly(cli)?;
+    let txn = env.begin_read()?;
+    // Keys are spread over the tables by hash, so every prefix has entries in all of them.
+    let mut cursors = (0..cli.tables)
+        .map(|table| txn.cursor(table))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut rand = cli.rng(0);
+
+    let probes = KeyGen::new(cli, 0, 1)?;
+    let (mut scans, mut entries, mut empty) = (0, 0, 0);
+    let mut stats = cli.stats()?;
+    let deadline = cli.deadline();
+    let mut limiter = cli.limiter(1);
+    while scans < opts.n && !deadline.passed() {
+        scans += 1;
+        let intended = limiter.wait();
+        let mut prefix = probes.probe(&mut rand);
+        prefix.truncate(prefix_len);
+
+        let start = intended.unwrap_or_else(std::time::Instant::now);
+        let mut visited = 0;
+        for (table, cursor) in cursors.iter_mut().enumerate() {
+            let seek = std::time::Instant::now();
+            let mut entry = cursor.seek(&prefix)?;
+            stats.record("seek", seek.elapsed());
+            while let Some((key, value)) = &entry {
+                if !key.starts_with(&prefix) {
+                    break;
+                }
+                cli.check_value(table, key, value)?;
+                visited += 1;
+                entry = cursor.next()?;
+            }
+        }
+        stats.record("prefix", start.elapsed());
+        stats.add_ops(visited);
+        entries += visited;
+        if visited == 0 {
+            empty += 1;
+        }
+        stats.maybe_report();
     }
 
+    println!(
+        "Scanned {} prefixes ({} entries, {:.1} per prefix, {} empty)",
+        scans,
+        entries,
+        entries as f64 / scans.max(1) as f64,
+        empty
+    );
+    stats.done();
     Ok(())
 }
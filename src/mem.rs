@@ -0,0 +1,163 @@
+//! Memory usage: the resident and dirty memory of the process, and how much of the database files
+//! is in the page cache. With a writemap, dirty pages of the mdbx map count towards the process.
+//!
+//! Also evicting the database files from the page cache, to measure with a cold cache, and
+//! taking memory away from the run, to have a dataset exceed it on a machine with plenty.
+
+use std::{fs::File, os::unix::io::AsRawFd, path::Path};
+
+/// Returns the memory usage as a report line, with how much of the database is cached if `cache`.
+/// That maps every database file, so it is only worth it once in a while.
+pub fn report(db_path: &Path, cache: bool) -> anyhow::Result<String> {
+    let (mut rss, mut dirty) = (0, 0);
+    for line in std::fs::read_to_string("/proc/self/smaps_rollup")?.lines() {
+        let Some((name, kb)) = line.split_once(':') else {
+            continue;
+        };
+        let Some(kb) = kb.trim().strip_suffix(" kB") else {
+            continue;
+        };
+        match name {
+            "Rss" => rss = kb.parse::<u64>()? * 1024,
+            "Shared_Dirty" | "Private_Dirty" => dirty += kb.parse::<u64>()? * 1024,
+            _ => {}
+        }
+    }
+    let mut line = format!("rss {} bytes, {} dirty", rss, dirty);
+    if cache {
+        let (cached, size) = residency(db_path, &mut Vec::new())?;
+        line += &format!(
+            ", {} of {} database bytes cached ({:.1}%)",
+            cached,
+            size,
+            cached as f64 * 100.0 / size.max(1) as f64
+        );
+    }
+    Ok(line)
+}
+
+/// Returns the bytes of the files under `path` that are in the page cache, and their size.
+///
+/// Every file is mapped and the mapping checked with `mincore`, which reports the page cache
+/// pages of the file no matter who mapped or read them. `pages` is the buffer for its answer,
+/// reused for all files.
+fn residency(path: &Path, pages: &mut Vec<u8>) -> anyhow::Result<(u64, u64)> {
+    let meta = std::fs::metadata(path)?;
+    if meta.is_dir() {
+        let (mut cached, mut size) = (0, 0);
+        for entry in std::fs::read_dir(path)? {
+            let (entry_cached, entry_size) = residency(&entry?.path(), pages)?;
+            cached += entry_cached;
+            size += entry_size;
+        }
+        return Ok((cached, size));
+    }
+    let len = meta.len() as usize;
+    if len == 0 {
+        return Ok((0, 0));
+    }
+
+    let file = File::open(path)?;
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    pages.clear();
+    pages.resize(len.div_ceil(page), 0);
+    // SAFETY: the mapping is only passed to mincore and unmapped right after, never read.
+    let resident = unsafe {
+        let addr = libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_SHARED,
+            file.as_raw_fd(),
+            0,
+        );
+        if addr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let ret = libc::mincore(addr, len, pages.as_mut_ptr());
+        let err = std::io::Error::last_os_error();
+        libc::munmap(addr, len);
+        if ret != 0 {
+            return Err(err.into());
+        }
+        pages.iter().filter(|&&flags| flags & 1 != 0).count()
+    };
+    Ok(((resident * page).min(len) as u64, len as u64))
+}
+
+/// Evicts the database from the page cache. As root, the clean caches of the whole system are
+/// dropped, otherwise the clean pages of every database file are advised away.
+pub fn drop_cache(db_path: &Path) -> anyhow::Result<()> {
+    unsafe { libc::sync() };
+    if std::fs::write("/proc/sys/vm/drop_caches", "3").is_ok() {
+        return Ok(());
+    }
+    advise_dontneed(db_path)
+}
+
+/// Advises the kernel to drop the clean page cache pages of the files under `path`. Dirty pages
+/// and pages mapped by a process, like the mdbx map, stay.
+pub fn advise_dontneed(path: &Path) -> anyhow::Result<()> {
+    if std::fs::metadata(path)?.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            advise_dontneed(&entry?.path())?;
+        }
+        return Ok(());
+    }
+    let file = File::open(path)?;
+    let ret = unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+    if ret != 0 {
+        return Err(std::io::Error::from_raw_os_error(ret).into());
+    }
+    Ok(())
+}
+
+/// Memory allocated and locked, so that the rest of the system has only the limit of
+/// `--mem-limit` left, when there is no cgroup to enforce it. Only held to free it on drop.
+pub struct Balloon {
+    _memory: Vec<u8>,
+}
+
+impl Balloon {
+    /// Inflates the balloon to all available memory but `bytes`.
+    pub fn inflate(bytes: u64) -> anyhow::Result<Self> {
+        let available = meminfo("MemAvailable")?;
+        let Some(size) = available.checked_sub(bytes) else {
+            tracing::warn!(
+                available,
+                bytes,
+                "Less memory available than the limit already"
+            );
+            return Ok(Balloon {
+                _memory: Vec::new(),
+            });
+        };
+        let mut balloon = vec![0u8; size as usize];
+        // Zeroed allocations are mapped lazily, only writing makes the pages resident.
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        for byte in balloon.iter_mut().step_by(page) {
+            *byte = 1;
+        }
+        // Without the lock the balloon may be swapped out instead of shrinking the page cache.
+        if unsafe { libc::mlock(balloon.as_ptr().cast(), balloon.len()) } != 0 {
+            let err = std::io::Error::last_os_error();
+            tracing::warn!("Locking the balloon failed, it may be swapped out: {}", err);
+        }
+        tracing::info!(size, available, "Inflated the memory balloon");
+        Ok(Balloon { _memory: balloon })
+    }
+}
+
+/// Returns a field of `/proc/meminfo` in bytes.
+pub fn meminfo(field: &str) -> anyhow::Result<u64> {
+    for line in std::fs::read_to_string("/proc/meminfo")?.lines() {
+        if let Some(kb) = line
+            .strip_prefix(field)
+            .and_then(|rest| rest.strip_prefix(':'))
+            .and_then(|rest| rest.trim().strip_suffix(" kB"))
+        {
+            return Ok(kb.parse::<u64>()? * 1024);
+        }
+    }
+    anyhow::bail!("/proc/meminfo has no {}", field)
+}
@@ -0,0 +1,131 @@
+//! A Prometheus scrape endpoint, for dashboards of long runs.
+//!
+//! The statistics of the running workload publish a copy of their histograms and gauges about
+//! once per second, the endpoint serves the latest copy in the text exposition format.
+
+use hdrhistogram::Histogram;
+use std::{
+    fmt::Write as _,
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+struct Registry {
+    ops: Option<Arc<AtomicU64>>,
+    db_path: Option<PathBuf>,
+    latencies: Vec<(&'static str, Histogram<u64>)>,
+    gauges: Vec<(&'static str, u64)>,
+}
+
+/// Whether there is an endpoint, without one nothing is published.
+static SERVING: AtomicBool = AtomicBool::new(false);
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    ops: None,
+    db_path: None,
+    latencies: Vec::new(),
+    gauges: Vec::new(),
+});
+
+/// Makes the operations counter of a new workload the one that is served.
+pub fn register(ops: Arc<AtomicU64>, db_path: PathBuf) {
+    let mut registry = REGISTRY.lock().unwrap();
+    registry.ops = Some(ops);
+    registry.db_path = Some(db_path);
+    registry.latencies.clear();
+    registry.gauges.clear();
+}
+
+pub fn publish(latencies: &[(&'static str, Histogram<u64>)], gauges: Vec<(&'static str, u64)>) {
+    if !SERVING.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut registry = REGISTRY.lock().unwrap();
+    registry.latencies = latencies.to_vec();
+    registry.gauges = gauges;
+}
+
+/// Serves the metrics on the given address from a background thread.
+pub fn serve(addr: SocketAddr) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    tracing::info!(addr = %listener.local_addr()?, "Serving metrics on /metrics");
+    SERVING.store(true, Ordering::Relaxed);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream.map_err(anyhow::Error::from).and_then(|mut stream| {
+                // Whatever was asked for, there is only one page.
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request)?;
+                let body = render();
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )?;
+                Ok(())
+            });
+            if let Err(err) = result {
+                tracing::warn!("Serving metrics failed: {:#}", err);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn render() -> String {
+    let registry = REGISTRY.lock().unwrap();
+    let mut out = String::new();
+    if let Some(ops) = &registry.ops {
+        out += "# TYPE torture_ops_total counter\n";
+        let _ = writeln!(out, "torture_ops_total {}", ops.load(Ordering::Relaxed));
+    }
+    if let Some(size) = registry
+        .db_path
+        .as_ref()
+        .and_then(|p| crate::db_size(p).ok())
+    {
+        out += "# TYPE torture_db_size_bytes gauge\n";
+        let _ = writeln!(out, "torture_db_size_bytes {}", size);
+    }
+    for (name, value) in &registry.gauges {
+        let _ = writeln!(
+            out,
+            "# TYPE torture_{} gauge\ntorture_{} {}",
+            name, name, value
+        );
+    }
+    if !registry.latencies.is_empty() {
+        out += "# TYPE torture_latency_seconds summary\n";
+    }
+    for (name, hist) in &registry.latencies {
+        for q in [0.5, 0.9, 0.99, 0.999] {
+            let _ = writeln!(
+                out,
+                "torture_latency_seconds{{op=\"{}\",quantile=\"{}\"}} {}",
+                name,
+                q,
+                hist.value_at_quantile(q) as f64 / 1e9
+            );
+        }
+        let sum = hist.mean() * hist.len() as f64 / 1e9;
+        let _ = writeln!(
+            out,
+            "torture_latency_seconds_sum{{op=\"{}\"}} {}",
+            name, sum
+        );
+        let _ = writeln!(
+            out,
+            "torture_latency_seconds_count{{op=\"{}\"}} {}",
+            name,
+            hist.len()
+        );
+    }
+    out
+}
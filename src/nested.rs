@@ -0,0 +1,111 @@
+//! Nested transactions: every batch is written in sub-batches, each in a child transaction that
+//! is committed into the batch or aborted, like speculative execution does. With `--flat` the
+//! same sub-batches go straight into the batch, and the aborted ones are generated but skipped,
+//! so that both write the same data and the difference is the cost of the children.
+
+use crate::{backend, interrupt, keys::KeyGen, values, Cli, NestedOpts, SubCommand};
+use rand::Rng;
+use std::time::Instant;
+
+pub fn run(cli: &Cli, opts: &NestedOpts) -> anyhow::Result<()> {
+    if opts.batch_sz == 0 || opts.sub_batch_sz == 0 {
+        anyhow::bail!("Invalid nested transaction options: {:?}", opts);
+    }
+    crate::check_existing(cli)?;
+    interrupt::install();
+
+    tracing::info!(?cli, "Opening database");
+    let env = backend::open(cli)?;
+
+    let mut rand = cli.rng(0);
+    let mut keys = KeyGen::new(cli, 0, 1)?;
+    let mut stats = cli.stats()?;
+    stats.set_total(opts.n);
+    let mut remaining = opts.n;
+    let mut aborted = 0;
+    while remaining > 0 && !interrupt::interrupted() {
+        let _batch = tracing::debug_span!("batch").entered();
+        let mut txn = env.begin()?;
+        let batch_sz = opts.batch_sz.min(remaining);
+        let mut bytes = 0;
+        let start = Instant::now();
+        for sub_start in (0..batch_sz).step_by(opts.sub_batch_sz) {
+            let sub_batch_sz = opts.sub_batch_sz.min(batch_sz - sub_start);
+            let abort = rand.gen_bool(opts.abort_pct);
+            let mut entries = Vec::with_capacity(sub_batch_sz);
+            for _ in 0..sub_batch_sz {
+                let key = keys.fresh(&mut rand);
+                let mut data = vec![0; opts.value_dist.sample(&mut rand)];
+                values::fill(&mut rand, &mut data, cli.compressibility);
+                cli.seal(&key, &mut data, opts.n - remaining + sub_start);
+                entries.push((key, data));
+            }
+            if abort {
+                aborted += 1;
+            } else {
+                bytes += entries
+                    .iter()
+                    .map(|(k, v)| k.len() + v.len())
+                    .sum::<usize>();
+                keys.keys
+                    .extend(entries.iter().map(|(key, _)| key.as_slice()));
+            }
+            if opts.flat {
+                if !abort {
+                    for (key, value) in entries {
+                        txn.put(cli.table(&key), &key, &value)?;
+                    }
+                }
+                continue;
+            }
+
+            let begin = Instant::now();
+            let mut child = txn.nested()?;
+            stats.record("sub begin", begin.elapsed());
+            for (key, value) in entries {
+                child.put(cli.table(&key), &key, &value)?;
+            }
+            let end = Instant::now();
+            if abort {
+                drop(child);
+                stats.record("sub abort", end.elapsed());
+            } else {
+                child.commit()?;
+                stats.record("sub commit", end.elapsed());
+            }
+        }
+        let batch_lat = start.elapsed();
+        remaining -= batch_sz;
+        stats.add_ops(batch_sz);
+
+        let start = Instant::now();
+        tracing::debug_span!("commit").in_scope(|| txn.commit())?;
+        let commit_lat = start.elapsed();
+        if let Some(sync_lat) = crate::after_commit(cli, &*env, commit_lat)? {
+            stats.record("sync", sync_lat);
+        }
+        stats.record_batch(&*env, batch_sz, bytes, batch_lat, commit_lat)?;
+        stats.maybe_report();
+    }
+
+    println!(
+        "{} sub-batches aborted, {} items committed",
+        aborted,
+        keys.keys.len()
+    );
+    stats.done();
+    print!("{}", env.compaction_report()?);
+    drop(env);
+    crate::keys::save_manifest(cli, &keys.keys)?;
+    if opts.against_flat {
+        let mut flat_cli = cli.clone();
+        flat_cli.path = format!("{}.flat", cli.path.trim_end_matches('/'));
+        flat_cli.subcmd = SubCommand::Nested(NestedOpts {
+            flat: true,
+            against_flat: false,
+            ..opts.clone()
+        });
+        crate::bulk::against(cli, &flat_cli, ["nested", "flat"])?;
+    }
+    Ok(())
+}
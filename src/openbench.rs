@@ -0,0 +1,71 @@
+//! Reopen benchmark: the time it takes to open and close the database, optionally after a writer
+//! was killed, so that the engine has to recover on open.
+
+use crate::{backend, torture::Batches, Cli, OpenBenchOpts};
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+/// Set in the environment of the child, which then writes until it is killed.
+const CHILD_ENV: &str = "OPEN_BENCH_CHILD";
+
+pub fn run(cli: &Cli, opts: &OpenBenchOpts) -> anyhow::Result<()> {
+    if opts.batch_sz == 0 {
+        anyhow::bail!("Invalid open bench options: {:?}", opts);
+    }
+    if std::env::var_os(CHILD_ENV).is_some() {
+        return child(cli, opts);
+    }
+    if !Path::new(&cli.path).exists() {
+        anyhow::bail!("Database does not exist, aborting.");
+    }
+
+    let exe = std::env::current_exe()?;
+    let mut stats = cli.stats()?;
+    for round in 0..opts.rounds {
+        if opts.unclean {
+            let mut child = Command::new(&exe)
+                .args(std::env::args_os().skip(1))
+                .env(CHILD_ENV, "1")
+                .stdout(Stdio::null())
+                .spawn()?;
+            std::thread::sleep(Duration::from_millis(opts.kill_after_ms));
+            if let Some(status) = child.try_wait()? {
+                anyhow::bail!("Writer child exited before it was killed: {}", status);
+            }
+            child.kill()?;
+            child.wait()?;
+        }
+
+        let start = Instant::now();
+        let env = backend::open(cli)?;
+        let open_lat = start.elapsed();
+        let start = Instant::now();
+        drop(env);
+        let close_lat = start.elapsed();
+
+        stats.record("open", open_lat);
+        stats.record("close", close_lat);
+        stats.add_ops(1);
+        println!(
+            "Round {}: opened in {:?}, closed in {:?}",
+            round, open_lat, close_lat
+        );
+    }
+    stats.done();
+    Ok(())
+}
+
+fn child(cli: &Cli, opts: &OpenBenchOpts) -> anyhow::Result<()> {
+    let env = backend::open(cli)?;
+    for items in Batches::new(cli, usize::MAX, opts.batch_sz, opts.value_dist)? {
+        let mut txn = env.begin()?;
+        for (key, value) in items {
+            txn.put(cli.table(&key), &key, &value)?;
+        }
+        txn.commit()?;
+    }
+    unreachable!("batches are endless")
+}
@@ -0,0 +1,136 @@
+//! Machine-readable per-batch results.
+//!
+//! The environment of the run comes first, as a JSON line of its own or as `# name: value`
+//! comment lines before the CSV header.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Copy, Clone)]
+pub enum OutputFormat {
+    /// One JSON object per line.
+    Json,
+    Csv,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => anyhow::bail!("Unknown output format: {}", s),
+        }
+    }
+}
+
+pub struct Output {
+    format: OutputFormat,
+    out: BufWriter<File>,
+    db_path: PathBuf,
+    batch: u64,
+}
+
+impl Output {
+    pub fn create(format: OutputFormat, out_file: &Path, db_path: &Path) -> anyhow::Result<Self> {
+        let mut out = BufWriter::new(File::create(out_file)?);
+        let env = crate::fingerprint::collect(db_path);
+        match format {
+            OutputFormat::Json => {
+                writeln!(out, "{{\"env\":{}}}", crate::ledger::object_json(&env))?
+            }
+            OutputFormat::Csv => {
+                for (name, value) in &env {
+                    writeln!(out, "# {}: {}", name, value)?;
+                }
+                writeln!(
+                    out,
+                    "timestamp_ms,batch,items,batch_lat_us,commit_lat_us,db_size,written,used_bytes,engine"
+                )?;
+            }
+        }
+        Ok(Output {
+            format,
+            out,
+            db_path: db_path.to_path_buf(),
+            batch: 0,
+        })
+    }
+
+    /// Writes a batch, along with the bytes written so far, the bytes of the database in use if
+    /// the engine tracks them, as last sampled, and the engine counters of the commit, see
+    /// `backend::last_commit_info`.
+    pub fn write_batch(
+        &mut self,
+        items: usize,
+        written: u64,
+        used: Option<u64>,
+        (batch_lat, commit_lat): (Duration, Duration),
+        engine: &[(&'static str, u64)],
+    ) -> anyhow::Result<()> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        let db_size = crate::db_size(&self.db_path)?;
+        let batch_lat = batch_lat.as_micros();
+        let commit_lat = commit_lat.as_micros();
+        match self.format {
+            OutputFormat::Json => {
+                let engine: Vec<String> = engine
+                    .iter()
+                    .map(|(name, value)| format!("\"{}\":{}", name, value))
+                    .collect();
+                writeln!(
+                    self.out,
+                    "{{\"timestamp_ms\":{},\"batch\":{},\"items\":{},\"batch_lat_us\":{},\
+                     \"commit_lat_us\":{},\"db_size\":{},\"written\":{},\"used_bytes\":{},\
+                     \"engine\":{{{}}}}}",
+                    timestamp,
+                    self.batch,
+                    items,
+                    batch_lat,
+                    commit_lat,
+                    db_size,
+                    written,
+                    used.map_or("null".to_string(), |used| used.to_string()),
+                    engine.join(",")
+                )?
+            }
+            // The counters differ between engines, so they share a column as `name=value;...`.
+            OutputFormat::Csv => writeln!(
+                self.out,
+                "{},{},{},{},{},{},{},{},{}",
+                timestamp,
+                self.batch,
+                items,
+                batch_lat,
+                commit_lat,
+                db_size,
+                written,
+                used.map_or(String::new(), |used| used.to_string()),
+                engine
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, value))
+                    .collect::<Vec<_>>()
+                    .join(";")
+            )?,
+        }
+        // Someone is probably tailing the file to plot it.
+        self.out.flush()?;
+        self.batch += 1;
+        Ok(())
+    }
+}
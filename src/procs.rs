@@ -0,0 +1,178 @@
+//! Several processes on one environment, a writer and any number of readers, coordinating through
+//! files next to the database. The writer holds a lock on one for its whole run and writes its
+//! state into the other: `ready` once the environment exists and `done` when it finished. Readers
+//! wait for the writer to be ready and stop like at a deadline once it is done or gone.
+
+use crate::{Cli, SubCommand};
+use std::{
+    fs::{File, OpenOptions},
+    os::unix::io::AsRawFd,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+    time::Duration,
+};
+
+/// How often readers look at the coordination file.
+const POLL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProcessRole {
+    Writer,
+    Reader,
+}
+
+impl FromStr for ProcessRole {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "writer" => Ok(ProcessRole::Writer),
+            "reader" => Ok(ProcessRole::Reader),
+            _ => anyhow::bail!("Unknown process role: {}", s),
+        }
+    }
+}
+
+/// The lock file, locked by the writer for as long as it runs.
+static WRITER: OnceLock<File> = OnceLock::new();
+
+/// Set for readers once the writer is done.
+static WRITER_DONE: AtomicBool = AtomicBool::new(false);
+
+/// The state of the writer. Replaced as a whole, so readers never see it half written.
+pub fn path(cli: &Cli) -> PathBuf {
+    format!("{}.coord", cli.path.trim_end_matches('/')).into()
+}
+
+/// Separate from the state, the lock would be lost with the file it replaces.
+fn lock_path(cli: &Cli) -> PathBuf {
+    format!("{}.coord.lock", cli.path.trim_end_matches('/')).into()
+}
+
+/// Takes the writer lock, or waits for the writer to be ready and starts watching it.
+pub fn start(cli: &Cli) -> anyhow::Result<()> {
+    let Some(role) = cli.process_role else {
+        return Ok(());
+    };
+    if !matches!(cli.kind, crate::EngineKind::Mdbx | crate::EngineKind::Lmdb) {
+        anyhow::bail!("Only mdbx and lmdb environments can be shared by processes.");
+    }
+    let reads_only = matches!(
+        cli.subcmd,
+        SubCommand::Read(_)
+            | SubCommand::Scan(_)
+            | SubCommand::PrefixScan(_)
+            | SubCommand::Stat
+            | SubCommand::GcStat(_)
+            | SubCommand::Checksum
+    );
+    if role == ProcessRole::Reader && !reads_only {
+        anyhow::bail!("Readers only run read workloads: {:?}", cli.subcmd);
+    }
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path(cli))?;
+    match role {
+        ProcessRole::Writer => {
+            if !try_lock(&file, libc::LOCK_EX) {
+                anyhow::bail!("Another writer holds {}", lock_path(cli).display());
+            }
+            WRITER.get_or_init(|| file);
+            set_state(cli, "starting")
+        }
+        ProcessRole::Reader => {
+            tracing::info!(path = %path(cli).display(), "Waiting for the writer");
+            loop {
+                let state = state(&path(cli))?;
+                if state == "ready" {
+                    break;
+                }
+                if state == "done" {
+                    WRITER_DONE.store(true, Ordering::Relaxed);
+                    return Ok(());
+                }
+                // Without a state the writer didn't start yet, with one and without a writer it
+                // died before it got ready.
+                if !state.is_empty() && writer_gone(&file) {
+                    anyhow::bail!("The writer stopped while {}", state);
+                }
+                std::thread::sleep(POLL);
+            }
+            let path = path(cli);
+            std::thread::spawn(move || watch(&path, file));
+            Ok(())
+        }
+    }
+}
+
+/// Tells the readers that the environment exists, once the writer opened it.
+pub fn ready(cli: &Cli) -> anyhow::Result<()> {
+    match cli.process_role {
+        Some(ProcessRole::Writer) => set_state(cli, "ready"),
+        _ => Ok(()),
+    }
+}
+
+/// Tells the readers that the writer is done.
+pub fn finish(cli: &Cli) -> anyhow::Result<()> {
+    match cli.process_role {
+        Some(ProcessRole::Writer) => set_state(cli, "done"),
+        _ => Ok(()),
+    }
+}
+
+/// Whether the writer this reader coordinates with is done.
+pub fn writer_done() -> bool {
+    WRITER_DONE.load(Ordering::Relaxed)
+}
+
+/// Waits until the writer says it is done or dies without saying so, which releases its lock.
+fn watch(path: &Path, file: File) {
+    loop {
+        std::thread::sleep(POLL);
+        let done = state(path).map_or(true, |state| state == "done") || writer_gone(&file);
+        if done {
+            WRITER_DONE.store(true, Ordering::Relaxed);
+            return;
+        }
+    }
+}
+
+/// The state the writer last wrote, empty if it didn't write one yet.
+fn state(path: &Path) -> anyhow::Result<String> {
+    match std::fs::read_to_string(path) {
+        Ok(state) => Ok(state.trim().to_string()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn set_state(cli: &Cli, state: &str) -> anyhow::Result<()> {
+    let tmp = format!("{}.tmp", path(cli).display());
+    std::fs::write(&tmp, format!("{}\n", state))?;
+    std::fs::rename(&tmp, path(cli))?;
+    tracing::info!(state, "Told the readers");
+    Ok(())
+}
+
+/// Whether the writer released its lock. The shared lock taken to find out is released again, so
+/// that it doesn't keep the next writer out.
+fn writer_gone(file: &File) -> bool {
+    let gone = try_lock(file, libc::LOCK_SH);
+    if gone {
+        unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_UN) };
+    }
+    gone
+}
+
+/// Tries to lock the file without blocking. A shared lock only succeeds without a writer.
+fn try_lock(file: &File, op: libc::c_int) -> bool {
+    unsafe { libc::flock(file.as_raw_fd(), op | libc::LOCK_NB) == 0 }
+}
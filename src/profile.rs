@@ -0,0 +1,101 @@
+//! CPU profiles of a run with pprof-rs, behind the `profile` feature.
+//!
+//! The samples of every thread are labeled with the engine, so that the flamegraphs of several
+//! engines can be told apart and `compare` writes one profile per engine.
+
+use crate::backend::EngineKind;
+use std::{path::PathBuf, str::FromStr};
+
+/// What to profile and where to write it, `cpu:<path>`. A `.svg` path gets a flamegraph, any
+/// other a pprof protobuf.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub path: PathBuf,
+}
+
+impl FromStr for Profile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("cpu", path)) if !path.is_empty() => Ok(Profile { path: path.into() }),
+            _ => anyhow::bail!("Expected cpu:<path>, got {}", s),
+        }
+    }
+}
+
+impl Profile {
+    /// The profile of one of several engines, with the engine name before the extension.
+    pub fn for_engine(&self, engine: &str) -> Profile {
+        let mut name = self.path.file_stem().unwrap_or_default().to_os_string();
+        name.push(format!(".{}", engine));
+        if let Some(extension) = self.path.extension() {
+            name.push(".");
+            name.push(extension);
+        }
+        Profile {
+            path: self.path.with_file_name(name),
+        }
+    }
+}
+
+#[cfg(feature = "profile")]
+pub struct Profiler {
+    guard: pprof::ProfilerGuard<'static>,
+    path: PathBuf,
+    engine: String,
+}
+
+#[cfg(feature = "profile")]
+pub fn start(profile: &Profile, kind: EngineKind) -> anyhow::Result<Profiler> {
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(997)
+        // Unwinding through these may deadlock in the signal handler.
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()?;
+    Ok(Profiler {
+        guard,
+        path: profile.path.clone(),
+        engine: format!("{:?}", kind).to_lowercase(),
+    })
+}
+
+#[cfg(feature = "profile")]
+impl Profiler {
+    pub fn finish(self) -> anyhow::Result<()> {
+        use pprof::protos::Message;
+
+        let engine = self.engine;
+        let report = self
+            .guard
+            .report()
+            .frames_post_processor(move |frames| {
+                frames.thread_name = format!("{} {}", engine, frames.thread_name);
+            })
+            .build()?;
+        if self.path.extension().is_some_and(|ext| ext == "svg") {
+            report.flamegraph(std::fs::File::create(&self.path)?)?;
+        } else {
+            let mut content = Vec::new();
+            report.pprof()?.encode(&mut content)?;
+            std::fs::write(&self.path, content)?;
+        }
+        tracing::info!(path = %self.path.display(), "Wrote the CPU profile");
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "profile"))]
+pub struct Profiler;
+
+#[cfg(not(feature = "profile"))]
+pub fn start(_profile: &Profile, _kind: EngineKind) -> anyhow::Result<Profiler> {
+    anyhow::bail!("Profiling is not compiled in, enable the profile cargo feature");
+}
+
+#[cfg(not(feature = "profile"))]
+impl Profiler {
+    pub fn finish(self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+//! The progress record of an interrupted fill, so that `--cont` resumes at the very item the fill
+//! stopped at and the combined run is the same as an uninterrupted one.
+//!
+//! The random streams can't be saved directly, but PCG can jump ahead, so the record holds the
+//! number of steps every stream took instead.
+
+use crate::Cli;
+use rand::RngCore;
+use std::{fmt::Write as _, io::ErrorKind, path::PathBuf, time::Duration};
+
+/// How far a fill got.
+#[derive(Debug, Default)]
+pub struct Progress {
+    pub elapsed: Duration,
+    /// The key and value bytes written.
+    pub written: u64,
+    pub workers: Vec<WorkerProgress>,
+}
+
+/// How far one fill worker got.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct WorkerProgress {
+    /// The items inserted.
+    pub items: usize,
+    /// The number of keys the worker knew, its share of the manifest.
+    pub keys: usize,
+    /// The steps its random stream took.
+    pub steps: u128,
+    /// The counter of its sequential keys.
+    pub next: u64,
+}
+
+pub fn path(cli: &Cli) -> PathBuf {
+    format!("{}.progress", cli.path.trim_end_matches('/')).into()
+}
+
+/// Loads the progress of the interrupted fill, `None` if the last fill wasn't interrupted.
+pub fn load(cli: &Cli) -> anyhow::Result<Option<Progress>> {
+    let record = match std::fs::read_to_string(path(cli)) {
+        Ok(record) => record,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let mut progress = Progress::default();
+    for line in record.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            ["elapsed", nanos] => progress.elapsed = Duration::from_nanos(nanos.parse()?),
+            ["written", bytes] => progress.written = bytes.parse()?,
+            ["worker", items, keys, steps, next] => progress.workers.push(WorkerProgress {
+                items: items.parse()?,
+                keys: keys.parse()?,
+                steps: steps.parse()?,
+                next: next.parse()?,
+            }),
+            _ => anyhow::bail!("Invalid line in {}: {}", path(cli).display(), line),
+        }
+    }
+    Ok(Some(progress))
+}
+
+pub fn save(cli: &Cli, progress: &Progress) -> anyhow::Result<()> {
+    if cli.dry_run {
+        return Ok(());
+    }
+    let mut record = format!(
+        "elapsed {}\nwritten {}\n",
+        progress.elapsed.as_nanos(),
+        progress.written
+    );
+    for worker in &progress.workers {
+        writeln!(
+            record,
+            "worker {} {} {} {}",
+            worker.items, worker.keys, worker.steps, worker.next
+        )?;
+    }
+    std::fs::write(path(cli), record)?;
+    tracing::info!(path = %path(cli).display(), "Saved the fill progress");
+    Ok(())
+}
+
+/// Removes the record, once the fill completed or a fresh one starts.
+pub fn clear(cli: &Cli) -> anyhow::Result<()> {
+    match std::fs::remove_file(path(cli)) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// A random stream that counts its steps.
+pub struct Tracked {
+    rand: rand_pcg::Pcg64,
+    steps: u128,
+}
+
+impl Tracked {
+    /// Continues `rand` after `steps` steps.
+    pub fn new(mut rand: rand_pcg::Pcg64, steps: u128) -> Self {
+        rand.advance(steps);
+        Tracked { rand, steps }
+    }
+
+    /// The steps taken since the start of the stream.
+    pub fn steps(&self) -> u128 {
+        self.steps
+    }
+}
+
+impl RngCore for Tracked {
+    fn next_u32(&mut self) -> u32 {
+        self.steps += 1;
+        self.rand.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.steps += 1;
+        self.rand.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        // PCG fills 8 bytes per step, the last step also if it needs fewer.
+        self.steps += dest.len().div_ceil(8) as u128;
+        self.rand.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cli(name: &str) -> Cli {
+        let path = std::env::temp_dir().join(format!("progress-{}-{}", name, std::process::id()));
+        Cli::for_test(&["--path", path.to_str().unwrap()])
+    }
+
+    #[test]
+    fn loads_what_was_saved() -> anyhow::Result<()> {
+        let cli = cli("saved");
+        assert!(load(&cli)?.is_none());
+        let worker = WorkerProgress {
+            items: 10,
+            keys: 8,
+            steps: u64::MAX as u128 + 1,
+            next: 42,
+        };
+        let progress = Progress {
+            elapsed: Duration::from_nanos(1_500_000_001),
+            written: 1 << 40,
+            workers: vec![worker, WorkerProgress::default()],
+        };
+        save(&cli, &progress)?;
+        let loaded = load(&cli)?;
+        clear(&cli)?;
+        let Some(loaded) = loaded else {
+            anyhow::bail!("Nothing loaded");
+        };
+        assert_eq!(loaded.elapsed, progress.elapsed);
+        assert_eq!(loaded.written, progress.written);
+        assert_eq!(loaded.workers.len(), 2);
+        assert_eq!(loaded.workers[0].steps, worker.steps);
+        assert_eq!(loaded.workers[0].next, 42);
+        assert_eq!(loaded.workers[1].items, 0);
+        assert!(load(&cli)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_lines() -> anyhow::Result<()> {
+        let cli = cli("invalid");
+        std::fs::write(path(&cli), "elapsed 1\nworker 1 2 3\n")?;
+        let loaded = load(&cli);
+        clear(&cli)?;
+        assert!(loaded.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+//! Rate limiting, to measure latencies at a fixed offered load instead of flat out.
+
+use std::time::{Duration, Instant};
+
+/// A schedule of a number of operations per second, starting up to `burst` of them at once.
+/// Unlimited if there is no rate.
+pub struct Limiter(Option<Schedule>);
+
+struct Schedule {
+    /// The time between two operations of the schedule.
+    interval: Duration,
+    /// How far ahead of the schedule an operation may start, the intervals of the burst but one.
+    ahead: Duration,
+    /// When the next operation is meant to start. Never moves forward to catch up with a stall.
+    next: Instant,
+}
+
+impl Limiter {
+    pub fn new(rate: Option<f64>, burst: u32) -> Self {
+        Limiter(rate.map(|rate| {
+            let interval = Duration::from_secs_f64(1.0 / rate);
+            Schedule {
+                interval,
+                ahead: interval * (burst.max(1) - 1),
+                next: Instant::now(),
+            }
+        }))
+    }
+
+    /// Blocks until the next operation may start. If limited, returns when it was meant to start
+    /// by the schedule, which is earlier than now once the workload fell behind, and stays behind
+    /// by the whole stall until the operations after it caught up.
+    ///
+    /// Latencies measured from the intended start include the time an operation waited for the
+    /// ones before it, so a stall counts against every operation it delayed instead of only the
+    /// one that hit it, which is the coordinated omission of measuring from the actual start.
+    pub fn wait(&mut self) -> Option<Instant> {
+        let schedule = self.0.as_mut()?;
+        let intended = schedule.next;
+        schedule.next += schedule.interval;
+        // Only the sleep is cut short by the burst, an operation started early is timed from its
+        // actual start.
+        let start = intended.checked_sub(schedule.ahead).unwrap_or(intended);
+        let now = Instant::now();
+        if start > now {
+            std::thread::sleep(start - now);
+        }
+        Some(intended.min(Instant::now()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unlimited_without_a_rate() {
+        assert_eq!(Limiter::new(None, 1).wait(), None);
+    }
+
+    #[test]
+    fn keeps_the_schedule_behind_a_stall() {
+        let mut limiter = Limiter::new(Some(1000.0), 1);
+        let first = limiter.wait().unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        let now = Instant::now();
+        for i in 1..10 {
+            let intended = limiter.wait().unwrap();
+            assert_eq!(intended, first + Duration::from_millis(i));
+            assert!(intended < now);
+        }
+    }
+
+    #[test]
+    fn sleeps_until_the_schedule() {
+        let mut limiter = Limiter::new(Some(100.0), 1);
+        let first = limiter.wait().unwrap();
+        let second = limiter.wait().unwrap();
+        assert_eq!(second, first + Duration::from_millis(10));
+        assert!(Instant::now() >= second);
+    }
+
+    #[test]
+    fn bursts_start_ahead_of_the_schedule() {
+        let mut limiter = Limiter::new(Some(10.0), 3);
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.wait().unwrap() <= Instant::now());
+        }
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+}
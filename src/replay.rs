@@ -0,0 +1,385 @@
+//! Executing a trace recorded with `--record-trace`, see `backend::trace` for the format, or one
+//! generated by another tool.
+//!
+//! Foreign traces have no transactions, their writes are committed every `--batch-sz`. Keys are
+//! taken as they are, values are generated with the given length.
+//!
+//! - `csv`: `op,key,value_size` lines, where `op` is `get`, `put` or `delete` (also `read`,
+//!   `insert`, `update`). A header line is skipped.
+//! - `ycsb`: the operation log of YCSB's basic binding, like `READ usertable user123 [ <all
+//!   fields>]` or `SCAN usertable user123 50 [ <all fields>]`. Written values are as long as the
+//!   field list.
+
+use crate::{
+    backend::{
+        self,
+        trace::{hash, unhex, value},
+        ReadTx, Tx,
+    },
+    interrupt, Cli, ReplayOpts,
+};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    str::FromStr,
+    time::Instant,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TraceFormat {
+    Native,
+    Csv,
+    Ycsb,
+}
+
+impl FromStr for TraceFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "native" => Ok(TraceFormat::Native),
+            "csv" => Ok(TraceFormat::Csv),
+            "ycsb" => Ok(TraceFormat::Ycsb),
+            _ => anyhow::bail!("Unknown trace format: {}", s),
+        }
+    }
+}
+
+/// The value length of YCSB writes whose field list is elided from the log.
+const YCSB_VALUE_SZ: usize = 1000;
+
+enum Op {
+    Get(usize, Vec<u8>),
+    Seek(usize, Vec<u8>),
+    /// Visits the given number of entries from the key on.
+    Scan(usize, Vec<u8>, usize),
+    Put(usize, Vec<u8>, Vec<u8>),
+    Delete(usize, Vec<u8>),
+    /// Deletes the keys from the first up to but excluding the second.
+    DeleteRange(usize, Vec<u8>, Vec<u8>),
+    Add(usize, Vec<u8>, u64),
+    Dup(Vec<u8>, Vec<u8>),
+    Commit,
+}
+
+pub fn run(cli: &Cli, opts: &ReplayOpts) -> anyhow::Result<()> {
+    if opts.batch_sz == 0 {
+        anyhow::bail!("Invalid replay options: {:?}", opts);
+    }
+    let trace = BufReader::new(File::open(&opts.trace)?);
+    tracing::info!(?cli, "Opening database");
+    let env = backend::open(cli)?;
+    interrupt::install();
+
+    // Reads outside of write transactions share a read transaction until the next write.
+    let mut read: Option<Box<dyn ReadTx + '_>> = None;
+    let mut write: Option<Box<dyn Tx + '_>> = None;
+    let (mut ops, mut commits, mut pending) = (0, 0, 0);
+    let mut stats = cli.stats()?;
+    let deadline = cli.deadline();
+    let mut limiter = cli.limiter(1);
+    for (line_no, line) in trace.lines().enumerate() {
+        if deadline.passed() || interrupt::interrupted() {
+            tracing::info!(line = line_no + 1, "Stopped replaying");
+            break;
+        }
+        let line = line?;
+        let parsed = match opts.format {
+            TraceFormat::Native => parse_native(cli, &line),
+            TraceFormat::Csv => parse_csv(cli, &line, line_no),
+            TraceFormat::Ycsb => parse_ycsb(cli, &line),
+        };
+        let op = match parsed {
+            Ok(Some(op)) => op,
+            Ok(None) => continue,
+            Err(err) => anyhow::bail!("Line {}: {:#}: {}", line_no + 1, err, line),
+        };
+
+        let mut start = Instant::now();
+        let name = match op {
+            Op::Get(..) | Op::Seek(..) | Op::Scan(..) => {
+                start = limiter.wait().unwrap_or(start);
+                match &write {
+                    Some(write) => read_op(&**write, op)?,
+                    None => {
+                        if read.is_none() {
+                            read = Some(env.begin_read()?);
+                        }
+                        read_op(&**read.as_ref().unwrap(), op)?
+                    }
+                }
+            }
+            Op::Commit => {
+                if let Some(write) = write.take() {
+                    write.commit()?;
+                    commits += 1;
+                }
+                stats.record("commit", start.elapsed());
+                stats.maybe_report();
+                continue;
+            }
+            op => {
+                start = limiter.wait().unwrap_or(start);
+                if write.is_none() {
+                    // Some engines don't allow a read and a write transaction on one thread.
+                    read = None;
+                    write = Some(env.begin()?);
+                }
+                let name = write_op(&mut **write.as_mut().unwrap(), op)?;
+                pending += 1;
+                name
+            }
+        };
+        stats.record(name, start.elapsed());
+        stats.add_ops(1);
+        ops += 1;
+
+        if opts.format != TraceFormat::Native && pending >= opts.batch_sz {
+            let start = Instant::now();
+            write.take().unwrap().commit()?;
+            stats.record("commit", start.elapsed());
+            stats.maybe_report();
+            commits += 1;
+            pending = 0;
+        }
+    }
+    match write {
+        Some(write) if opts.format != TraceFormat::Native => {
+            write.commit()?;
+            commits += 1;
+        }
+        // A trace cut short by a kill may end in the middle of a transaction, which is dropped.
+        _ => {}
+    }
+    drop(read);
+
+    println!("Replayed {} operations in {} commits", ops, commits);
+    stats.done();
+    Ok(())
+}
+
+fn read_op(txn: &(impl ReadTx + ?Sized), op: Op) -> anyhow::Result<&'static str> {
+    match op {
+        Op::Get(table, key) => {
+            txn.get(table, &key)?;
+            Ok("get")
+        }
+        Op::Seek(table, key) => {
+            txn.seek(table, &key)?;
+            Ok("seek")
+        }
+        Op::Scan(table, key, len) => {
+            let mut cursor = txn.cursor(table)?;
+            let mut entry = cursor.seek(&key)?;
+            for _ in 1..len {
+                if entry.is_none() {
+                    break;
+                }
+                entry = cursor.next()?;
+            }
+            Ok("scan")
+        }
+        _ => unreachable!("not a read"),
+    }
+}
+
+fn write_op(txn: &mut (impl Tx + ?Sized), op: Op) -> anyhow::Result<&'static str> {
+    match op {
+        Op::Put(table, key, value) => {
+            txn.put(table, &key, &value)?;
+            Ok("put")
+        }
+        Op::Delete(table, key) => {
+            txn.delete(table, &key)?;
+            Ok("delete")
+        }
+        Op::DeleteRange(table, start, end) => {
+            txn.delete_range(table, &start, &end)?;
+            Ok("delete range")
+        }
+        Op::Add(table, key, delta) => {
+            txn.add(table, &key, delta)?;
+            Ok("add")
+        }
+        Op::Dup(key, value) => {
+            txn.put_dup(&key, &value)?;
+            Ok("dup")
+        }
+        _ => unreachable!("not a write"),
+    }
+}
+
+fn parse_native(cli: &Cli, line: &str) -> anyhow::Result<Option<Op>> {
+    let fields: Vec<&str> = line.split(' ').collect();
+    let field = |i: usize| {
+        fields
+            .get(i)
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("Missing fields"))
+    };
+    // Recorded with more `--tables` than replayed with, the engine would index out of bounds.
+    let table = || -> anyhow::Result<usize> {
+        let table: usize = parse(field(1)?)?;
+        if table >= cli.tables {
+            anyhow::bail!("Table {} of {} tables", table, cli.tables);
+        }
+        Ok(table)
+    };
+    let op = match field(0)? {
+        "" => return Ok(None),
+        "get" => Op::Get(table()?, unhex(field(2)?)?),
+        "seek" => Op::Seek(table()?, unhex(field(2)?)?),
+        "put" => {
+            let hash = u64::from_str_radix(field(4)?, 16)?;
+            let value = value(cli, parse(field(3)?)?, hash);
+            Op::Put(table()?, unhex(field(2)?)?, value)
+        }
+        "del" => Op::Delete(table()?, unhex(field(2)?)?),
+        "delrange" => Op::DeleteRange(table()?, unhex(field(2)?)?, unhex(field(3)?)?),
+        "add" => Op::Add(table()?, unhex(field(2)?)?, parse(field(3)?)?),
+        "dup" => Op::Dup(unhex(field(1)?)?, unhex(field(2)?)?),
+        "commit" => Op::Commit,
+        op => anyhow::bail!("Unknown operation {}", op),
+    };
+    Ok(Some(op))
+}
+
+fn parse_csv(cli: &Cli, line: &str, line_no: usize) -> anyhow::Result<Option<Op>> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    let (op, key) = match fields.as_slice() {
+        [""] => return Ok(None),
+        [op, key, ..] => (op.to_ascii_lowercase(), key.as_bytes().to_vec()),
+        _ => anyhow::bail!("Expected op,key,value_size"),
+    };
+    let table = cli.table(&key);
+    let op = match op.as_str() {
+        "get" | "read" => Op::Get(table, key),
+        "put" | "insert" | "update" => {
+            let Some(len) = fields.get(2) else {
+                anyhow::bail!("Writes need a value size");
+            };
+            let value = value(cli, parse(len)?, hash(&key));
+            Op::Put(table, key, value)
+        }
+        "delete" => Op::Delete(table, key),
+        "op" if line_no == 0 => return Ok(None),
+        op => anyhow::bail!("Unknown operation {}", op),
+    };
+    Ok(Some(op))
+}
+
+fn parse_ycsb(cli: &Cli, line: &str) -> anyhow::Result<Option<Op>> {
+    let (head, fields) = match line.split_once(" [") {
+        Some((head, fields)) => (head, Some(fields.trim_end_matches(']').trim())),
+        None => (line, None),
+    };
+    let words: Vec<&str> = head.split_whitespace().collect();
+    // YCSB also logs its own status lines, only operations on a table are replayed.
+    let (op, key, rest) = match words.as_slice() {
+        [op, _table, key, rest @ ..] => (*op, key.as_bytes().to_vec(), rest),
+        _ => return Ok(None),
+    };
+    let table = cli.table(&key);
+    let value_sz = match fields {
+        Some(fields) if fields != "<all fields>" && !fields.is_empty() => fields.len(),
+        _ => YCSB_VALUE_SZ,
+    };
+    let op = match op {
+        "READ" => Op::Get(table, key),
+        "SCAN" => {
+            let Some(len) = rest.first() else {
+                anyhow::bail!("Scans need a record count");
+            };
+            Op::Scan(table, key, parse(len)?)
+        }
+        "UPDATE" | "INSERT" => {
+            let value = value(cli, value_sz, hash(&key));
+            Op::Put(table, key, value)
+        }
+        "DELETE" => Op::Delete(table, key),
+        _ => return Ok(None),
+    };
+    Ok(Some(op))
+}
+
+fn parse<T: FromStr>(field: &str) -> anyhow::Result<T>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    Ok(field.parse()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_native_lines() -> anyhow::Result<()> {
+        let cli = Cli::for_test(&["--tables", "2"]);
+        let parse = |line| parse_native(&cli, line);
+        assert!(matches!(parse("get 1 0aff")?, Some(Op::Get(1, key)) if key == [0x0a, 0xff]));
+        let Some(Op::Put(0, key, value)) = parse("put 0 01 5 abc")? else {
+            anyhow::bail!("Not a put");
+        };
+        assert_eq!(key, [1]);
+        assert_eq!(value, super::value(&cli, 5, 0xabc));
+        assert!(matches!(
+            parse("delrange 1 00 ff")?,
+            Some(Op::DeleteRange(1, ..))
+        ));
+        assert!(matches!(parse("add 0 01 7")?, Some(Op::Add(0, _, 7))));
+        assert!(matches!(parse("commit")?, Some(Op::Commit)));
+        assert!(parse("")?.is_none());
+        assert!(parse("get 2 00").is_err());
+        assert!(parse("get 0").is_err());
+        assert!(parse("get 0 0g").is_err());
+        assert!(parse("frob 0 00").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parses_csv_lines() -> anyhow::Result<()> {
+        let cli = Cli::for_test(&[]);
+        assert!(parse_csv(&cli, "op,key,value_size", 0)?.is_none());
+        assert!(
+            matches!(parse_csv(&cli, "read,user1", 1)?, Some(Op::Get(0, key)) if key == b"user1")
+        );
+        let Some(Op::Put(0, _, value)) = parse_csv(&cli, "INSERT, user2, 100", 2)? else {
+            anyhow::bail!("Not a put");
+        };
+        assert_eq!(value.len(), 100);
+        assert!(matches!(
+            parse_csv(&cli, "delete,user1", 3)?,
+            Some(Op::Delete(..))
+        ));
+        assert!(parse_csv(&cli, "update,user1", 4).is_err());
+        assert!(parse_csv(&cli, "op,key,value_size", 5).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parses_ycsb_lines() -> anyhow::Result<()> {
+        let cli = Cli::for_test(&[]);
+        let parse = |line| parse_ycsb(&cli, line);
+        assert!(matches!(
+            parse("READ usertable user1 [ <all fields>]")?,
+            Some(Op::Get(..))
+        ));
+        let Some(Op::Put(_, key, value)) = parse("INSERT usertable user2 [ field0=abc ]")? else {
+            anyhow::bail!("Not a put");
+        };
+        assert_eq!(key, b"user2");
+        assert_eq!(value.len(), "field0=abc".len());
+        let Some(Op::Put(_, _, value)) = parse("UPDATE usertable user2 [ <all fields>]")? else {
+            anyhow::bail!("Not a put");
+        };
+        assert_eq!(value.len(), YCSB_VALUE_SZ);
+        assert!(matches!(
+            parse("SCAN usertable user1 10 [ <all fields>]")?,
+            Some(Op::Scan(_, _, 10))
+        ));
+        assert!(parse("[OVERALL], RunTime(ms), 1234")?.is_none());
+        assert!(parse("SCAN usertable user1").is_err());
+        Ok(())
+    }
+}
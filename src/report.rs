@@ -0,0 +1,384 @@
+//! Reports of the per-batch results written with `--output`, as HTML with inline SVG charts or as
+//! Markdown with the charts in SVG files next to it. Several results files are drawn over each
+//! other, labeled with their file names, followed by the environments they were measured in.
+
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+const WIDTH: f64 = 720.0;
+const HEIGHT: f64 = 280.0;
+/// The space left of, right of, above and below the plot area.
+const MARGINS: (f64, f64, f64, f64) = (70.0, 20.0, 30.0, 40.0);
+const COLORS: &[&str] = &[
+    "#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f",
+];
+
+/// The fields of a batch the report needs, in the order of `Batch`.
+const FIELDS: [&str; 5] = [
+    "timestamp_ms",
+    "items",
+    "batch_lat_us",
+    "commit_lat_us",
+    "db_size",
+];
+
+/// A batch of a results file.
+struct Batch {
+    /// Since the first batch of the file.
+    secs: f64,
+    items: u64,
+    batch_lat_us: u64,
+    commit_lat_us: u64,
+    db_size: u64,
+}
+
+struct Results {
+    name: String,
+    batches: Vec<Batch>,
+    env: Vec<(String, String)>,
+}
+
+struct Series {
+    name: String,
+    points: Vec<(f64, f64)>,
+}
+
+/// Writes the report of the results files to `out`, Markdown if it ends with `.md`.
+pub fn write(results: &[PathBuf], out: &Path, bucket_secs: u64) -> anyhow::Result<()> {
+    if results.is_empty() || bucket_secs == 0 {
+        anyhow::bail!("A report needs results files and a bucket of at least a second");
+    }
+    let results = results
+        .iter()
+        .map(|path| load(path))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let bucket = bucket_secs as f64;
+
+    let series = |value: &dyn Fn(&[&Batch]) -> f64| -> Vec<Series> {
+        results
+            .iter()
+            .map(|results| Series {
+                name: results.name.clone(),
+                points: buckets(&results.batches, bucket)
+                    .into_iter()
+                    .map(|(start, batches)| (start, value(&batches)))
+                    .collect(),
+            })
+            .collect()
+    };
+    let commit = |batches: &[&Batch], q: f64| {
+        percentile(batches.iter().map(|b| b.commit_lat_us).collect(), q) as f64
+    };
+    let mut latencies = series(&|batches| commit(batches, 0.5));
+    for series in &mut latencies {
+        series.name += " p50";
+    }
+    for mut p99 in series(&|batches| commit(batches, 0.99)) {
+        p99.name += " p99";
+        latencies.push(p99);
+    }
+    let charts = [
+        (
+            "throughput",
+            chart(
+                "Throughput",
+                "items/s",
+                &series(&|batches| batches.iter().map(|b| b.items).sum::<u64>() as f64 / bucket),
+            ),
+        ),
+        ("commit", chart("Commit latency", "us", &latencies)),
+        (
+            "size",
+            chart(
+                "Database size",
+                "MiB",
+                &series(&|batches| {
+                    batches
+                        .last()
+                        .map_or(0.0, |b| b.db_size as f64 / (1 << 20) as f64)
+                }),
+            ),
+        ),
+    ];
+
+    let mut rows = vec![vec![
+        "results".to_string(),
+        "batches".to_string(),
+        "items/s".to_string(),
+        "batch p50 us".to_string(),
+        "batch p99 us".to_string(),
+        "commit p50 us".to_string(),
+        "commit p99 us".to_string(),
+        "commit p999 us".to_string(),
+        "commit max us".to_string(),
+    ]];
+    for results in &results {
+        let batches = &results.batches;
+        let secs = batches.last().map_or(0.0, |b| b.secs).max(1e-3);
+        let items: u64 = batches.iter().map(|b| b.items).sum();
+        let batch_lats: Vec<u64> = batches.iter().map(|b| b.batch_lat_us).collect();
+        let commit_lats: Vec<u64> = batches.iter().map(|b| b.commit_lat_us).collect();
+        rows.push(vec![
+            results.name.clone(),
+            batches.len().to_string(),
+            format!("{:.1}", items as f64 / secs),
+            percentile(batch_lats.clone(), 0.5).to_string(),
+            percentile(batch_lats, 0.99).to_string(),
+            percentile(commit_lats.clone(), 0.5).to_string(),
+            percentile(commit_lats.clone(), 0.99).to_string(),
+            percentile(commit_lats.clone(), 0.999).to_string(),
+            percentile(commit_lats, 1.0).to_string(),
+        ]);
+    }
+
+    // A row per name in the environment of any results file, in the order of the first one.
+    let mut env_rows = vec![std::iter::once("environment".to_string())
+        .chain(results.iter().map(|results| results.name.clone()))
+        .collect::<Vec<_>>()];
+    for (name, _) in results.iter().flat_map(|results| &results.env) {
+        if env_rows.iter().any(|row| row[0] == *name) {
+            continue;
+        }
+        let values = results.iter().map(|results| {
+            let value = results.env.iter().find(|(n, _)| n == name);
+            value.map_or(String::new(), |(_, value)| value.clone())
+        });
+        env_rows.push(std::iter::once(name.clone()).chain(values).collect());
+    }
+
+    let title = results
+        .iter()
+        .map(|results| results.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut report = String::new();
+    if out.extension().is_some_and(|ext| ext == "md") {
+        writeln!(report, "# {}\n", title)?;
+        markdown_table(&mut report, &rows)?;
+        for (name, svg) in charts {
+            let path = out.with_extension(format!("{}.svg", name));
+            std::fs::write(&path, svg)?;
+            let file = path.file_name().unwrap_or_default().to_string_lossy();
+            writeln!(report, "\n![{}]({})", name, file)?;
+        }
+        if env_rows.len() > 1 {
+            writeln!(report)?;
+            markdown_table(&mut report, &env_rows)?;
+        }
+    } else {
+        writeln!(
+            report,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+             <style>body {{ font-family: sans-serif; max-width: 760px; margin: auto; }} \
+             table {{ border-collapse: collapse; }} \
+             td, th {{ border: 1px solid #ccc; padding: 4px 8px; text-align: right; }}</style>\n\
+             </head>\n<body>\n<h1>{}</h1>\n<table>",
+            escape(&title),
+            escape(&title)
+        )?;
+        html_rows(&mut report, &rows)?;
+        writeln!(report, "</table>")?;
+        for (_, svg) in charts {
+            writeln!(report, "<p>{}</p>", svg)?;
+        }
+        if env_rows.len() > 1 {
+            writeln!(report, "<table>")?;
+            html_rows(&mut report, &env_rows)?;
+            writeln!(report, "</table>")?;
+        }
+        writeln!(report, "</body>\n</html>")?;
+    }
+    std::fs::write(out, report)?;
+    println!("Wrote the report to {}", out.display());
+    Ok(())
+}
+
+/// Writes a table, the first row as the header.
+fn markdown_table(report: &mut String, rows: &[Vec<String>]) -> std::fmt::Result {
+    for (i, row) in rows.iter().enumerate() {
+        writeln!(report, "| {} |", row.join(" | "))?;
+        if i == 0 {
+            writeln!(report, "|{}", "---|".repeat(row.len()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the rows of a table, the first one as the header.
+fn html_rows(report: &mut String, rows: &[Vec<String>]) -> std::fmt::Result {
+    for (i, row) in rows.iter().enumerate() {
+        let cell = if i == 0 { "th" } else { "td" };
+        let cells: Vec<String> = row
+            .iter()
+            .map(|value| format!("<{}>{}</{}>", cell, escape(value), cell))
+            .collect();
+        writeln!(report, "<tr>{}</tr>", cells.concat())?;
+    }
+    Ok(())
+}
+
+/// Reads a results file, JSON lines or CSV by its first byte, along with its environment.
+fn load(path: &Path) -> anyhow::Result<Results> {
+    let content = std::fs::read_to_string(path)?;
+    let mut env = Vec::new();
+    let lines = content.lines().filter(|line| !line.trim().is_empty());
+    let mut rows: Vec<[u64; 5]> = Vec::new();
+    if content.starts_with('{') {
+        for line in lines {
+            if line.starts_with("{\"env\":") {
+                env = crate::ledger::object(line, "env");
+                continue;
+            }
+            let mut row = [0; 5];
+            for (value, field) in row.iter_mut().zip(FIELDS) {
+                *value = json_field(line, field)?;
+            }
+            rows.push(row);
+        }
+    } else {
+        let mut lines = lines.skip_while(|line| {
+            let comment = line.strip_prefix("# ");
+            if let Some((name, value)) = comment.and_then(|comment| comment.split_once(": ")) {
+                env.push((name.to_string(), value.to_string()));
+            }
+            comment.is_some()
+        });
+        let Some(header) = lines.next() else {
+            anyhow::bail!("{} is empty", path.display());
+        };
+        let columns: Vec<&str> = header.split(',').collect();
+        let mut indices = [0; 5];
+        for (index, field) in indices.iter_mut().zip(FIELDS) {
+            let Some(i) = columns.iter().position(|column| *column == field) else {
+                anyhow::bail!("{} has no {} column", path.display(), field);
+            };
+            *index = i;
+        }
+        for line in lines {
+            let cells: Vec<&str> = line.split(',').collect();
+            let mut row = [0; 5];
+            for (value, &i) in row.iter_mut().zip(&indices) {
+                *value = cells.get(i).copied().unwrap_or_default().parse()?;
+            }
+            rows.push(row);
+        }
+    }
+    let start = rows.first().map_or(0, |row| row[0]);
+    let batches = rows
+        .into_iter()
+        .map(
+            |[timestamp, items, batch_lat_us, commit_lat_us, db_size]| Batch {
+                secs: timestamp.saturating_sub(start) as f64 / 1000.0,
+                items,
+                batch_lat_us,
+                commit_lat_us,
+                db_size,
+            },
+        )
+        .collect();
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+    Ok(Results {
+        name: name.into_owned(),
+        batches,
+        env,
+    })
+}
+
+/// Extracts a number from a line written by `Output`, which has no nesting before the engine
+/// counters.
+fn json_field(line: &str, field: &str) -> anyhow::Result<u64> {
+    let Some((_, rest)) = line.split_once(&format!("\"{}\":", field)) else {
+        anyhow::bail!("No {} in {}", field, line);
+    };
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    Ok(rest[..end].parse()?)
+}
+
+/// Groups the batches by the bucket they finished in, keyed by the start of the bucket.
+fn buckets(batches: &[Batch], bucket: f64) -> Vec<(f64, Vec<&Batch>)> {
+    let mut buckets: Vec<(f64, Vec<&Batch>)> = Vec::new();
+    for batch in batches {
+        let start = (batch.secs / bucket).floor() * bucket;
+        match buckets.last_mut() {
+            Some((last, batches)) if *last == start => batches.push(batch),
+            _ => buckets.push((start, vec![batch])),
+        }
+    }
+    buckets
+}
+
+fn percentile(mut values: Vec<u64>, q: f64) -> u64 {
+    if values.is_empty() {
+        return 0;
+    }
+    values.sort_unstable();
+    values[((values.len() - 1) as f64 * q).round() as usize]
+}
+
+/// Draws the series as lines over the seconds since the start.
+fn chart(title: &str, unit: &str, series: &[Series]) -> String {
+    let (left, right, top, bottom) = MARGINS;
+    let (plot_w, plot_h) = (WIDTH - left - right, HEIGHT - top - bottom);
+    let points = series.iter().flat_map(|series| &series.points);
+    let max_x = points.clone().map(|p| p.0).fold(0.0, f64::max).max(1.0);
+    let max_y = points.map(|p| p.1).fold(0.0, f64::max).max(1.0);
+    let x = |v: f64| left + v / max_x * plot_w;
+    let y = |v: f64| top + plot_h - v / max_y * plot_h;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+         font-family=\"sans-serif\" font-size=\"11\">\n\
+         <text x=\"{}\" y=\"18\" font-size=\"14\">{} ({})</text>\n",
+        WIDTH,
+        HEIGHT,
+        left,
+        escape(title),
+        unit
+    );
+    for tick in 0..=4 {
+        let (vx, vy) = (max_x * tick as f64 / 4.0, max_y * tick as f64 / 4.0);
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{l}\" y1=\"{py:.1}\" x2=\"{r}\" y2=\"{py:.1}\" stroke=\"#eee\"/>\
+             <text x=\"{tx}\" y=\"{py:.1}\" text-anchor=\"end\" dy=\"4\">{vy:.0}</text>\
+             <text x=\"{px:.1}\" y=\"{by}\" text-anchor=\"middle\">{vx:.0}s</text>",
+            l = left,
+            r = WIDTH - right,
+            py = y(vy),
+            tx = left - 6.0,
+            px = x(vx),
+            by = HEIGHT - bottom + 16.0,
+        );
+    }
+    for (i, series) in series.iter().enumerate() {
+        let color = COLORS[i % COLORS.len()];
+        let path: Vec<String> = series
+            .points
+            .iter()
+            .map(|&(px, py)| format!("{:.1},{:.1}", x(px), y(py)))
+            .collect();
+        let _ = writeln!(
+            svg,
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\" points=\"{}\"/>\
+             <text x=\"{}\" y=\"{}\" fill=\"{}\">{}</text>",
+            color,
+            path.join(" "),
+            left + 10.0,
+            top + 14.0 * (i + 1) as f64,
+            color,
+            escape(&series.name)
+        );
+    }
+    svg + "</svg>"
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
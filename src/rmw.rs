@@ -0,0 +1,131 @@
+//! Read-modify-write: every transaction reads counters and writes them back incremented.
+//!
+//! Engines that serialize write transactions never lose an increment. Engines whose transactions
+//! are only write batches, like rocksdb, lose the increments of concurrent writers that read the
+//! same value, which shows up as lost updates at the end. With `--merge` the increments are
+//! blind adds instead, which rocksdb resolves with a merge operator and the others still read.
+
+use crate::{backend, interrupt, stats::Stats, Cli, RmwOpts};
+use rand::Rng;
+use std::{sync::Mutex, time::Instant};
+
+/// The stream of the first worker, distinct from the ones of the fill workers.
+const STREAM: u128 = 3 << 64;
+
+pub fn run(cli: &Cli, opts: &RmwOpts) -> anyhow::Result<()> {
+    if opts.threads == 0 || opts.batch_sz == 0 || opts.counters == 0 {
+        anyhow::bail!("Invalid read-modify-write options: {:?}", opts);
+    }
+    crate::check_existing(cli)?;
+    interrupt::install();
+
+    tracing::info!(?cli, "Opening database");
+    let env = backend::open(cli)?;
+    let before = sum(cli, &*env, opts)?;
+
+    let mut stats = cli.stats()?;
+    stats.set_total(opts.n);
+    let stats = Mutex::new(stats);
+    let increments = std::thread::scope(|s| {
+        let workers: Vec<_> = (0..opts.threads)
+            .map(|worker| {
+                let n = opts.n / opts.threads + usize::from(worker < opts.n % opts.threads);
+                let (env, stats) = (&*env, &stats);
+                s.spawn(move || worker_loop(env, cli, opts, worker, n, stats))
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("read-modify-write worker panicked"))
+            .sum::<anyhow::Result<u64>>()
+    })?;
+
+    let after = sum(cli, &*env, opts)?;
+    let applied = after.saturating_sub(before);
+    println!(
+        "{} increments committed, {} applied, {} lost to concurrent writers",
+        increments,
+        applied,
+        increments.saturating_sub(applied)
+    );
+    stats.into_inner().unwrap().done();
+    print!("{}", env.compaction_report()?);
+    Ok(())
+}
+
+/// Returns the key of a counter, its index big-endian in a key of `--key-sz` bytes.
+pub fn counter_key(cli: &Cli, counter: usize) -> Vec<u8> {
+    let mut key = vec![0; cli.key_sz.max(8)];
+    let start = key.len() - 8;
+    key[start..].copy_from_slice(&(counter as u64).to_be_bytes());
+    key
+}
+
+/// Returns the sum of all counters.
+fn sum(cli: &Cli, env: &dyn backend::Engine, opts: &RmwOpts) -> anyhow::Result<u64> {
+    let txn = env.begin_read()?;
+    let mut sum = 0;
+    for counter in 0..opts.counters {
+        let key = counter_key(cli, counter);
+        sum += backend::decode_counter(txn.get(cli.table(&key), &key)?.as_deref())?;
+    }
+    Ok(sum)
+}
+
+/// Runs `n` increments and returns the number that were committed.
+fn worker_loop(
+    env: &dyn backend::Engine,
+    cli: &Cli,
+    opts: &RmwOpts,
+    worker: usize,
+    n: usize,
+    stats: &Mutex<Stats>,
+) -> anyhow::Result<u64> {
+    let mut rand = cli.rng(STREAM + worker as u128);
+    let deadline = cli.deadline();
+    let mut limiter = cli.limiter(opts.threads);
+    let mut lats = Vec::new();
+    let mut committed = 0;
+    let mut remaining = n;
+    while remaining > 0 && !deadline.passed() && !interrupt::interrupted() {
+        let batch_sz = opts.batch_sz.min(remaining);
+        let start = Instant::now();
+        let mut txn = env.begin()?;
+        for _ in 0..batch_sz {
+            let intended = limiter.wait();
+            let key = counter_key(cli, rand.gen_range(0..opts.counters));
+            let table = cli.table(&key);
+            if opts.merge {
+                let merge_start = intended.unwrap_or_else(Instant::now);
+                txn.add(table, &key, 1)?;
+                lats.push(("add", merge_start.elapsed()));
+                continue;
+            }
+            let read_start = intended.unwrap_or_else(Instant::now);
+            let value = backend::decode_counter(txn.get(table, &key)?.as_deref())?;
+            lats.push(("read", read_start.elapsed()));
+            let write_start = Instant::now();
+            txn.put(table, &key, &(value + 1).to_le_bytes())?;
+            lats.push(("write", write_start.elapsed()));
+        }
+        let commit_start = Instant::now();
+        txn.commit()?;
+        let commit_lat = commit_start.elapsed();
+        let sync_lat = crate::after_commit(cli, env, commit_lat)?;
+        remaining -= batch_sz;
+        committed += batch_sz as u64;
+
+        let mut stats = stats.lock().unwrap();
+        for (name, lat) in lats.drain(..) {
+            stats.record(name, lat);
+        }
+        stats.record("commit", commit_lat);
+        if let Some(sync_lat) = sync_lat {
+            stats.record("sync", sync_lat);
+        }
+        stats.record("txn", start.elapsed());
+        stats.add_ops(batch_sz);
+        stats.maybe_report();
+    }
+    Ok(committed)
+}
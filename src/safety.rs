@@ -0,0 +1,101 @@
+//! Checks of the filesystem the database goes to, before anything is opened: that it is not tmpfs,
+//! has room for the workload, and whether it is on a network filesystem or a spinning disk.
+
+use crate::{backend::EngineKind, fingerprint, units::ByteSize, Cli, SubCommand, GIGABYTE};
+use std::{ffi::CString, os::unix::ffi::OsStrExt, path::Path};
+
+/// The filesystems that are reached over the network, by their name in `/proc/self/mountinfo`.
+const NETWORK_FILESYSTEMS: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "ceph",
+    "glusterfs",
+    "9p",
+    "afs",
+    "fuse.sshfs",
+];
+
+pub fn check(cli: &Cli) -> anyhow::Result<()> {
+    let path = Path::new(&cli.path);
+    // The database may not exist yet, then it goes to the filesystem of the closest parent.
+    let absolute = std::env::current_dir()?.join(path);
+    let Some(existing) = absolute.ancestors().find(|p| p.exists()) else {
+        return Ok(());
+    };
+    let existing = existing.canonicalize()?;
+    let (point, fs) = fingerprint::mount(&existing)
+        .map(|mount| (mount.point, mount.fs))
+        .unwrap_or_default();
+    let free = free_space(&existing)?;
+    tracing::info!(path = %cli.path, mount = %point, fs = %fs, free, "Target filesystem");
+
+    if fs == "tmpfs" && !cli.allow_tmpfs {
+        anyhow::bail!(
+            "{} is on tmpfs, which keeps it in memory. Pass --allow-tmpfs to run there anyway.",
+            cli.path
+        );
+    }
+    if NETWORK_FILESYSTEMS.contains(&fs.as_str()) {
+        tracing::warn!("{} is on the network filesystem {}", cli.path, fs);
+    }
+    if !path.exists() && point == "/" {
+        tracing::warn!(
+            "{} doesn't exist and will be created on the root filesystem",
+            cli.path
+        );
+    }
+    let rotational = fingerprint::disk(&existing)
+        .and_then(|disk| std::fs::read_to_string(disk.join("queue/rotational")).ok());
+    if rotational.is_some_and(|rotational| rotational.trim() == "1") {
+        tracing::warn!("{} is on a rotational device", cli.path);
+    }
+
+    if let Some(required) = required_space(cli) {
+        if free < required && !cli.allow_low_space {
+            anyhow::bail!(
+                "{} has {} MiB free, but the run needs about {} MiB. Pass --allow-low-space to \
+                 run anyway.",
+                point,
+                free >> 20,
+                required >> 20
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Returns the bytes a fill writes, its items at the mean value size or the target of `--fill-to`,
+/// whichever is less, and at least the first growth of an mdbx map. The memory engine needs none.
+fn required_space(cli: &Cli) -> Option<u64> {
+    let SubCommand::Fill(opts) = &cli.subcmd else {
+        return None;
+    };
+    if let EngineKind::Memory = cli.kind {
+        return None;
+    }
+    let items = opts
+        .n
+        .map(|n| (n as f64 * (cli.key_sz as f64 + opts.value_dist.mean())) as u64);
+    let workload = match (items, opts.fill_to) {
+        (Some(items), Some(target)) => items.min(target.0),
+        (Some(bytes), None) | (None, Some(ByteSize(bytes))) => bytes,
+        (None, None) => return None,
+    };
+    let growth = match cli.kind {
+        EngineKind::Mdbx => cli.mdbx.growth_step.unwrap_or(GIGABYTE) as u64,
+        _ => 0,
+    };
+    Some(workload.max(growth))
+}
+
+/// Returns the bytes available to unprivileged users on the filesystem of `path`.
+fn free_space(path: &Path) -> anyhow::Result<u64> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
@@ -0,0 +1,571 @@
+//! Latency statistics collected during a run.
+
+use crate::{
+    backend::{Engine, GcStat},
+    output::Output,
+    sysio::IoSampler,
+    units::Span,
+};
+use hdrhistogram::Histogram;
+use std::{
+    collections::VecDeque,
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// The final report of every workload run by this process, for `compare`.
+static SUMMARIES: Mutex<Vec<Summary>> = Mutex::new(Vec::new());
+
+/// The throughput and the latency percentiles of a final report.
+#[derive(Debug)]
+pub struct Summary {
+    pub ops: u64,
+    pub elapsed: Duration,
+    /// The p50, p99 and p999 of every histogram.
+    pub latencies: Vec<(&'static str, [Duration; 3])>,
+}
+
+/// Returns the summaries of the workloads that finished since the last call.
+pub fn take_summaries() -> Vec<Summary> {
+    std::mem::take(&mut SUMMARIES.lock().unwrap())
+}
+
+/// The start of a run that is executed but left out of the statistics, while caches are cold and
+/// the database has not grown yet.
+#[derive(Debug, Copy, Clone)]
+pub enum Warmup {
+    /// A number of operations, a plain number.
+    Ops(u64),
+    /// A duration with a unit, like `30s`.
+    Time(Duration),
+}
+
+impl FromStr for Warmup {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.chars().all(|c| c.is_ascii_digit()) {
+            return Ok(Warmup::Ops(s.parse()?));
+        }
+        let Span(duration) = s.parse()?;
+        Ok(Warmup::Time(duration))
+    }
+}
+
+/// Named latency histograms plus a counter of completed operations.
+///
+/// Histograms are created on the first sample recorded under a name and reported in that order.
+pub struct Stats {
+    start: Instant,
+    last_report: Instant,
+    interval: Duration,
+    ops: Arc<AtomicU64>,
+    /// The number of operations of the whole run, 0 if unknown.
+    total: Arc<AtomicU64>,
+    /// The operations of the warm-up, left out of the throughput.
+    ops_before: u64,
+    warmup: Option<Warmup>,
+    // Only held to stop the thread on drop.
+    _reporter: Option<Reporter>,
+    latencies: Vec<(&'static str, Histogram<u64>)>,
+    output: Option<Output>,
+    /// The key and value bytes written so far, against which the size on disk is compared.
+    written: u64,
+    /// The part of `written` by the run that was resumed.
+    written_before: u64,
+    db_path: PathBuf,
+    io: Option<IoSampler>,
+    /// The size on disk at the start, to tell how much the files grew.
+    start_size: u64,
+    /// What the engine says it wrote, sampled at most once per second.
+    engine_written: Option<u64>,
+    engine_sampled: Option<Instant>,
+    /// What the engine says it uses, sampled along with `engine_written`.
+    used: Option<u64>,
+    published: Instant,
+    /// The last map size of every engine, by its address, as there may be shards.
+    map_sizes: Vec<(usize, u64)>,
+    /// How often a map grew during a batch.
+    growths: u64,
+    /// The free pages at the start and the latest, sampled along with `engine_written`.
+    gc: Option<(GcStat, GcStat)>,
+}
+
+impl Stats {
+    pub fn new(interval: Duration, db_path: &Path) -> Self {
+        let now = Instant::now();
+        let ops = Arc::new(AtomicU64::new(0));
+        crate::metrics::register(ops.clone(), db_path.to_path_buf());
+        Stats {
+            start: now,
+            last_report: now,
+            interval,
+            ops,
+            total: Arc::new(AtomicU64::new(0)),
+            ops_before: 0,
+            warmup: None,
+            _reporter: None,
+            latencies: Vec::new(),
+            output: None,
+            written: 0,
+            written_before: 0,
+            db_path: db_path.to_path_buf(),
+            io: IoSampler::new(db_path),
+            start_size: crate::db_size(db_path).unwrap_or(0),
+            engine_written: None,
+            engine_sampled: None,
+            used: None,
+            published: now,
+            map_sizes: Vec::new(),
+            growths: 0,
+            gc: None,
+        }
+    }
+
+    /// Prints the throughput from a background thread every `interval`, so that there is output
+    /// even while a huge batch is being filled or committed. Once the total is known, a terminal
+    /// gets a progress bar instead.
+    pub fn with_reporter(mut self, interval: Duration) -> Self {
+        self._reporter = Some(Reporter::spawn(
+            self.ops.clone(),
+            self.total.clone(),
+            self.db_path.clone(),
+            interval,
+        ));
+        self
+    }
+
+    /// Leaves the start of the run out of the statistics.
+    pub fn with_warmup(mut self, warmup: Option<Warmup>) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Restarts the statistics once the warm-up is over.
+    fn check_warmup(&mut self) {
+        let ops = self.ops.load(Ordering::Relaxed);
+        let over = match self.warmup {
+            None => return,
+            Some(Warmup::Ops(n)) => ops >= n,
+            Some(Warmup::Time(duration)) => self.start.elapsed() >= duration,
+        };
+        if over {
+            tracing::info!(ops, elapsed = ?self.start.elapsed(), "Warm-up over");
+            self.warmup = None;
+            self.ops_before = ops;
+            self.start = Instant::now();
+            self.latencies.clear();
+            self.io = IoSampler::new(&self.db_path);
+        }
+    }
+
+    /// Sets the number of operations of the whole run, for the progress and the ETA.
+    pub fn set_total(&self, total: usize) {
+        self.total.store(total as u64, Ordering::Relaxed);
+    }
+
+    /// Continues a run that already did `ops` operations writing `written` bytes in `elapsed`, so
+    /// the throughput and the ETA cover both.
+    pub fn resume(&mut self, ops: usize, written: u64, elapsed: Duration) {
+        self.ops.store(ops as u64, Ordering::Relaxed);
+        self.written = written;
+        self.written_before = written;
+        self.start = Instant::now().checked_sub(elapsed).unwrap_or(self.start);
+    }
+
+    /// The time since the start, including the one of a resumed run.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Returns how much the files grew, the engine wrote and the process wrote per byte of keys
+    /// and values written by this run.
+    fn write_amplification(&self) -> Option<String> {
+        let written = self.written - self.written_before;
+        if written == 0 {
+            return None;
+        }
+        let amp = |bytes: u64| bytes as f64 / written as f64;
+        let size = crate::db_size(&self.db_path).ok()?;
+        let mut wamp = format!(
+            "{} bytes written, files grew {:.2}x",
+            written,
+            amp(size.saturating_sub(self.start_size))
+        );
+        if let Some(engine) = self.engine_written {
+            wamp += &format!(", engine wrote {:.2}x", amp(engine));
+        }
+        if let Some(Ok(process)) = self.io.as_ref().map(IoSampler::process_written) {
+            wamp += &format!(", process wrote {:.2}x", amp(process));
+        }
+        Some(wamp)
+    }
+
+    /// Also write every batch recorded with [`Stats::record_batch`] to the given output.
+    pub fn with_output(mut self, output: Output) -> Self {
+        self.output = Some(output);
+        self
+    }
+
+    /// Records a committed batch of `items` operations that wrote `bytes` of keys and values.
+    pub fn record_batch(
+        &mut self,
+        env: &dyn Engine,
+        items: usize,
+        bytes: usize,
+        batch_lat: Duration,
+        commit_lat: Duration,
+    ) -> anyhow::Result<()> {
+        self.record("batch", batch_lat);
+        self.record("commit", commit_lat);
+        self.written += bytes as u64;
+        let info = crate::backend::last_commit_info();
+        tracing::debug!(
+            items,
+            bytes,
+            ?batch_lat,
+            ?commit_lat,
+            engine = ?info,
+            "Committed a batch"
+        );
+        let due = match self.engine_sampled {
+            Some(sampled) => sampled.elapsed() >= Duration::from_secs(1),
+            None => true,
+        };
+        if due {
+            self.engine_written = env.engine_written()?;
+            self.used = env.used_bytes()?;
+            if let Some(gc) = env.gc_stat()? {
+                let start = self.gc.map_or(gc, |(start, _)| start);
+                self.gc = Some((start, gc));
+            }
+            self.engine_sampled = Some(Instant::now());
+        }
+        self.check_growth(env, batch_lat, commit_lat)?;
+        if let Some(output) = &mut self.output {
+            let lats = (batch_lat, commit_lat);
+            output.write_batch(items, self.written, self.used, lats, &info)?;
+        }
+        Ok(())
+    }
+
+    /// Logs every growth of the map along with the latency of the batch that caused it, and
+    /// records those latencies apart, to tell whether growth steps are behind commit spikes.
+    fn check_growth(
+        &mut self,
+        env: &dyn Engine,
+        batch_lat: Duration,
+        commit_lat: Duration,
+    ) -> anyhow::Result<()> {
+        let Some(size) = env.map_size()? else {
+            return Ok(());
+        };
+        let id = env as *const dyn Engine as *const () as usize;
+        let Some(idx) = self.map_sizes.iter().position(|(i, _)| *i == id) else {
+            self.map_sizes.push((id, size));
+            return Ok(());
+        };
+        let before = std::mem::replace(&mut self.map_sizes[idx].1, size);
+        if size > before {
+            self.growths += 1;
+            tracing::info!(
+                from = before,
+                to = size,
+                ?batch_lat,
+                ?commit_lat,
+                "Map grew"
+            );
+            self.record("growth batch", batch_lat);
+            self.record("growth commit", commit_lat);
+        }
+        Ok(())
+    }
+
+    pub fn record(&mut self, name: &'static str, lat: Duration) {
+        self.check_warmup();
+        if self.warmup.is_some() {
+            return;
+        }
+        let idx = match self.latencies.iter().position(|(n, _)| *n == name) {
+            Some(idx) => idx,
+            None => {
+                // Auto-resizing histogram with 3 significant digits.
+                let hist = Histogram::new(3).expect("3 significant digits are supported");
+                self.latencies.push((name, hist));
+                self.latencies.len() - 1
+            }
+        };
+        self.latencies[idx]
+            .1
+            .saturating_record(lat.as_nanos().min(u64::MAX as u128) as u64);
+    }
+
+    /// Returns a handle to count operations from other threads.
+    pub fn counter(&self) -> OpCounter {
+        OpCounter(self.ops.clone())
+    }
+
+    /// Counts completed operations. Call this per operation rather than per batch, the
+    /// throughput reporter relies on it.
+    pub fn add_ops(&mut self, ops: usize) {
+        self.ops.fetch_add(ops as u64, Ordering::Relaxed);
+    }
+
+    /// Prints an intermediate report if the report interval elapsed since the previous one.
+    pub fn maybe_report(&mut self) {
+        if self.published.elapsed() >= Duration::from_secs(1) {
+            self.publish();
+        }
+        if self.last_report.elapsed() >= self.interval {
+            self.report("Progress", false);
+            self.last_report = Instant::now();
+        }
+    }
+
+    /// Hands a copy of the statistics to the metrics endpoint.
+    fn publish(&mut self) {
+        let mut gauges = vec![("written_bytes", self.written)];
+        if let Some(used) = self.used {
+            gauges.push(("engine_used_bytes", used));
+        }
+        if let Some(engine_written) = self.engine_written {
+            gauges.push(("engine_written_bytes", engine_written));
+        }
+        crate::metrics::publish(&self.latencies, gauges);
+        self.published = Instant::now();
+    }
+
+    /// Prints the final report and keeps its summary.
+    pub fn done(&self) {
+        self.report("Done", true);
+        let q = |hist: &Histogram<u64>, q: f64| Duration::from_nanos(hist.value_at_quantile(q));
+        let summary = Summary {
+            ops: self.ops.load(Ordering::Relaxed) - self.ops_before,
+            elapsed: self.start.elapsed(),
+            latencies: self
+                .latencies
+                .iter()
+                .map(|(name, hist)| (*name, [q(hist, 0.5), q(hist, 0.99), q(hist, 0.999)]))
+                .collect(),
+        };
+        SUMMARIES.lock().unwrap().push(summary);
+    }
+
+    /// Prints the throughput and the percentiles of every histogram, all since the start. Only
+    /// the `last` report samples how much of the database is cached.
+    pub fn report(&self, label: &str, last: bool) {
+        let elapsed = self.start.elapsed();
+        let ops = self.ops.load(Ordering::Relaxed) - self.ops_before;
+        println!(
+            "{}: {} ops in {:.1} s ({:.0} ops/s)",
+            label,
+            ops,
+            elapsed.as_secs_f64(),
+            ops as f64 / elapsed.as_secs_f64()
+        );
+        if self.written > 0 {
+            if let Ok(size) = crate::db_size(&self.db_path) {
+                println!(
+                    "  space    {} bytes on disk for {} bytes written ({:.2}x)",
+                    size,
+                    self.written,
+                    size as f64 / self.written as f64
+                );
+            }
+        }
+        if let Some(wamp) = self.write_amplification() {
+            println!("  wamp     {}", wamp);
+        }
+        if let Some((start, gc)) = &self.gc {
+            let (allocated, reclaimed) = gc.allocated_since(start);
+            println!(
+                "  gc       {} free pages, {} allocated ({} reclaimed), reader lag {} txns",
+                gc.free_pages, allocated, reclaimed, gc.reader_lag
+            );
+        }
+        if self.growths > 0 {
+            println!("  growth   the map grew {} times", self.growths);
+        }
+        if let Some(Ok(io)) = self.io.as_ref().map(|io| io.report(self.written)) {
+            println!("  io       {}", io);
+        }
+        if let Ok(mem) = crate::mem::report(&self.db_path, last) {
+            println!("  memory   {}", mem);
+        }
+        for (name, hist) in &self.latencies {
+            let q = |q: f64| Duration::from_nanos(hist.value_at_quantile(q));
+            println!(
+                "  {:<8} n={} p50={:?} p90={:?} p99={:?} p999={:?} max={:?}",
+                name,
+                hist.len(),
+                q(0.5),
+                q(0.9),
+                q(0.99),
+                q(0.999),
+                Duration::from_nanos(hist.max())
+            );
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct OpCounter(Arc<AtomicU64>);
+
+impl OpCounter {
+    pub fn add(&self, ops: usize) {
+        self.0.fetch_add(ops as u64, Ordering::Relaxed);
+    }
+}
+
+/// Picks the single operations inside batches whose latency is recorded, every `every`-th one.
+/// Timing every put of a big batch would cost more than some of the puts.
+pub struct OpSampler {
+    every: u64,
+    count: u64,
+}
+
+impl OpSampler {
+    /// Samples none if `every` is 0.
+    pub fn new(every: u64) -> Self {
+        OpSampler { every, count: 0 }
+    }
+
+    /// Returns the start time if the next operation is sampled.
+    pub fn start(&mut self) -> Option<Instant> {
+        if self.every == 0 {
+            return None;
+        }
+        self.count += 1;
+        if self.count < self.every {
+            return None;
+        }
+        self.count = 0;
+        Some(Instant::now())
+    }
+}
+
+/// A thread sampling the operation counter every second and printing the throughput over the
+/// last second, the last 10 seconds and the whole run. On a terminal with a known total it redraws
+/// a progress bar every second instead.
+struct Reporter {
+    stop: Option<mpsc::Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Reporter {
+    fn spawn(
+        ops: Arc<AtomicU64>,
+        total: Arc<AtomicU64>,
+        db_path: PathBuf,
+        interval: Duration,
+    ) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let tty = std::io::stderr().is_terminal();
+        let handle = std::thread::spawn(move || {
+            let mut drawn = false;
+            let start = Instant::now();
+            let mut last_print = start;
+            let mut samples = VecDeque::from([(start, 0)]);
+            // Wakes up every second until the sender is dropped.
+            while let Err(mpsc::RecvTimeoutError::Timeout) =
+                stopped.recv_timeout(Duration::from_secs(1))
+            {
+                let now = Instant::now();
+                samples.push_back((now, ops.load(Ordering::Relaxed)));
+                if samples.len() > 11 {
+                    samples.pop_front();
+                }
+
+                let rate = |window: usize| {
+                    let (then, then_ops) = samples[samples.len().saturating_sub(window + 1)];
+                    let (now, now_ops) = samples[samples.len() - 1];
+                    (now_ops - then_ops) as f64 / (now - then).as_secs_f64()
+                };
+                let (_, done) = samples[samples.len() - 1];
+                let total = total.load(Ordering::Relaxed);
+                if tty && total > 0 {
+                    let size = crate::db_size(&db_path).unwrap_or(0);
+                    eprint!("\r{}", progress_bar(done, total, rate(10), size));
+                    let _ = std::io::stderr().flush();
+                    drawn = true;
+                    continue;
+                }
+
+                if now - last_print < interval {
+                    continue;
+                }
+                last_print = now;
+                let progress = match total {
+                    0 => String::new(),
+                    total => format!(
+                        ", {}/{} ({:.1}%), ETA {}",
+                        done,
+                        total,
+                        done as f64 * 100.0 / total as f64,
+                        eta(done, total, rate(10))
+                    ),
+                };
+                println!(
+                    "Throughput: 1s {:.0} ops/s, 10s {:.0} ops/s, run {:.0} ops/s{}",
+                    rate(1),
+                    rate(10),
+                    done as f64 / (now - start).as_secs_f64(),
+                    progress
+                );
+            }
+            if drawn {
+                eprintln!();
+            }
+        });
+        Reporter {
+            stop: Some(stop),
+            handle: Some(handle),
+        }
+    }
+}
+
+fn progress_bar(done: u64, total: u64, rate: f64, size: u64) -> String {
+    const WIDTH: usize = 30;
+    let fraction = (done as f64 / total as f64).min(1.0);
+    let filled = (fraction * WIDTH as f64) as usize;
+    format!(
+        "[{}{}] {}/{} {:.1}% {:.0} ops/s ETA {} {} bytes ",
+        "#".repeat(filled),
+        " ".repeat(WIDTH - filled),
+        done,
+        total,
+        fraction * 100.0,
+        rate,
+        eta(done, total, rate),
+        size
+    )
+}
+
+/// Formats the time until `total` is reached at `rate` ops/s as hours, minutes and seconds.
+fn eta(done: u64, total: u64, rate: f64) -> String {
+    if rate <= 0.0 {
+        return "unknown".to_string();
+    }
+    let secs = (total.saturating_sub(done) as f64 / rate) as u64;
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+impl Drop for Reporter {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Running a workload repeatedly with one parameter changed every step and reporting the results
+//! against it.
+
+use crate::{
+    backend::EngineKind, compare, stats, units::ByteSize, values::ValueDist, Cli, SubCommand,
+    SweepBatchOpts, SweepValueOpts,
+};
+
+/// The result of a step: its label, summary, the bytes on disk after it and, if known, the
+/// logical bytes it wrote.
+type Step = (String, stats::Summary, u64, Option<u64>);
+
+pub fn batch(cli: &Cli, opts: &SweepBatchOpts) -> anyhow::Result<()> {
+    if opts.sizes.is_empty() || opts.sizes.contains(&0) {
+        anyhow::bail!("Invalid batch sizes: {:?}", opts.sizes);
+    }
+    let mut steps = Vec::new();
+    for (i, &batch_sz) in opts.sizes.iter().enumerate() {
+        let mut workload = (*opts.workload).clone();
+        let Some(workload_batch_sz) = batch_sz_of(&mut workload) else {
+            anyhow::bail!(
+                "Only write workloads have a batch size: {:?}",
+                opts.workload
+            );
+        };
+        *workload_batch_sz = batch_sz;
+        tracing::info!(batch_sz, "Sweep step");
+        let mut step_cli = cli.clone();
+        step_cli.subcmd = workload;
+        let label = batch_sz.to_string();
+        let (summary, size) = step(&mut step_cli, &label, i, opts.reset)?;
+        steps.push((label, summary, size, None));
+    }
+    report("batch size", &steps);
+    Ok(())
+}
+
+/// Sweeps fixed value sizes, with as many items at every size as fit the total bytes.
+pub fn value(cli: &Cli, opts: &SweepValueOpts) -> anyhow::Result<()> {
+    if opts.sizes.is_empty() || opts.sizes.iter().any(|size| size.0 == 0) {
+        anyhow::bail!("Invalid value sizes: {:?}", opts.sizes);
+    }
+    let kinds: Vec<EngineKind> = std::iter::once(cli.kind)
+        .chain(opts.with.iter().copied())
+        .collect();
+    let mut steps = Vec::new();
+    for (i, &ByteSize(value_sz)) in opts.sizes.iter().enumerate() {
+        let item_sz = (cli.key_sz as u64 + value_sz).max(1);
+        let n = (opts.bytes.0 / item_sz).max(1) as usize;
+        let mut workload = (*opts.workload).clone();
+        let Some((workload_n, value_dist)) = items_of(&mut workload) else {
+            anyhow::bail!(
+                "Only write workloads have a value size: {:?}",
+                opts.workload
+            );
+        };
+        *workload_n = n;
+        *value_dist = ValueDist::Fixed(value_sz as usize);
+        for &kind in &kinds {
+            tracing::info!(value_sz, n, engine = compare::name(kind), "Sweep step");
+            let mut step_cli = cli.clone();
+            step_cli.subcmd = workload.clone();
+            let mut label = ByteSize(value_sz).to_string();
+            if !opts.with.is_empty() {
+                step_cli.kind = kind;
+                step_cli.path = cli.engine_path(kind);
+                label = format!("{} {}", label, compare::name(kind));
+            }
+            let (summary, size) = step(&mut step_cli, &label.replace(' ', "."), i, opts.reset)?;
+            steps.push((label, summary, size, Some(n as u64 * item_sz)));
+        }
+    }
+    report("value size", &steps);
+    Ok(())
+}
+
+fn batch_sz_of(workload: &mut SubCommand) -> Option<&mut usize> {
+    match workload {
+        SubCommand::Fill(opts) => Some(&mut opts.batch_sz),
+        SubCommand::FillSeq(opts) => Some(&mut opts.batch_sz),
+        SubCommand::Mixed(opts) => Some(&mut opts.batch_sz),
+        SubCommand::Churn(opts) => Some(&mut opts.batch_sz),
+        SubCommand::Update(opts) => Some(&mut opts.batch_sz),
+        SubCommand::Rmw(opts) => Some(&mut opts.batch_sz),
+        SubCommand::Expire(opts) => Some(&mut opts.batch_sz),
+        SubCommand::Dupsort(opts) => Some(&mut opts.batch_sz),
+        SubCommand::LargeValues(opts) => Some(&mut opts.batch_sz),
+        SubCommand::Nested(opts) => Some(&mut opts.batch_sz),
+        _ => None,
+    }
+}
+
+/// Returns the number of items of a write workload and the distribution of their value sizes.
+fn items_of(workload: &mut SubCommand) -> Option<(&mut usize, &mut ValueDist)> {
+    match workload {
+        SubCommand::Fill(opts) => {
+            opts.fill_to = None;
+            Some((opts.n.insert(0), &mut opts.value_dist))
+        }
+        SubCommand::FillSeq(opts) => Some((&mut opts.n, &mut opts.value_dist)),
+        SubCommand::Ingest(opts) => Some((&mut opts.n, &mut opts.value_dist)),
+        SubCommand::Churn(opts) => Some((&mut opts.n, &mut opts.value_dist)),
+        SubCommand::Update(opts) => Some((&mut opts.n, &mut opts.value_dist)),
+        SubCommand::Dupsort(opts) => Some((&mut opts.n, &mut opts.value_dist)),
+        SubCommand::LargeValues(opts) => Some((&mut opts.n, &mut opts.value_dist)),
+        SubCommand::Nested(opts) => Some((&mut opts.n, &mut opts.value_dist)),
+        SubCommand::Expire(opts) => Some((&mut opts.n, &mut opts.value_dist)),
+        _ => None,
+    }
+}
+
+/// Runs one step of a sweep and returns its summary and the bytes on disk after it. Steps after
+/// the first continue on the database of the previous one, unless it is reset.
+fn step(
+    step_cli: &mut Cli,
+    label: &str,
+    i: usize,
+    reset: bool,
+) -> anyhow::Result<(stats::Summary, u64)> {
+    step_cli.profile = step_cli.profile.as_ref().map(|p| p.for_engine(label));
+    if i > 0 {
+        if reset {
+            crate::remove_database(step_cli)?;
+        }
+        step_cli.y = false;
+        step_cli.cont = !reset;
+    }
+    crate::run(step_cli)?;
+    let Some(summary) = stats::take_summaries().pop() else {
+        anyhow::bail!("Step {} of the sweep produced no report", label);
+    };
+    Ok((summary, crate::db_size(step_cli.path.as_ref())?))
+}
+
+/// Prints a row per step, which is labeled with the swept value. With the logical bytes known
+/// also the write throughput and the space amplification.
+fn report(param: &str, steps: &[Step]) {
+    let commit = |summary: &stats::Summary, q: usize| {
+        summary
+            .latencies
+            .iter()
+            .find(|(name, _)| *name == "commit")
+            .map_or(f64::NAN, |(_, lats)| lats[q].as_secs_f64() * 1e6)
+    };
+    let logical = steps.iter().any(|(_, _, _, logical)| logical.is_some());
+    print!(
+        "{:>20}{:>16}{:>16}{:>16}{:>16}{:>20}",
+        param, "ops/s", "elapsed s", "commit p50 us", "commit p99 us", "bytes on disk"
+    );
+    if logical {
+        print!("{:>12}{:>12}", "MiB/s", "space amp");
+    }
+    println!();
+    for (value, summary, size, written) in steps {
+        let secs = summary.elapsed.as_secs_f64();
+        print!(
+            "{:>20}{:>16.1}{:>16.1}{:>16.1}{:>16.1}{:>20}",
+            value,
+            summary.ops as f64 / secs,
+            secs,
+            commit(summary, 0),
+            commit(summary, 1),
+            size
+        );
+        if let Some(written) = written {
+            print!(
+                "{:>12.1}{:>12.2}",
+                *written as f64 / secs / (1 << 20) as f64,
+                *size as f64 / (*written).max(1) as f64
+            );
+        }
+        println!();
+    }
+}
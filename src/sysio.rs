@@ -0,0 +1,183 @@
+//! System-level I/O counters from procfs, to compare what the engine was asked to write with what
+//! the process and the device holding the database actually did.
+//!
+//! Also throttling the writes of the process, in place of a cgroup I/O limit.
+
+use crate::cgroup::IoLimit;
+use std::{
+    os::unix::fs::MetadataExt,
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// The unit of the sector counts in `/proc/diskstats`, regardless of the device.
+const SECTOR: u64 = 512;
+
+/// What this process read and wrote from storage, per `/proc/self/io`.
+#[derive(Debug, Default, Copy, Clone)]
+struct ProcCounters {
+    read_bytes: u64,
+    write_bytes: u64,
+}
+
+/// What the device completed, per `/proc/diskstats`. Includes the I/O of other processes.
+#[derive(Debug, Default, Copy, Clone)]
+struct DevCounters {
+    reads: u64,
+    read_bytes: u64,
+    writes: u64,
+    write_bytes: u64,
+}
+
+/// Takes the counters at creation and reports the difference on demand.
+pub struct IoSampler {
+    /// The major and minor number of the device, `None` if it has no diskstats, like tmpfs.
+    dev: Option<(u64, u64)>,
+    start: (ProcCounters, Option<DevCounters>),
+    start_time: Instant,
+}
+
+impl IoSampler {
+    /// Returns `None` if procfs is not available.
+    pub fn new(db_path: &Path) -> Option<Self> {
+        let mut sampler = IoSampler {
+            dev: device(db_path),
+            start: Default::default(),
+            start_time: Instant::now(),
+        };
+        sampler.start = (read_proc().ok()?, sampler.read_dev());
+        if sampler.start.1.is_none() {
+            sampler.dev = None;
+        }
+        Some(sampler)
+    }
+
+    fn read_dev(&self) -> Option<DevCounters> {
+        let (major, minor) = self.dev?;
+        let diskstats = std::fs::read_to_string("/proc/diskstats").ok()?;
+        diskstats.lines().find_map(|line| {
+            let fields: Vec<u64> = line
+                .split_whitespace()
+                .filter_map(|field| field.parse().ok())
+                .collect();
+            // Skips the device name, the only field that is not a number.
+            match fields.as_slice() {
+                [maj, min, reads, _, read_sectors, _, writes, _, write_sectors, ..]
+                    if (*maj, *min) == (major, minor) =>
+                {
+                    Some(DevCounters {
+                        reads: *reads,
+                        read_bytes: read_sectors * SECTOR,
+                        writes: *writes,
+                        write_bytes: write_sectors * SECTOR,
+                    })
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// Returns the bytes this process wrote to storage since the sampler was created.
+    pub fn process_written(&self) -> anyhow::Result<u64> {
+        Ok(read_proc()?
+            .write_bytes
+            .saturating_sub(self.start.0.write_bytes))
+    }
+
+    /// Returns the I/O since the sampler was created, with the amplification over `written`
+    /// logical bytes.
+    pub fn report(&self, written: u64) -> anyhow::Result<String> {
+        let proc = read_proc()?;
+        let secs = self.start_time.elapsed().as_secs_f64();
+        let amp = |bytes: u64| bytes as f64 / written.max(1) as f64;
+
+        let proc_written = proc.write_bytes.saturating_sub(self.start.0.write_bytes);
+        let mut report = format!(
+            "process read {} written {} bytes ({:.2}x)",
+            proc.read_bytes.saturating_sub(self.start.0.read_bytes),
+            proc_written,
+            amp(proc_written)
+        );
+        if let (Some(start), Some(dev)) = (self.start.1, self.read_dev()) {
+            let dev_written = dev.write_bytes.saturating_sub(start.write_bytes);
+            report += &format!(
+                ", device read {} written {} bytes ({:.2}x), {:.0} read IOPS, {:.0} write IOPS",
+                dev.read_bytes.saturating_sub(start.read_bytes),
+                dev_written,
+                amp(dev_written),
+                dev.reads.saturating_sub(start.reads) as f64 / secs,
+                dev.writes.saturating_sub(start.writes) as f64 / secs
+            );
+        }
+        Ok(report)
+    }
+}
+
+/// Returns the major and minor number of the device holding `path`.
+pub fn device(path: &Path) -> Option<(u64, u64)> {
+    let dev = std::fs::metadata(path).ok()?.dev();
+    let major = ((dev >> 32) & 0xffff_f000) | ((dev >> 8) & 0xfff);
+    let minor = ((dev >> 12) & 0xffff_ff00) | (dev & 0xff);
+    Some((major, minor))
+}
+
+/// The write limits of [`throttle`] and what was written since they were set.
+struct Throttle {
+    limit: IoLimit,
+    start: Instant,
+    start_written: u64,
+    commits: u64,
+}
+
+static THROTTLE: Mutex<Option<Throttle>> = Mutex::new(None);
+
+/// Makes [`throttle`] hold the process to the write limits. Reads are not throttled.
+pub fn throttle_writes(limit: IoLimit) -> anyhow::Result<()> {
+    *THROTTLE.lock().unwrap() = Some(Throttle {
+        limit,
+        start: Instant::now(),
+        start_written: read_proc()?.write_bytes,
+        commits: 0,
+    });
+    Ok(())
+}
+
+/// Sleeps after a commit until the bytes the process wrote to storage and the commits, each at
+/// least one write with its sync, fit the limits. Concurrent writers wait for each other, like on
+/// a slow device.
+pub fn throttle() -> anyhow::Result<()> {
+    let mut throttle = THROTTLE.lock().unwrap();
+    let Some(throttle) = throttle.as_mut() else {
+        return Ok(());
+    };
+    throttle.commits += 1;
+    let written = read_proc()?
+        .write_bytes
+        .saturating_sub(throttle.start_written);
+    let mut due = Duration::ZERO;
+    if let Some(wbps) = throttle.limit.wbps {
+        due = due.max(Duration::from_secs_f64(written as f64 / wbps.max(1) as f64));
+    }
+    if let Some(wiops) = throttle.limit.wiops {
+        let commits = throttle.commits as f64;
+        due = due.max(Duration::from_secs_f64(commits / wiops.max(1) as f64));
+    }
+    if let Some(wait) = due.checked_sub(throttle.start.elapsed()) {
+        let _throttled = tracing::debug_span!("throttle").entered();
+        std::thread::sleep(wait);
+    }
+    Ok(())
+}
+
+fn read_proc() -> anyhow::Result<ProcCounters> {
+    let mut counters = ProcCounters::default();
+    for line in std::fs::read_to_string("/proc/self/io")?.lines() {
+        match line.split_once(": ") {
+            Some(("read_bytes", n)) => counters.read_bytes = n.parse()?,
+            Some(("write_bytes", n)) => counters.write_bytes = n.parse()?,
+            _ => {}
+        }
+    }
+    Ok(counters)
+}
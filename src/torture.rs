@@ -0,0 +1,219 @@
+//! Crash injection: a child process fills the database and is killed at a random point, then the
+//! database is reopened and checked.
+//!
+//! The child appends the index of every batch to a marker file right after committing it. Once
+//! the child is dead, every marked batch must be fully present, the batch after them may be
+//! committed but unmarked and so has to be all or nothing, and nothing after that may exist.
+
+use crate::{backend, keys::KeyGen, values, values::ValueDist, Cli, Entry, TortureOpts};
+use rand::Rng;
+use std::{
+    fs::OpenOptions,
+    io::{ErrorKind, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+/// Set in the environment of the child, which then fills instead of orchestrating.
+const CHILD_ENV: &str = "TORTURE_CHILD";
+
+/// The stream of the kill delays, distinct from the one of the data.
+const KILL_STREAM: u128 = 2 << 64;
+
+pub fn run(cli: &Cli, opts: &TortureOpts) -> anyhow::Result<()> {
+    if opts.n == 0 || opts.batch_sz == 0 {
+        anyhow::bail!("Invalid torture options: {:?}", opts);
+    }
+    if std::env::var_os(CHILD_ENV).is_some() {
+        return child(cli, opts);
+    }
+
+    crate::check_existing(cli)?;
+    if !cli.cont {
+        let _ = std::fs::remove_file(markers_path(cli));
+    }
+
+    let exe = std::env::current_exe()?;
+    let batches = opts.n.div_ceil(opts.batch_sz);
+    let mut rand = cli.rng(KILL_STREAM);
+    for round in 0..opts.rounds {
+        // The child gets the very same arguments, so it sees the same options.
+        let mut child = Command::new(&exe)
+            .args(std::env::args_os().skip(1))
+            .env(CHILD_ENV, "1")
+            .stdout(Stdio::null())
+            .spawn()?;
+
+        let delay = Duration::from_millis(rand.gen_range(0..=opts.max_kill_delay_ms));
+        let start = Instant::now();
+        let finished = loop {
+            if let Some(status) = child.try_wait()? {
+                if !status.success() {
+                    anyhow::bail!("Fill child failed: {}", status);
+                }
+                break true;
+            }
+            if start.elapsed() >= delay {
+                // SIGKILL, the child gets no chance to clean up.
+                child.kill()?;
+                child.wait()?;
+                break false;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        };
+
+        let committed = read_markers(cli)?;
+        check(cli, batches(cli, opts)?, committed)?;
+        println!(
+            "Round {}: {} after {:?}, {}/{} batches committed, verified",
+            round,
+            if finished { "finished" } else { "killed" },
+            start.elapsed(),
+            committed,
+            batches
+        );
+        if finished {
+            break;
+        }
+    }
+    Ok(())
+}
+
+pub fn markers_path(cli: &Cli) -> PathBuf {
+    format!("{}.markers", cli.path.trim_end_matches('/')).into()
+}
+
+/// Returns the number of batches marked as committed. A line cut short by the kill doesn't count.
+fn read_markers(cli: &Cli) -> anyhow::Result<usize> {
+    match std::fs::read_to_string(markers_path(cli)) {
+        Ok(markers) => Ok(markers.matches('\n').count()),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(0),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Generates the batches in order. Keys are always fresh, so the expected value of every key is
+/// known without knowing which batches made it.
+pub struct Batches {
+    rand: rand_pcg::Pcg64,
+    keys: KeyGen,
+    remaining: usize,
+    batch_sz: usize,
+    value_dist: ValueDist,
+    compressibility: f64,
+    checksum_values: bool,
+    /// The number of items generated so far.
+    seq: u64,
+}
+
+impl Batches {
+    pub fn new(
+        cli: &Cli,
+        n: usize,
+        batch_sz: usize,
+        value_dist: ValueDist,
+    ) -> anyhow::Result<Self> {
+        Ok(Batches {
+            rand: cli.rng(0),
+            keys: KeyGen::new(cli, 0, 1)?,
+            remaining: n,
+            batch_sz,
+            value_dist,
+            compressibility: cli.compressibility,
+            checksum_values: cli.checksum_values,
+            seq: 0,
+        })
+    }
+}
+
+impl Iterator for Batches {
+    type Item = Vec<Entry>;
+
+    fn next(&mut self) -> Option<Vec<Entry>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let batch_sz = self.batch_sz.min(self.remaining);
+        self.remaining -= batch_sz;
+        let batch = (0..batch_sz)
+            .map(|_| {
+                let key = self.keys.fresh(&mut self.rand);
+                let mut value = vec![0; self.value_dist.sample(&mut self.rand)];
+                values::fill(&mut self.rand, &mut value, self.compressibility);
+                if self.checksum_values {
+                    values::seal(&key, &mut value, self.seq);
+                }
+                self.seq += 1;
+                (key, value)
+            })
+            .collect();
+        Some(batch)
+    }
+}
+
+fn batches(cli: &Cli, opts: &TortureOpts) -> anyhow::Result<Batches> {
+    Batches::new(cli, opts.n, opts.batch_sz, opts.value_dist)
+}
+
+fn child(cli: &Cli, opts: &TortureOpts) -> anyhow::Result<()> {
+    let committed = read_markers(cli)?;
+    let env = backend::open(cli)?;
+    let mut markers = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(markers_path(cli))?;
+
+    // The batch after the marked ones might have been committed already, rewriting it is a no-op.
+    for (batch, items) in batches(cli, opts)?.enumerate().skip(committed) {
+        let mut txn = env.begin()?;
+        for (key, value) in items {
+            txn.put(cli.table(&key), &key, &value)?;
+        }
+        txn.commit()?;
+        // A single write, so a kill can only cut the line short, not interleave it.
+        markers.write_all(format!("{}\n", batch).as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Checks that the first `committed` batches are present, the next one is all or nothing and the
+/// rest is absent.
+pub fn check(cli: &Cli, batches: Batches, committed: usize) -> anyhow::Result<()> {
+    let env = backend::open(cli)?;
+    let txn = env.begin_read()?;
+    for (batch, items) in batches.enumerate().take(committed + 2) {
+        let mut present = 0;
+        for (key, value) in &items {
+            match txn.get(cli.table(key), key)? {
+                Some(found) if found == *value => present += 1,
+                Some(_) => anyhow::bail!("Batch {} has a corrupted value", batch),
+                None => {}
+            }
+        }
+        if batch < committed && present != items.len() {
+            anyhow::bail!(
+                "Batch {} was committed, but {} of {} items are missing",
+                batch,
+                items.len() - present,
+                items.len()
+            );
+        }
+        if batch == committed && present != 0 && present != items.len() {
+            anyhow::bail!(
+                "Batch {} was applied partially, {} of {} items are present",
+                batch,
+                present,
+                items.len()
+            );
+        }
+        if batch > committed && present != 0 {
+            anyhow::bail!(
+                "Batch {} was never written, but {} items are present",
+                batch,
+                present
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,123 @@
+//! Sizes and durations on the command line.
+
+use std::{fmt, str::FromStr, time::Duration};
+
+/// A number of bytes, optionally with a decimal (`KB`, `MB`, `GB`, `TB`) or binary (`KiB`, `MiB`,
+/// `GiB`, `TiB`) unit, like `2TiB` or `500GB`.
+#[derive(Debug, Copy, Clone)]
+pub struct ByteSize(pub u64);
+
+impl FromStr for ByteSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (n, unit) = s.split_at(split);
+        let unit: u64 = match unit.trim() {
+            "" | "B" => 1,
+            "KB" => 1000,
+            "MB" => 1_000_000,
+            "GB" => 1_000_000_000,
+            "TB" => 1_000_000_000_000,
+            "KiB" => 1 << 10,
+            "MiB" => 1 << 20,
+            "GiB" => 1 << 30,
+            "TiB" => 1 << 40,
+            _ => anyhow::bail!("Unknown size unit: {}", s),
+        };
+        let n: u64 = n.parse()?;
+        match n.checked_mul(unit) {
+            Some(bytes) => Ok(ByteSize(bytes)),
+            None => anyhow::bail!("Size is too large: {}", s),
+        }
+    }
+}
+
+/// Prints the largest binary unit that divides the size, so that it parses back to the same.
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (unit, name) in [
+            (1 << 40, "TiB"),
+            (1 << 30, "GiB"),
+            (1 << 20, "MiB"),
+            (1 << 10, "KiB"),
+        ] {
+            if self.0 >= unit && self.0 % unit == 0 {
+                return write!(f, "{}{}", self.0 / unit, name);
+            }
+        }
+        write!(f, "{}B", self.0)
+    }
+}
+
+/// A duration in whole seconds (`s`), minutes (`m`), hours (`h`) or days (`d`), like `90s` or
+/// `8h`. A plain number is in seconds.
+#[derive(Debug, Copy, Clone)]
+pub struct Span(pub Duration);
+
+impl FromStr for Span {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (n, unit) = s.split_at(split);
+        let unit: u64 = match unit.trim() {
+            "" | "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            _ => anyhow::bail!("Unknown duration unit: {}", s),
+        };
+        let n: u64 = n.parse()?;
+        match n.checked_mul(unit) {
+            Some(secs) => Ok(Span(Duration::from_secs(secs))),
+            None => anyhow::bail!("Duration is too long: {}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(s: &str) -> anyhow::Result<u64> {
+        Ok(s.parse::<ByteSize>()?.0)
+    }
+
+    fn secs(s: &str) -> anyhow::Result<u64> {
+        Ok(s.parse::<Span>()?.0.as_secs())
+    }
+
+    #[test]
+    fn parses_sizes() -> anyhow::Result<()> {
+        assert_eq!(bytes("4096")?, 4096);
+        assert_eq!(bytes("500GB")?, 500_000_000_000);
+        assert_eq!(bytes("2TiB")?, 2 << 40);
+        assert_eq!(bytes("64 KiB")?, 64 << 10);
+        assert!(bytes("1XB").is_err());
+        assert!(bytes("KiB").is_err());
+        assert!(bytes("99999999999TiB").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn prints_sizes_that_parse_back() -> anyhow::Result<()> {
+        for size in [0, 1500, 3 << 30, (1 << 20) + (1 << 10)] {
+            assert_eq!(bytes(&ByteSize(size).to_string())?, size);
+        }
+        assert_eq!(ByteSize(3 << 30).to_string(), "3GiB");
+        assert_eq!(ByteSize(1500).to_string(), "1500B");
+        Ok(())
+    }
+
+    #[test]
+    fn parses_spans() -> anyhow::Result<()> {
+        assert_eq!(secs("5")?, 5);
+        assert_eq!(secs("90s")?, 90);
+        assert_eq!(secs("8h")?, 8 * 3600);
+        assert_eq!(secs("2d")?, 2 * 86400);
+        assert!(secs("1w").is_err());
+        assert!(secs("1.5h").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,205 @@
+//! Value generation.
+
+use crate::units::ByteSize;
+use rand::{Rng, RngCore};
+use rand_distr::{Distribution, LogNormal};
+use std::str::FromStr;
+
+/// The distribution of value sizes in bytes.
+#[derive(Debug, Copy, Clone)]
+pub enum ValueDist {
+    Fixed(usize),
+    /// Uniform between the bounds, both inclusive.
+    Uniform(usize, usize),
+    /// Log-normal with the given median and the standard deviation of the logarithm.
+    LogNormal(LogNormal<f64>),
+    /// `small` bytes, or `large` bytes with the given probability.
+    Bimodal {
+        small: usize,
+        large: usize,
+        large_pct: f64,
+    },
+}
+
+impl ValueDist {
+    /// Returns the mean size, estimated from a sample as the log-normal doesn't expose its
+    /// parameters.
+    pub fn mean(&self) -> f64 {
+        let mut rand = rand_pcg::Pcg64::new(0, 0);
+        let samples = 1024;
+        (0..samples)
+            .map(|_| self.sample(&mut rand) as f64)
+            .sum::<f64>()
+            / samples as f64
+    }
+
+    pub fn sample(&self, rand: &mut impl Rng) -> usize {
+        match *self {
+            ValueDist::Fixed(sz) => sz,
+            ValueDist::Uniform(min, max) => rand.gen_range(min..=max),
+            ValueDist::LogNormal(dist) => dist.sample(rand).round() as usize,
+            ValueDist::Bimodal {
+                small,
+                large,
+                large_pct,
+            } => {
+                if rand.gen_bool(large_pct) {
+                    large
+                } else {
+                    small
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for ValueDist {
+    type Err = anyhow::Error;
+
+    /// Parses `fixed:<size>`, `uniform:<min>..<max>`, `lognormal:<median>,<sigma>` or
+    /// `bimodal:<small>,<large>,<large fraction>`. A bare size is a fixed one. Sizes may have a
+    /// unit, like `256KiB`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((kind, params)) = s.split_once(':') else {
+            return Ok(ValueDist::Fixed(size(s)?));
+        };
+        let params: Vec<&str> = params.split(',').collect();
+        match (kind, params.as_slice()) {
+            ("fixed", [sz]) => Ok(ValueDist::Fixed(size(sz)?)),
+            ("uniform", [range]) => {
+                let Some((min, max)) = range.split_once("..") else {
+                    anyhow::bail!("Expected a range like 64..4096: {}", range);
+                };
+                let (min, max) = (size(min)?, size(max)?);
+                if min > max {
+                    anyhow::bail!("Empty value size range: {}", range);
+                }
+                Ok(ValueDist::Uniform(min, max))
+            }
+            ("lognormal", [median, sigma]) => {
+                let median: f64 = median.parse()?;
+                let dist = LogNormal::new(median.ln(), sigma.parse()?)?;
+                Ok(ValueDist::LogNormal(dist))
+            }
+            ("bimodal", [small, large, large_pct]) => {
+                let large_pct: f64 = large_pct.parse()?;
+                if !(0.0..=1.0).contains(&large_pct) {
+                    anyhow::bail!("Large value fraction must be within 0..=1: {}", large_pct);
+                }
+                Ok(ValueDist::Bimodal {
+                    small: size(small)?,
+                    large: size(large)?,
+                    large_pct,
+                })
+            }
+            _ => anyhow::bail!("Unknown value distribution: {}", s),
+        }
+    }
+}
+
+fn size(s: &str) -> anyhow::Result<usize> {
+    Ok(s.parse::<ByteSize>()?.0.try_into()?)
+}
+
+/// Fills `buf` so that about the `compressibility` fraction of it can be compressed away: random
+/// bytes followed by copies of them, like db_bench does.
+pub fn fill(rand: &mut impl RngCore, buf: &mut [u8], compressibility: f64) {
+    let random = (buf.len() as f64 * (1.0 - compressibility)).ceil() as usize;
+    let (head, tail) = buf.split_at_mut(random.min(buf.len()));
+    rand.fill_bytes(head);
+    if head.is_empty() {
+        return;
+    }
+    for chunk in tail.chunks_mut(head.len()) {
+        chunk.copy_from_slice(&head[..chunk.len()]);
+    }
+}
+
+/// The bytes at the end of a sealed value: a sequence number and a checksum, both little-endian.
+pub const SEAL_SZ: usize = 16;
+
+fn checksum(key: &[u8], seq: u64, payload: &[u8]) -> u64 {
+    let bytes = key.iter().chain(&seq.to_le_bytes()).chain(payload);
+    bytes.fold(0xcbf29ce484222325, |hash: u64, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Overwrites the end of a value with the sequence number of the write and a checksum of the key,
+/// the sequence number and the rest of the value. Values shorter than [`SEAL_SZ`] are left alone.
+pub fn seal(key: &[u8], value: &mut [u8], seq: u64) {
+    let Some(split) = value.len().checked_sub(SEAL_SZ) else {
+        return;
+    };
+    let (payload, tail) = value.split_at_mut(split);
+    let checksum = checksum(key, seq, payload);
+    tail[..8].copy_from_slice(&seq.to_le_bytes());
+    tail[8..].copy_from_slice(&checksum.to_le_bytes());
+}
+
+/// Checks a value sealed with [`seal`] under the given key.
+pub fn check_seal(key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+    let Some(split) = value.len().checked_sub(SEAL_SZ) else {
+        return Ok(());
+    };
+    let (payload, tail) = value.split_at(split);
+    let seq = u64::from_le_bytes(tail[..8].try_into().unwrap());
+    let stored = u64::from_le_bytes(tail[8..].try_into().unwrap());
+    let computed = checksum(key, seq, payload);
+    if stored != computed {
+        anyhow::bail!(
+            "Corrupted value of key {:02x?}: {} bytes, written as #{}, checksum {:#x} but the \
+             content hashes to {:#x}, value {:02x?}",
+            key,
+            value.len(),
+            seq,
+            stored,
+            computed,
+            value
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_value_dists() -> anyhow::Result<()> {
+        assert!(matches!("32".parse()?, ValueDist::Fixed(32)));
+        assert!(matches!("fixed:4KiB".parse()?, ValueDist::Fixed(4096)));
+        assert!(matches!(
+            "uniform:64..4096".parse()?,
+            ValueDist::Uniform(64, 4096)
+        ));
+        assert!(matches!(
+            "bimodal:100,1MiB,0.1".parse()?,
+            ValueDist::Bimodal {
+                small: 100,
+                large: 0x100000,
+                ..
+            }
+        ));
+        assert!("uniform:4096..64".parse::<ValueDist>().is_err());
+        assert!("uniform:64".parse::<ValueDist>().is_err());
+        assert!("bimodal:1,2,1.5".parse::<ValueDist>().is_err());
+        assert!("lognormal:100".parse::<ValueDist>().is_err());
+        assert!("pareto:1".parse::<ValueDist>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn samples_within_the_dist() -> anyhow::Result<()> {
+        let mut rand = rand_pcg::Pcg64::new(0, 0);
+        let uniform: ValueDist = "uniform:64..128".parse()?;
+        let bimodal: ValueDist = "bimodal:10,1000,0.5".parse()?;
+        for _ in 0..1000 {
+            assert!((64..=128).contains(&uniform.sample(&mut rand)));
+            assert!([10, 1000].contains(&bimodal.sample(&mut rand)));
+        }
+        let lognormal: ValueDist = "lognormal:1000,0.1".parse()?;
+        assert!((900.0..1100.0).contains(&lognormal.mean()));
+        Ok(())
+    }
+}
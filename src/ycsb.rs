@@ -0,0 +1,87 @@
+//! The standard YCSB core workloads as presets of the mixed workload.
+//!
+//! Only the operation mix and the request distribution are set, the records keep the configured
+//! key and value sizes. YCSB uses 1000 byte records, `-v fixed:1000` for comparable numbers.
+
+use crate::keys::KeyDist;
+use rand::Rng;
+use std::str::FromStr;
+
+/// The longest scan of workload E, the actual lengths are uniform up to it.
+pub const MAX_SCAN_LEN: usize = 100;
+
+#[derive(Debug, Copy, Clone)]
+pub enum Workload {
+    /// Update heavy: 50% reads, 50% updates.
+    A,
+    /// Read mostly: 95% reads, 5% updates.
+    B,
+    /// Read only.
+    C,
+    /// Read latest: 95% reads, 5% inserts, the recently inserted keys are the popular ones.
+    D,
+    /// Short ranges: 95% scans, 5% inserts.
+    E,
+    /// Read-modify-write: 50% reads, 50% read-modify-writes.
+    F,
+}
+
+impl FromStr for Workload {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ycsb-a" => Ok(Workload::A),
+            "ycsb-b" => Ok(Workload::B),
+            "ycsb-c" => Ok(Workload::C),
+            "ycsb-d" => Ok(Workload::D),
+            "ycsb-e" => Ok(Workload::E),
+            "ycsb-f" => Ok(Workload::F),
+            _ => anyhow::bail!("Unknown workload: {}", s),
+        }
+    }
+}
+
+/// An operation of the mixed workload.
+#[derive(Debug, Copy, Clone)]
+pub enum Op {
+    Read,
+    Update,
+    Insert,
+    Scan,
+    ReadModifyWrite,
+}
+
+impl Workload {
+    /// The operations and their fractions.
+    fn mix(self) -> &'static [(Op, f64)] {
+        match self {
+            Workload::A => &[(Op::Read, 0.5), (Op::Update, 0.5)],
+            Workload::B => &[(Op::Read, 0.95), (Op::Update, 0.05)],
+            Workload::C => &[(Op::Read, 1.0)],
+            Workload::D => &[(Op::Read, 0.95), (Op::Insert, 0.05)],
+            Workload::E => &[(Op::Scan, 0.95), (Op::Insert, 0.05)],
+            Workload::F => &[(Op::Read, 0.5), (Op::ReadModifyWrite, 0.5)],
+        }
+    }
+
+    /// The request distribution, with YCSB's default zipfian constant.
+    pub fn key_dist(self) -> KeyDist {
+        match self {
+            Workload::D => KeyDist::Latest(0.99),
+            _ => KeyDist::Zipf(0.99),
+        }
+    }
+
+    pub fn pick(self, rand: &mut impl Rng) -> Op {
+        let mut x = rand.gen::<f64>();
+        let mix = self.mix();
+        for &(op, fraction) in mix {
+            if x < fraction {
+                return op;
+            }
+            x -= fraction;
+        }
+        mix[mix.len() - 1].0
+    }
+}